@@ -0,0 +1,463 @@
+//! Typed private account data: our own orders, fills, positions and balances,
+//! converted from OKX's `orders`, `positions`, `account` and
+//! `balance_and_position` pushes. Timestamps are Unix milliseconds.
+
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::models::{AccountData, BalanceAndPositionData, OrderData, PositionData};
+use crate::strategy::{OrderFill, Side};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrderState {
+    Live,
+    PartiallyFilled,
+    Filled,
+    Canceled,
+    /// Cancelled by market maker protection.
+    MmpCanceled,
+}
+
+impl OrderState {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "live" => Some(OrderState::Live),
+            "partially_filled" => Some(OrderState::PartiallyFilled),
+            "filled" => Some(OrderState::Filled),
+            "canceled" => Some(OrderState::Canceled),
+            "mmp_canceled" => Some(OrderState::MmpCanceled),
+            _ => None,
+        }
+    }
+}
+
+/// One execution against one of our orders.
+#[derive(Debug, Clone)]
+pub struct Execution {
+    pub trade_id: String,
+    pub price: f64,
+    pub size: f64,
+    /// Negative when charged, positive for rebates (OKX's sign convention).
+    pub fee: f64,
+    pub fee_ccy: String,
+    /// Realised PnL of this fill when it reduces a position.
+    pub pnl: f64,
+    /// True for a maker fill.
+    pub maker: bool,
+}
+
+/// Current state of one of our orders.
+#[derive(Debug, Clone)]
+pub struct OrderUpdate {
+    pub inst_id: String,
+    pub ord_id: String,
+    pub cl_ord_id: String,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub state: OrderState,
+    pub filled: f64,
+    pub avg_price: f64,
+    /// The fill behind this update, if it was caused by one.
+    pub execution: Option<Execution>,
+    pub ts: u64,
+}
+
+impl OrderUpdate {
+    pub fn from_okx(data: &OrderData) -> Option<Self> {
+        let execution = if data.tradeId.is_empty() {
+            None
+        } else {
+            Some(Execution {
+                trade_id: data.tradeId.clone(),
+                price: data.fillPx.parse().ok()?,
+                size: data.fillSz.parse().ok()?,
+                fee: decimal(&data.fillFee),
+                fee_ccy: data.fillFeeCcy.clone().unwrap_or_default(),
+                pnl: decimal(&data.fillPnl),
+                maker: data.execType.as_deref() == Some("M"),
+            })
+        };
+        Some(Self {
+            inst_id: data.instId.clone(),
+            ord_id: data.ordId.clone(),
+            cl_ord_id: data.clOrdId.clone(),
+            side: parse_side(&data.side)?,
+            // market orders have no price
+            price: data.px.parse().unwrap_or(0.0),
+            size: data.sz.parse().ok()?,
+            state: OrderState::from_name(&data.state)?,
+            filled: data.accFillSz.parse().unwrap_or(0.0),
+            avg_price: data.avgPx.parse().unwrap_or(0.0),
+            execution,
+            ts: data.uTime.parse().ok()?,
+        })
+    }
+
+    /// The execution as an `OrderFill` for `Strategy::on_order_filled`. The id is
+    /// the client order id when it's a UUID, otherwise derived from OKX's `ordId`.
+    pub fn to_fill(&self) -> Option<OrderFill> {
+        let exec = self.execution.as_ref()?;
+        let id = Uuid::try_parse(&self.cl_ord_id)
+            .ok()
+            .or_else(|| self.ord_id.parse::<u128>().ok().map(Uuid::from_u128))
+            .unwrap_or_else(Uuid::nil);
+        Some(OrderFill {
+            id,
+            side: self.side,
+            entry_price: exec.price,
+            exit_price: exec.price,
+            size: exec.size,
+            pnl: exec.pnl,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PosSide {
+    /// One-way (net) mode; the sign of `pos` gives the direction.
+    Net,
+    Long,
+    Short,
+}
+
+#[derive(Debug, Clone)]
+pub struct Position {
+    pub inst_id: String,
+    pub pos_side: PosSide,
+    /// `cross` or `isolated`.
+    pub margin_mode: String,
+    /// Only on the `positions` channel.
+    pub leverage: Option<f64>,
+    /// Contracts; negative for a net short.
+    pub pos: f64,
+    pub avg_price: f64,
+    pub upl: Option<f64>,
+    pub liq_price: Option<f64>,
+    pub mark: Option<f64>,
+}
+
+impl Position {
+    pub fn from_okx(data: &PositionData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            pos_side: parse_pos_side(&data.posSide)?,
+            margin_mode: data.mgnMode.clone(),
+            leverage: optional(&data.lever),
+            pos: data.pos.parse().unwrap_or(0.0),
+            avg_price: data.avgPx.parse().unwrap_or(0.0),
+            upl: optional(&data.upl),
+            liq_price: optional(&data.liqPx),
+            mark: optional(&data.markPx),
+        })
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {:?} {} @ {} {}", self.inst_id, self.pos_side, self.pos, self.avg_price, self.margin_mode)?;
+        if let Some(leverage) = self.leverage {
+            write!(f, " {}x", leverage)?;
+        }
+        if let Some(upl) = self.upl {
+            write!(f, ", upl {}", upl)?;
+        }
+        if let Some(liq) = self.liq_price {
+            write!(f, ", liq {}", liq)?;
+        }
+        if let Some(mark) = self.mark {
+            write!(f, ", mark {}", mark)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Balance {
+    pub ccy: String,
+    pub cash: f64,
+    /// Only on the `account` channel.
+    pub equity: Option<f64>,
+    pub available: Option<f64>,
+    pub frozen: Option<f64>,
+}
+
+impl fmt::Display for Balance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} cash {}", self.ccy, self.cash)?;
+        if let Some(equity) = self.equity {
+            write!(f, ", eq {}", equity)?;
+        }
+        if let Some(available) = self.available {
+            write!(f, ", avail {}", available)?;
+        }
+        if let Some(frozen) = self.frozen {
+            write!(f, ", frozen {}", frozen)?;
+        }
+        Ok(())
+    }
+}
+
+/// Balances after an account event. `balance_and_position` only carries cash
+/// balances for the currencies that changed.
+#[derive(Debug, Clone)]
+pub struct AccountUpdate {
+    /// What changed the balances (`filled`, `transferred`, `funding_fee`, ...);
+    /// only on `balance_and_position`.
+    pub event: Option<String>,
+    pub total_equity: Option<f64>,
+    pub balances: Vec<Balance>,
+}
+
+impl AccountUpdate {
+    pub fn from_okx(data: &AccountData) -> Option<Self> {
+        Some(Self {
+            event: None,
+            total_equity: data.totalEq.parse().ok(),
+            balances: data
+                .details
+                .iter()
+                .filter_map(|d| {
+                    Some(Balance {
+                        ccy: d.ccy.clone(),
+                        cash: d.cashBal.parse().ok()?,
+                        equity: d.eq.parse().ok(),
+                        available: d.availBal.parse().ok(),
+                        frozen: d.frozenBal.parse().ok(),
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Balances and positions from one `balance_and_position` push.
+    pub fn from_balance_and_position(data: &BalanceAndPositionData) -> Option<(Self, Vec<Position>)> {
+        let balances = data
+            .balData
+            .iter()
+            .filter_map(|b| {
+                Some(Balance { ccy: b.ccy.clone(), cash: b.cashBal.parse().ok()?, equity: None, available: None, frozen: None })
+            })
+            .collect();
+        let positions = data
+            .posData
+            .iter()
+            .filter_map(|p| {
+                Some(Position {
+                    inst_id: p.instId.clone(),
+                    pos_side: parse_pos_side(&p.posSide)?,
+                    margin_mode: p.mgnMode.clone(),
+                    leverage: None,
+                    pos: p.pos.parse().unwrap_or(0.0),
+                    avg_price: p.avgPx.parse().unwrap_or(0.0),
+                    upl: None,
+                    liq_price: None,
+                    mark: None,
+                })
+            })
+            .collect();
+        Some((Self { event: Some(data.eventType.clone()), total_equity: None, balances }, positions))
+    }
+}
+
+impl fmt::Display for AccountUpdate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(event) = &self.event {
+            write!(f, "{}: ", event)?;
+        }
+        if let Some(equity) = self.total_equity {
+            write!(f, "equity {}; ", equity)?;
+        }
+        let balances: Vec<_> = self.balances.iter().map(Balance::to_string).collect();
+        write!(f, "{}", balances.join("; "))
+    }
+}
+
+fn parse_side(side: &str) -> Option<Side> {
+    match side {
+        "buy" => Some(Side::Buy),
+        "sell" => Some(Side::Sell),
+        _ => None,
+    }
+}
+
+fn parse_pos_side(side: &str) -> Option<PosSide> {
+    match side {
+        "net" | "" => Some(PosSide::Net),
+        "long" => Some(PosSide::Long),
+        "short" => Some(PosSide::Short),
+        _ => None,
+    }
+}
+
+/// OKX sends `""` for numbers that don't apply yet.
+fn optional(value: &Option<String>) -> Option<f64> {
+    value.as_deref()?.parse().ok()
+}
+
+fn decimal(value: &Option<String>) -> f64 {
+    optional(value).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn order_data(overrides: serde_json::Value) -> OrderData {
+        let mut data = serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "ordId": "312269865356374016",
+            "clOrdId": "0f5e8c2a7b3d4e1f9a6b5c4d3e2f1a0b",
+            "px": "42000",
+            "sz": "2",
+            "ordType": "post_only",
+            "side": "buy",
+            "posSide": "net",
+            "state": "partially_filled",
+            "accFillSz": "1",
+            "avgPx": "42000",
+            "fillPx": "41999.5",
+            "fillSz": "1",
+            "fillTime": "1597026383085",
+            "tradeId": "242589207",
+            "fillFee": "-0.08",
+            "fillFeeCcy": "USDT",
+            "fillPnl": "1.5",
+            "execType": "M",
+            "uTime": "1597026383085",
+            "cTime": "1597026383000",
+        });
+        for (key, value) in overrides.as_object().unwrap() {
+            data[key] = value.clone();
+        }
+        serde_json::from_value(data).unwrap()
+    }
+
+    #[test]
+    fn fills_become_executions() {
+        let update = OrderUpdate::from_okx(&order_data(serde_json::json!({}))).unwrap();
+        assert_eq!(update.state, OrderState::PartiallyFilled);
+        assert_eq!((update.side, update.price, update.size, update.filled), (Side::Buy, 42000.0, 2.0, 1.0));
+        let exec = update.execution.unwrap();
+        assert_eq!(exec.trade_id, "242589207");
+        assert_eq!((exec.price, exec.size, exec.fee, exec.pnl), (41999.5, 1.0, -0.08, 1.5));
+        assert_eq!(exec.fee_ccy, "USDT");
+        assert!(exec.maker);
+
+        let taker = OrderUpdate::from_okx(&order_data(serde_json::json!({ "execType": "T", "fillFee": "" }))).unwrap();
+        let exec = taker.execution.unwrap();
+        assert!(!exec.maker);
+        assert_eq!(exec.fee, 0.0);
+    }
+
+    #[test]
+    fn updates_without_a_trade_id_carry_no_execution() {
+        let ack = order_data(serde_json::json!({ "state": "live", "tradeId": "", "fillPx": "", "fillSz": "", "fillTime": "" }));
+        let update = OrderUpdate::from_okx(&ack).unwrap();
+        assert_eq!(update.state, OrderState::Live);
+        assert!(update.execution.is_none());
+        assert!(update.to_fill().is_none());
+    }
+
+    #[test]
+    fn orders_placed_elsewhere_have_an_empty_client_id() {
+        let update = OrderUpdate::from_okx(&order_data(serde_json::json!({ "clOrdId": "" }))).unwrap();
+        assert_eq!(update.cl_ord_id, "");
+        assert_eq!(update.to_fill().unwrap().id, Uuid::from_u128(312269865356374016));
+    }
+
+    #[test]
+    fn mmp_cancels_and_unknown_states_are_told_apart() {
+        let mmp = order_data(serde_json::json!({ "state": "mmp_canceled", "tradeId": "" }));
+        assert_eq!(OrderUpdate::from_okx(&mmp).unwrap().state, OrderState::MmpCanceled);
+        assert!(OrderUpdate::from_okx(&order_data(serde_json::json!({ "state": "expired" }))).is_none());
+        assert!(OrderUpdate::from_okx(&order_data(serde_json::json!({ "side": "both" }))).is_none());
+    }
+
+    #[test]
+    fn fills_keep_our_client_id_and_the_execution_price() {
+        let update = OrderUpdate::from_okx(&order_data(serde_json::json!({ "side": "sell" }))).unwrap();
+        let fill = update.to_fill().unwrap();
+        assert_eq!(fill.id, Uuid::try_parse("0f5e8c2a7b3d4e1f9a6b5c4d3e2f1a0b").unwrap());
+        assert_eq!(fill.side, Side::Sell);
+        assert_eq!((fill.entry_price, fill.exit_price, fill.size, fill.pnl), (41999.5, 41999.5, 1.0, 1.5));
+
+        // neither a UUID nor a numeric ordId
+        let odd = OrderUpdate { cl_ord_id: "mine".to_string(), ord_id: "x".to_string(), ..update };
+        assert_eq!(odd.to_fill().unwrap().id, Uuid::nil());
+    }
+
+    #[test]
+    fn positions_parse_with_optional_risk_fields() {
+        let data: PositionData = serde_json::from_value(serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "posId": "307173036051017730",
+            "posSide": "net",
+            "pos": "-3",
+            "avgPx": "42000",
+            "upl": "-12.5",
+            "liqPx": "",
+            "markPx": "42004.1",
+            "lever": "10",
+            "mgnMode": "cross",
+            "uTime": "1597026383085",
+        }))
+        .unwrap();
+        let position = Position::from_okx(&data).unwrap();
+        assert_eq!(position.inst_id, "BTC-USDT-SWAP");
+        assert_eq!(position.pos_side, PosSide::Net);
+        assert_eq!((position.pos, position.avg_price), (-3.0, 42000.0));
+        assert_eq!((position.upl, position.liq_price, position.mark), (Some(-12.5), None, Some(42004.1)));
+        assert_eq!(position.to_string(), "BTC-USDT-SWAP Net -3 @ 42000 cross 10x, upl -12.5, mark 42004.1");
+    }
+
+    #[test]
+    fn account_pushes_carry_equity_and_balances() {
+        let data: AccountData = serde_json::from_value(serde_json::json!({
+            "totalEq": "41624.32",
+            "uTime": "1597026383085",
+            "details": [
+                { "ccy": "USDT", "eq": "41624.32", "cashBal": "41624.32", "availBal": "41000", "frozenBal": "624.32", "uTime": "1597026383085" },
+                { "ccy": "BTC", "eq": "", "cashBal": "", "availBal": "", "frozenBal": "", "uTime": "1597026383085" },
+            ],
+        }))
+        .unwrap();
+        let update = AccountUpdate::from_okx(&data).unwrap();
+        assert_eq!(update.total_equity, Some(41624.32));
+        // a balance without cash is skipped
+        assert_eq!(update.balances.len(), 1);
+        let usdt = &update.balances[0];
+        assert_eq!((usdt.ccy.as_str(), usdt.cash), ("USDT", 41624.32));
+        assert_eq!((usdt.equity, usdt.available, usdt.frozen), (Some(41624.32), Some(41000.0), Some(624.32)));
+        assert_eq!(update.to_string(), "equity 41624.32; USDT cash 41624.32, eq 41624.32, avail 41000, frozen 624.32");
+    }
+
+    #[test]
+    fn balance_and_position_splits_into_balances_and_positions() {
+        let data: BalanceAndPositionData = serde_json::from_value(serde_json::json!({
+            "pTime": "1597026383085",
+            "eventType": "filled",
+            "balData": [{ "ccy": "USDT", "cashBal": "41624.32", "uTime": "1597026383085" }],
+            "posData": [{
+                "posId": "1",
+                "instId": "BTC-USDT-SWAP",
+                "instType": "SWAP",
+                "mgnMode": "isolated",
+                "posSide": "long",
+                "pos": "1",
+                "avgPx": "42000",
+                "uTime": "1597026383085",
+            }],
+        }))
+        .unwrap();
+        let (update, positions) = AccountUpdate::from_balance_and_position(&data).unwrap();
+        assert_eq!(update.total_equity, None);
+        assert_eq!(update.to_string(), "filled: USDT cash 41624.32");
+        assert_eq!(positions.len(), 1);
+        assert_eq!((positions[0].pos_side, positions[0].pos, positions[0].upl), (PosSide::Long, 1.0, None));
+        assert_eq!(positions[0].to_string(), "BTC-USDT-SWAP Long 1 @ 42000 isolated");
+    }
+}
@@ -0,0 +1,455 @@
+//! Backtest accounting: follows our order updates and the marks of the
+//! instruments we trade, and turns them into inventory, PnL, fee, turnover and
+//! fill statistics plus a sampled equity curve. It only reads `OrderUpdate`s,
+//! so it works the same behind the paper venue in a replay as behind OKX.
+//!
+//! PnL is in the quote (settlement) currency of linear instruments and spot,
+//! and instruments are summed as if they shared it. Inverse contracts settle
+//! in coin and aren't accounted for: `--backtest` refuses them. Realised PnL
+//! uses average cost; fees follow OKX's sign (negative when charged).
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::account::OrderUpdate;
+use crate::orderbook::OrderBook;
+use crate::strategy::Side;
+
+pub const REPORT_FILE: &str = "report.json";
+pub const EQUITY_FILE: &str = "equity.csv";
+
+const YEAR_MS: f64 = 365.0 * 24.0 * 3600.0 * 1000.0;
+
+/// Positions smaller than this many contracts are flat.
+const FLAT: f64 = 1e-9;
+
+/// Running totals for one instrument.
+#[derive(Debug, Clone, Default)]
+struct Book {
+    contract_size: f64,
+    /// Signed, in contracts (base units for spot).
+    position: f64,
+    avg_entry: f64,
+    mark: Option<f64>,
+    realised: f64,
+    fees: f64,
+    turnover: f64,
+    volume: f64,
+    fills: u64,
+    maker_fills: u64,
+    /// Largest size each order was quoted at, by clOrdId.
+    orders: HashMap<String, f64>,
+    filled_orders: HashSet<String>,
+    size_filled: f64,
+    max_position: f64,
+    exposure_sum: f64,
+    max_exposure: f64,
+    /// Cost and price impact of flattening the position against the last book, in bps of its mid.
+    exit_slippage_bps: Option<f64>,
+    exit_impact_bps: Option<f64>,
+}
+
+impl Book {
+    fn unrealised(&self) -> f64 {
+        self.mark.map_or(0.0, |mark| self.position * (mark - self.avg_entry) * self.contract_size)
+    }
+
+    fn exposure(&self) -> f64 {
+        self.mark.map_or(0.0, |mark| self.position * mark * self.contract_size)
+    }
+
+    fn fill(&mut self, side: Side, price: f64, size: f64) {
+        let signed = match side {
+            Side::Buy => size,
+            Side::Sell => -size,
+        };
+        if self.position.abs() < FLAT || self.position.signum() == signed.signum() {
+            let held = self.position.abs();
+            self.avg_entry = (self.avg_entry * held + price * size) / (held + size);
+        } else {
+            let closed = size.min(self.position.abs());
+            self.realised += closed * (price - self.avg_entry) * self.position.signum() * self.contract_size;
+            if size > self.position.abs() {
+                // flipped: what's left over opened a new position here
+                self.avg_entry = price;
+            }
+        }
+        self.position += signed;
+        if self.position.abs() < FLAT {
+            self.position = 0.0;
+            self.avg_entry = 0.0;
+        }
+        self.max_position = self.max_position.max(self.position.abs());
+    }
+}
+
+/// One point of the equity curve.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct EquityPoint {
+    /// Unix ms.
+    pub ts: u64,
+    /// Realised + unrealised + fees.
+    pub equity: f64,
+    pub realised: f64,
+    pub unrealised: f64,
+    pub fees: f64,
+    /// Sum of absolute position notionals.
+    pub gross_exposure: f64,
+    /// Sum of signed position notionals.
+    pub net_exposure: f64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct InstrumentReport {
+    pub inst_id: String,
+    pub position: f64,
+    pub avg_entry: f64,
+    pub mark: Option<f64>,
+    pub pnl: f64,
+    pub realised: f64,
+    pub unrealised: f64,
+    pub fees: f64,
+    /// Notional traded.
+    pub turnover: f64,
+    /// Contracts (base units for spot) traded.
+    pub volume: f64,
+    pub fills: u64,
+    pub maker_fills: u64,
+    pub orders: u64,
+    pub orders_filled: u64,
+    /// Orders with any fill, per order placed.
+    pub fill_ratio: Option<f64>,
+    /// Size filled, per size quoted.
+    pub size_fill_ratio: Option<f64>,
+    pub max_position: f64,
+    /// Mean absolute position notional over the equity samples.
+    pub avg_exposure: f64,
+    pub max_exposure: f64,
+    /// Average price of flattening `position` against the last book, in bps
+    /// worse than its mid; `None` when flat or the book is too thin.
+    pub exit_slippage_bps: Option<f64>,
+    /// How far flattening `position` would move the touch, in bps from the mid.
+    pub exit_impact_bps: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BacktestReport {
+    pub start_ts: Option<u64>,
+    pub end_ts: Option<u64>,
+    pub duration_secs: f64,
+    pub capital: f64,
+    pub pnl: f64,
+    pub realised: f64,
+    pub unrealised: f64,
+    pub fees: f64,
+    /// `pnl` over `capital`.
+    pub return_pct: f64,
+    pub turnover: f64,
+    pub fills: u64,
+    pub orders: u64,
+    pub fill_ratio: Option<f64>,
+    /// Annualised, from the equity changes between samples.
+    pub sharpe: Option<f64>,
+    pub max_drawdown: f64,
+    /// Relative to `capital` plus the equity peak the drawdown started from.
+    pub max_drawdown_pct: f64,
+    pub avg_gross_exposure: f64,
+    pub max_gross_exposure: f64,
+    pub instruments: Vec<InstrumentReport>,
+}
+
+pub struct Backtest {
+    capital: f64,
+    books: BTreeMap<String, Book>,
+    curve: Vec<EquityPoint>,
+}
+
+impl Backtest {
+    /// `capital` only scales the percentages in the report.
+    pub fn new(capital: f64) -> Self {
+        Self { capital, books: BTreeMap::new(), curve: Vec::new() }
+    }
+
+    /// Account for `inst_id`, whose sizes are in units of `contract_size`.
+    pub fn track(&mut self, inst_id: impl Into<String>, contract_size: f64) {
+        self.books.insert(inst_id.into(), Book { contract_size, ..Book::default() });
+    }
+
+    /// Count placements and apply the fill, if the update carries one.
+    pub fn on_order_update(&mut self, update: &OrderUpdate) {
+        let Some(book) = self.books.get_mut(&update.inst_id) else {
+            return;
+        };
+        let quoted = book.orders.entry(update.cl_ord_id.clone()).or_default();
+        *quoted = quoted.max(update.size);
+        let Some(exec) = &update.execution else {
+            return;
+        };
+        book.fill(update.side, exec.price, exec.size);
+        book.fees += exec.fee;
+        book.turnover += exec.price * exec.size * book.contract_size;
+        book.volume += exec.size;
+        book.size_filled += exec.size;
+        book.fills += 1;
+        book.maker_fills += exec.maker as u64;
+        book.filled_orders.insert(update.cl_ord_id.clone());
+    }
+
+    /// Latest fair price of `inst_id` (e.g. the mid), for unrealised PnL and exposure.
+    pub fn on_mark(&mut self, inst_id: &str, price: f64) {
+        if let Some(book) = self.books.get_mut(inst_id) {
+            book.mark = Some(price);
+        }
+    }
+
+    /// Mark `inst_id` at the mid of its book, and price flattening the
+    /// position by sweeping the book.
+    pub fn on_book(&mut self, inst_id: &str, order_book: &OrderBook) {
+        let Some(mid) = order_book.mid_price() else {
+            return;
+        };
+        self.on_mark(inst_id, mid);
+        let Some(book) = self.books.get_mut(inst_id) else {
+            return;
+        };
+        let size = book.position.abs();
+        let side = if book.position > 0.0 { Side::Sell } else { Side::Buy };
+        (book.exit_slippage_bps, book.exit_impact_bps) = if size < FLAT {
+            (None, None)
+        } else {
+            (order_book.slippage_bps(side, size), order_book.impact_bps(side, size * mid))
+        };
+    }
+
+    /// Add a point to the equity curve at `ts` (Unix ms).
+    pub fn sample(&mut self, ts: u64) {
+        let mut point = EquityPoint { ts, equity: 0.0, realised: 0.0, unrealised: 0.0, fees: 0.0, gross_exposure: 0.0, net_exposure: 0.0 };
+        for book in self.books.values_mut() {
+            let exposure = book.exposure();
+            book.exposure_sum += exposure.abs();
+            book.max_exposure = book.max_exposure.max(exposure.abs());
+            point.realised += book.realised;
+            point.unrealised += book.unrealised();
+            point.fees += book.fees;
+            point.gross_exposure += exposure.abs();
+            point.net_exposure += exposure;
+        }
+        point.equity = point.realised + point.unrealised + point.fees;
+        if self.curve.last().is_some_and(|last| last.ts == ts) {
+            self.curve.pop();
+        }
+        self.curve.push(point);
+    }
+
+    pub fn report(&self) -> BacktestReport {
+        let samples = self.curve.len().max(1) as f64;
+        let instruments: Vec<_> = self
+            .books
+            .iter()
+            .map(|(inst_id, book)| {
+                let unrealised = book.unrealised();
+                let size_quoted: f64 = book.orders.values().sum();
+                InstrumentReport {
+                    inst_id: inst_id.clone(),
+                    position: book.position,
+                    avg_entry: book.avg_entry,
+                    mark: book.mark,
+                    pnl: book.realised + unrealised + book.fees,
+                    realised: book.realised,
+                    unrealised,
+                    fees: book.fees,
+                    turnover: book.turnover,
+                    volume: book.volume,
+                    fills: book.fills,
+                    maker_fills: book.maker_fills,
+                    orders: book.orders.len() as u64,
+                    orders_filled: book.filled_orders.len() as u64,
+                    fill_ratio: ratio(book.filled_orders.len() as f64, book.orders.len() as f64),
+                    size_fill_ratio: ratio(book.size_filled, size_quoted),
+                    max_position: book.max_position,
+                    avg_exposure: book.exposure_sum / samples,
+                    max_exposure: book.max_exposure,
+                    exit_slippage_bps: book.exit_slippage_bps,
+                    exit_impact_bps: book.exit_impact_bps,
+                }
+            })
+            .collect();
+
+        let (start_ts, end_ts) = (self.curve.first().map(|p| p.ts), self.curve.last().map(|p| p.ts));
+        let (max_drawdown, max_drawdown_pct) = self.max_drawdown();
+        let sum = |f: fn(&InstrumentReport) -> f64| instruments.iter().map(f).sum::<f64>();
+        let pnl = sum(|i| i.pnl);
+        let orders = instruments.iter().map(|i| i.orders).sum::<u64>();
+        let orders_filled = instruments.iter().map(|i| i.orders_filled).sum::<u64>();
+        BacktestReport {
+            start_ts,
+            end_ts,
+            duration_secs: end_ts.zip(start_ts).map_or(0.0, |(end, start)| (end - start) as f64 / 1000.0),
+            capital: self.capital,
+            pnl,
+            realised: sum(|i| i.realised),
+            unrealised: sum(|i| i.unrealised),
+            fees: sum(|i| i.fees),
+            return_pct: ratio(pnl * 100.0, self.capital).unwrap_or(0.0),
+            turnover: sum(|i| i.turnover),
+            fills: instruments.iter().map(|i| i.fills).sum(),
+            orders,
+            fill_ratio: ratio(orders_filled as f64, orders as f64),
+            sharpe: self.sharpe(),
+            max_drawdown,
+            max_drawdown_pct,
+            avg_gross_exposure: self.curve.iter().map(|p| p.gross_exposure).sum::<f64>() / samples,
+            max_gross_exposure: self.curve.iter().map(|p| p.gross_exposure).fold(0.0, f64::max),
+            instruments,
+        }
+    }
+
+    /// Write `report.json` and `equity.csv` into `dir`.
+    pub fn write(&self, dir: &Path) -> io::Result<BacktestReport> {
+        fs::create_dir_all(dir)?;
+        let report = self.report();
+        let json = serde_json::to_string_pretty(&report).map_err(io::Error::other)?;
+        fs::write(dir.join(REPORT_FILE), json)?;
+
+        let mut csv = io::BufWriter::new(fs::File::create(dir.join(EQUITY_FILE))?);
+        writeln!(csv, "ts,equity,realised,unrealised,fees,gross_exposure,net_exposure")?;
+        for p in &self.curve {
+            writeln!(csv, "{},{},{},{},{},{},{}", p.ts, p.equity, p.realised, p.unrealised, p.fees, p.gross_exposure, p.net_exposure)?;
+        }
+        csv.flush()?;
+        Ok(report)
+    }
+
+    /// Mean over standard deviation of the equity changes between samples,
+    /// scaled to a year by the mean sample spacing. `None` without variation.
+    fn sharpe(&self) -> Option<f64> {
+        let changes: Vec<f64> = self.curve.windows(2).map(|w| w[1].equity - w[0].equity).collect();
+        let (first, last) = (self.curve.first()?, self.curve.last()?);
+        if changes.len() < 2 || last.ts <= first.ts {
+            return None;
+        }
+        let n = changes.len() as f64;
+        let mean = changes.iter().sum::<f64>() / n;
+        let var = changes.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / (n - 1.0);
+        let spacing = (last.ts - first.ts) as f64 / n;
+        (var > 0.0).then(|| mean / var.sqrt() * (YEAR_MS / spacing).sqrt())
+    }
+
+    /// Largest fall of equity from a previous peak, absolute and as a percentage.
+    fn max_drawdown(&self) -> (f64, f64) {
+        let (mut peak, mut worst, mut worst_pct) = (0.0f64, 0.0f64, 0.0f64);
+        for p in &self.curve {
+            peak = peak.max(p.equity);
+            let drawdown = peak - p.equity;
+            worst = worst.max(drawdown);
+            worst_pct = worst_pct.max(ratio(drawdown * 100.0, self.capital + peak).unwrap_or(0.0));
+        }
+        (worst, worst_pct)
+    }
+}
+
+fn ratio(num: f64, den: f64) -> Option<f64> {
+    (den > 0.0).then(|| num / den)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::account::{Execution, OrderState};
+
+    const INST: &str = "BTC-USDT-SWAP";
+
+    fn fill(backtest: &mut Backtest, side: Side, price: f64, size: f64, fee: f64) {
+        let execution = Execution { trade_id: String::new(), price, size, fee, fee_ccy: "USDT".to_string(), pnl: 0.0, maker: true };
+        backtest.on_order_update(&OrderUpdate {
+            inst_id: INST.to_string(),
+            ord_id: String::new(),
+            cl_ord_id: format!("{:?}{}", side, price),
+            side,
+            price,
+            size,
+            state: OrderState::Filled,
+            filled: size,
+            avg_price: price,
+            execution: Some(execution),
+            ts: 0,
+        });
+    }
+
+    /// Long one contract of size 1 from 100, sampled at each mark a second apart.
+    fn marked(marks: &[f64]) -> Backtest {
+        let mut backtest = Backtest::new(1000.0);
+        backtest.track(INST, 1.0);
+        fill(&mut backtest, Side::Buy, 100.0, 1.0, 0.0);
+        for (i, mark) in marks.iter().enumerate() {
+            backtest.on_mark(INST, *mark);
+            backtest.sample(i as u64 * 1000);
+        }
+        backtest
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn pnl_splits_into_realised_unrealised_and_fees() {
+        let mut backtest = Backtest::new(1000.0);
+        backtest.track(INST, 0.01);
+        fill(&mut backtest, Side::Buy, 100.0, 10.0, -0.1);
+        fill(&mut backtest, Side::Buy, 110.0, 10.0, -0.1);
+        fill(&mut backtest, Side::Sell, 120.0, 5.0, -0.1);
+        backtest.on_mark(INST, 115.0);
+
+        let report = backtest.report();
+        let inst = &report.instruments[0];
+        assert_close(inst.avg_entry, 105.0);
+        assert_close(inst.position, 15.0);
+        // 5 × (120 − 105) × 0.01, and 15 × (115 − 105) × 0.01 still open
+        assert_close(report.realised, 0.75);
+        assert_close(report.unrealised, 1.5);
+        assert_close(report.fees, -0.3);
+        assert_close(report.pnl, 1.95);
+        assert_close(report.turnover, (1000.0 + 1100.0 + 600.0) * 0.01);
+        assert_close(report.return_pct, 0.195);
+    }
+
+    #[test]
+    fn a_fill_through_flat_opens_at_its_own_price() {
+        let mut backtest = Backtest::new(1000.0);
+        backtest.track(INST, 1.0);
+        fill(&mut backtest, Side::Buy, 100.0, 2.0, 0.0);
+        fill(&mut backtest, Side::Sell, 110.0, 5.0, 0.0);
+        let inst = &backtest.report().instruments[0];
+        assert_close(inst.realised, 20.0);
+        assert_close(inst.position, -3.0);
+        assert_close(inst.avg_entry, 110.0);
+
+        // covering the short at 105 makes 3 × 5
+        fill(&mut backtest, Side::Buy, 105.0, 3.0, 0.0);
+        let inst = &backtest.report().instruments[0];
+        assert_close(inst.realised, 35.0);
+        assert_eq!((inst.position, inst.avg_entry), (0.0, 0.0));
+        assert_close(inst.max_position, 3.0);
+    }
+
+    #[test]
+    fn sharpe_is_annualised_from_equity_changes() {
+        // equity 0, 1, 3, 2: changes 1, 2, −1 with mean 2/3 and sample variance 7/3, a second apart
+        let sharpe = marked(&[100.0, 101.0, 103.0, 102.0]).report().sharpe.unwrap();
+        assert_close(sharpe, (2.0 / 3.0) / (7.0f64 / 3.0).sqrt() * (YEAR_MS / 1000.0).sqrt());
+        assert!(marked(&[100.0, 101.0, 102.0]).report().sharpe.is_none());
+    }
+
+    #[test]
+    fn drawdown_is_the_deepest_fall_from_a_peak() {
+        // equity 0, 3, 1, 4, −1: falls of 2 from 3 and of 5 from 4
+        let report = marked(&[100.0, 103.0, 101.0, 104.0, 99.0]).report();
+        assert_close(report.max_drawdown, 5.0);
+        assert_close(report.max_drawdown_pct, 500.0 / 1004.0);
+    }
+}
@@ -0,0 +1,324 @@
+//! Command line: every flag `main` understands, parsed and checked up front.
+//!
+//! ```text
+//! CEX-Order-Book [--channel books|books5|bbo-tbt|books-l2-tbt|books50-l2-tbt]
+//!                [--trades-all] [--bar BAR] [--strategy statmm|mmxm]
+//!                [--centre mid|microprice|weighted|stoikov] [--size-unit contracts|base]
+//!                [--rest-url URL] [--private-url URL] [--live | --paper] [--ord-type post_only|limit]
+//!                [--reduce-only] [--price-tolerance TICKS]
+//!                [--size-tolerance FRACTION] [--queue-model MODEL] [--record DIR]
+//!                [--replay DIR [--replay-speed max|N] [--replay-clock receive|exchange]
+//!                 [--instruments FILE]]
+//!                [--backtest DIR [--capital AMOUNT]] [--fee-tier lv1|vip1..vip5]
+//!                [--family FAMILY] [--base CCY] [--quote CCY] [--inst-type TYPE] [INST_ID...]
+//! ```
+//!
+//! --family, --base, --quote and --inst-type (SPOT, SWAP, FUTURES, OPTION) add every live instrument
+//! matching all of those given to the INST_IDs, e.g. --base BTC --inst-type SWAP; without any of
+//! them or INST_IDs, AI16Z-USDT-SWAP is traded
+//!
+//! --trades-all takes individual prints from the business endpoint instead of aggregated `trades`
+//!
+//! --bar backfills and streams candles of that size (1m, 1H, 4H, 1Dutc, ...); mmxm defaults to 1H
+//!
+//! --centre is what statmm centres its quotes on: the mid, the touch microprice (default), a
+//! microprice over the top five levels, or a Stoikov microprice learned from the books
+//!
+//! --size-unit says what strategies size orders in: OKX's `sz` (default; contracts for derivatives)
+//! or the base currency, converted to contracts with each instrument's `ctVal`
+//!
+//! With OKX_API_KEY/OKX_API_SECRET/OKX_API_PASSPHRASE set, our orders, positions and balances
+//! stream from the private endpoint (--private-url points it elsewhere, e.g. a mock server)
+//!
+//! --rest-url sends REST calls (instruments, candles, warm-up, order fallbacks) to another host than www.okx.com
+//!
+//! --live quotes each strategy's latest bid/ask on OKX (needs the keys above) as post-only orders, or
+//! plain limit orders with --ord-type limit, amending working orders only once they're more than
+//! --price-tolerance ticks (default 1) or --size-tolerance of their size (default 0.1) away from the
+//! quote; quotes go out in batches. --reduce-only makes derivative quotes only ever shrink the position
+//!
+//! --paper quotes the same way against a local paper venue that fills off the live book and trades
+//! once the queue ahead is gone; --queue-model optimistic|pessimistic|proportional (default) says
+//! whether size cancelled from our level was ahead of us, behind us, or spread evenly
+//!
+//! --record DIR writes every raw public/business frame to compressed per-instrument/channel/day
+//! files under DIR (see recorder.rs), with the instruments' specs; Ctrl-C closes and indexes them
+//!
+//! --replay DIR feeds a recording through the books and strategies instead of connecting (add --paper
+//! to trade it), as fast as possible or at N times real time, ordered and clocked by receive time
+//! (default) or OKX's timestamps; on_timer fires on the recording's clock, not the wall clock
+//! It trades the specs saved with the recording, or those in --instruments FILE (OKX's instrument
+//! rows as a JSON array), and never fetches OKX's current ones
+//!
+//! --backtest DIR trades the replay on paper and writes DIR/report.json (PnL, fees, turnover, fill
+//! ratios, Sharpe, drawdown, exposure, what flattening the final position would cost against the
+//! last book; returns are on --capital, default 10000) and DIR/equity.csv; it refuses inverse
+//! contracts, whose PnL is in coin
+//!
+//! --fee-tier charges paper fills OKX's maker rate for that tier (default lv1)
+
+use crate::execution::OrdType;
+use crate::fees::FeeTier;
+use crate::kline::Bar;
+use crate::normalize::SizeUnit;
+use crate::orderbook::signals::StoikovMicroprice;
+use crate::orderbook::BookChannel;
+use crate::paper::CancelModel;
+use crate::replay::{Speed, TimeOrder};
+use crate::sources::okx::PRIVATE_URL;
+use crate::sources::okx_rest::InstType;
+use crate::strategies::statmm::QuoteCentre;
+
+/// Capital a backtest's returns and drawdowns are measured against, unless given.
+const BACKTEST_CAPITAL: f64 = 10_000.0;
+
+/// Default quote tolerances: ticks a working order may be off its target price,
+/// and fraction its size may be off, before it's amended.
+const QUOTE_PRICE_TOLERANCE: i64 = 1;
+const QUOTE_SIZE_TOLERANCE: f64 = 0.1;
+
+/// What to trade, where and how, as given on the command line.
+pub struct Args {
+    pub channel: BookChannel,
+    pub trades_all: bool,
+    /// Candles to backfill and stream; always set for mmxm.
+    pub bar: Option<Bar>,
+    pub mmxm: bool,
+    pub centre: QuoteCentre,
+    pub size_unit: SizeUnit,
+    pub live: bool,
+    /// Quote on the paper venue; implied by `--backtest`.
+    pub paper: bool,
+    pub ord_type: OrdType,
+    pub reduce_only: bool,
+    pub record_dir: Option<String>,
+    pub replay_dir: Option<String>,
+    pub replay_speed: Speed,
+    pub replay_clock: TimeOrder,
+    pub instruments_file: Option<String>,
+    pub backtest_dir: Option<String>,
+    pub capital: f64,
+    pub fee_tier: FeeTier,
+    pub cancel_model: CancelModel,
+    pub price_tolerance: i64,
+    pub size_tolerance: f64,
+    pub private_url: String,
+    pub rest_url: Option<String>,
+    pub family: Option<String>,
+    pub base: Option<String>,
+    pub quote: Option<String>,
+    pub inst_type: Option<InstType>,
+    /// Instruments named outright; a selection adds to them.
+    pub inst_ids: Vec<String>,
+}
+
+impl Args {
+    /// Parse `std::env::args`, exiting with a message on anything invalid.
+    pub fn parse() -> Self {
+        let mut args: Vec<String> = std::env::args().skip(1).collect();
+        let channel = match take_option(&mut args, "--channel") {
+            Some(name) => BookChannel::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown book channel {:?}", name);
+                std::process::exit(1);
+            }),
+            None => BookChannel::Books,
+        };
+        if channel.requires_login() {
+            eprintln!("⚠️ {} needs a logged-in VIP connection; OKX will reject it on the public feed", channel);
+        }
+        let trades_all = take_flag(&mut args, "--trades-all");
+        let bar = take_option(&mut args, "--bar").map(|name| {
+            Bar::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown bar {:?}", name);
+                std::process::exit(1);
+            })
+        });
+        let mmxm = match take_option(&mut args, "--strategy").as_deref() {
+            None | Some("statmm") => false,
+            Some("mmxm") => true,
+            Some(other) => {
+                eprintln!("❌ Unknown strategy {:?}", other);
+                std::process::exit(1);
+            }
+        };
+        let centre = match take_option(&mut args, "--centre") {
+            Some(name) => quote_centre(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown quote centre {:?}", name);
+                std::process::exit(1);
+            }),
+            None => QuoteCentre::Microprice,
+        };
+        let size_unit = match take_option(&mut args, "--size-unit") {
+            Some(name) => SizeUnit::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown size unit {:?}", name);
+                std::process::exit(1);
+            }),
+            None => SizeUnit::Contracts,
+        };
+        let live = take_flag(&mut args, "--live");
+        let paper = take_flag(&mut args, "--paper");
+        if live && paper {
+            eprintln!("❌ --live and --paper are mutually exclusive");
+            std::process::exit(1);
+        }
+        let ord_type = match take_option(&mut args, "--ord-type") {
+            Some(name) => match OrdType::from_name(&name) {
+                Some(t @ (OrdType::PostOnly | OrdType::Limit)) => t,
+                _ => {
+                    eprintln!("❌ --ord-type must be post_only or limit, not {:?}: quotes rest on the book", name);
+                    std::process::exit(1);
+                }
+            },
+            None => OrdType::PostOnly,
+        };
+        if paper && ord_type != OrdType::PostOnly {
+            eprintln!("❌ --paper only simulates post-only orders");
+            std::process::exit(1);
+        }
+        let reduce_only = take_flag(&mut args, "--reduce-only");
+        let record_dir = take_option(&mut args, "--record");
+        let replay_dir = take_option(&mut args, "--replay");
+        let replay_speed = match take_option(&mut args, "--replay-speed") {
+            Some(name) => Speed::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Invalid --replay-speed {:?}", name);
+                std::process::exit(1);
+            }),
+            None => Speed::default(),
+        };
+        let replay_clock = match take_option(&mut args, "--replay-clock") {
+            Some(name) => TimeOrder::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown replay clock {:?}", name);
+                std::process::exit(1);
+            }),
+            None => TimeOrder::default(),
+        };
+        if replay_dir.is_some() && (live || record_dir.is_some()) {
+            eprintln!("❌ --replay can't be combined with --live or --record");
+            std::process::exit(1);
+        }
+        let instruments_file = take_option(&mut args, "--instruments");
+        if instruments_file.is_some() && replay_dir.is_none() {
+            eprintln!("❌ --instruments only applies to a replay (--replay DIR)");
+            std::process::exit(1);
+        }
+        let backtest_dir = take_option(&mut args, "--backtest");
+        if backtest_dir.is_some() && replay_dir.is_none() {
+            eprintln!("❌ --backtest needs a recording to run on (--replay DIR)");
+            std::process::exit(1);
+        }
+        let paper = paper || backtest_dir.is_some();
+        let capital = take_option(&mut args, "--capital").map_or(BACKTEST_CAPITAL, |c| {
+            c.parse().unwrap_or_else(|_| {
+                eprintln!("❌ Invalid --capital {:?}", c);
+                std::process::exit(1);
+            })
+        });
+        let fee_tier = match take_option(&mut args, "--fee-tier") {
+            Some(name) => FeeTier::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown fee tier {:?}", name);
+                std::process::exit(1);
+            }),
+            None => FeeTier::default(),
+        };
+        let cancel_model = match take_option(&mut args, "--queue-model") {
+            Some(name) => CancelModel::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown queue model {:?}", name);
+                std::process::exit(1);
+            }),
+            None => CancelModel::default(),
+        };
+        let price_tolerance = take_option(&mut args, "--price-tolerance").map_or(QUOTE_PRICE_TOLERANCE, |t| {
+            t.parse().unwrap_or_else(|_| {
+                eprintln!("❌ Invalid --price-tolerance {:?}", t);
+                std::process::exit(1);
+            })
+        });
+        let size_tolerance = take_option(&mut args, "--size-tolerance").map_or(QUOTE_SIZE_TOLERANCE, |t| {
+            t.parse().unwrap_or_else(|_| {
+                eprintln!("❌ Invalid --size-tolerance {:?}", t);
+                std::process::exit(1);
+            })
+        });
+        let private_url = take_option(&mut args, "--private-url").unwrap_or_else(|| PRIVATE_URL.to_string());
+        let rest_url = take_option(&mut args, "--rest-url");
+        let family = take_option(&mut args, "--family");
+        let base = take_option(&mut args, "--base");
+        let quote = take_option(&mut args, "--quote");
+        let inst_type = take_option(&mut args, "--inst-type").map(|name| {
+            InstType::from_name(&name).unwrap_or_else(|| {
+                eprintln!("❌ Unknown instrument type {:?}", name);
+                std::process::exit(1);
+            })
+        });
+        let selecting = family.is_some() || base.is_some() || quote.is_some() || inst_type.is_some();
+        let bar = if mmxm { Some(bar.unwrap_or(Bar::H1)) } else { bar };
+        let inst_ids = if args.is_empty() && !selecting { vec!["AI16Z-USDT-SWAP".to_string()] } else { args };
+
+        Self {
+            channel,
+            trades_all,
+            bar,
+            mmxm,
+            centre,
+            size_unit,
+            live,
+            paper,
+            ord_type,
+            reduce_only,
+            record_dir,
+            replay_dir,
+            replay_speed,
+            replay_clock,
+            instruments_file,
+            backtest_dir,
+            capital,
+            fee_tier,
+            cancel_model,
+            price_tolerance,
+            size_tolerance,
+            private_url,
+            rest_url,
+            family,
+            base,
+            quote,
+            inst_type,
+            inst_ids,
+        }
+    }
+
+    /// Whether instruments are selected by family, base, quote or type.
+    pub fn selecting(&self) -> bool {
+        self.family.is_some() || self.base.is_some() || self.quote.is_some() || self.inst_type.is_some()
+    }
+}
+
+/// StatMM quote centre named by `--centre`. `weighted` looks five levels deep
+/// with a 10 bps decay; `stoikov` learns over 10 imbalance buckets and spreads
+/// up to 5 ticks, crediting at most 1000 books to each mid change.
+fn quote_centre(name: &str) -> Option<QuoteCentre> {
+    match name {
+        "mid" => Some(QuoteCentre::Mid),
+        "microprice" => Some(QuoteCentre::Microprice),
+        "weighted" => Some(QuoteCentre::WeightedMicroprice { levels: 5, decay_bps: 10.0 }),
+        "stoikov" => Some(QuoteCentre::Stoikov(StoikovMicroprice::new(10, 5, 1000))),
+        _ => None,
+    }
+}
+
+/// Remove `name` from `args`, returning whether it was present.
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    match args.iter().position(|a| a == name) {
+        Some(i) => {
+            args.remove(i);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Remove `name <value>` from `args`, returning the value.
+fn take_option(args: &mut Vec<String>, name: &str) -> Option<String> {
+    let i = args.iter().position(|a| a == name)?;
+    args.remove(i);
+    (i < args.len()).then(|| args.remove(i))
+}
@@ -0,0 +1,36 @@
+//! Unix time as orders and fills should be stamped with it: the wall clock
+//! live, or a replay's simulated clock, so that a replay stamps everything
+//! with the recording's time and two runs of it agree.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The wall clock by default. Cheap to clone; clones of a simulated clock
+/// share its time.
+#[derive(Debug, Clone, Default)]
+pub struct Clock {
+    /// Simulated Unix ms; `None` reads the wall clock.
+    simulated: Option<Arc<AtomicU64>>,
+}
+
+impl Clock {
+    /// A clock that reads `ms` until moved with `set_ms`.
+    pub fn simulated(ms: u64) -> Self {
+        Self { simulated: Some(Arc::new(AtomicU64::new(ms))) }
+    }
+
+    /// Move a simulated clock; the wall clock can't be moved.
+    pub fn set_ms(&self, ms: u64) {
+        if let Some(simulated) = &self.simulated {
+            simulated.store(ms, Ordering::Relaxed);
+        }
+    }
+
+    pub fn now_ms(&self) -> u64 {
+        match &self.simulated {
+            Some(simulated) => simulated.load(Ordering::Relaxed),
+            None => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+        }
+    }
+}
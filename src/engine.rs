@@ -0,0 +1,824 @@
+//! The event loop: routes feed (or replayed) frames, timer ticks, instrument
+//! refreshes and order gateway answers between the books, the strategies, the
+//! OMS and the venue, until the feed stops, the replay ends or Ctrl-C.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::ControlFlow;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use futures::future::join_all;
+use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
+use tokio::time;
+use uuid::Uuid;
+
+use crate::account::{AccountUpdate, OrderUpdate, Position};
+use crate::backtest::Backtest;
+use crate::clock::Clock;
+use crate::execution::{ExecError, ExecutionGateway, NewOrder, OrdType, OrderAck, TdMode};
+use crate::instruments::{InstrumentChange, InstrumentRegistry, InstrumentSpec};
+use crate::kline::{Bar, BarStore, Candle};
+use crate::market::{FundingRate, IndexPrice, MarkPrice, OpenInterest, Ticker, Trade};
+use crate::models::{ws_codes, Instrument, OkxWsMessage, WsBookPush, WsEvent, WsPush};
+use crate::oms::{OrderAction, OrderManager};
+use crate::orderbook::manager::{BookManager, BookUpdate};
+use crate::orderbook::{BookChannel, BookStatus, OrderBook};
+use crate::paper::PaperVenue;
+use crate::quoting::{Quote, QuoteEngine};
+use crate::recorder::RecorderHandle;
+use crate::replay::{Replay, ReplayEvent};
+use crate::sources::okx::{rejected_subscription, FeedEvent, OkxFeed, Subscription};
+use crate::strategy::{OrderRequest, Strategy};
+
+/// Period of the `on_timer` tick, live and in replays.
+pub const TIMER_PERIOD: Duration = Duration::from_secs(1);
+
+/// Finished orders are kept this long for lookups, then forgotten.
+const FINISHED_ORDER_TTL: Duration = Duration::from_secs(3600);
+
+/// Everything the loop routes between, as `main` set it up.
+pub struct Engine {
+    pub strats: HashMap<String, Box<dyn Strategy>>,
+    pub books: BookManager,
+    pub quoters: HashMap<String, QuoteEngine>,
+    /// Instruments that aren't live (suspended, pre-open, delisted): strategies
+    /// see their data, but nothing is quoted.
+    pub halted: HashSet<String>,
+    pub registry: InstrumentRegistry,
+    pub bars: BarStore,
+    /// Underlying index of each derivative traded.
+    pub indices: HashMap<String, String>,
+    /// `None` when only watching.
+    pub venue: Option<Venue>,
+    pub oms: OrderManager,
+    pub backtest: Option<Backtest>,
+    pub clock: Clock,
+    pub channel: BookChannel,
+    pub ord_type: OrdType,
+    pub reduce_only: bool,
+    pub price_tolerance: i64,
+    pub size_tolerance: f64,
+    /// The public feed; `None` in a replay.
+    pub feed: Option<OkxFeed>,
+    pub public_url: String,
+    pub private_url: String,
+    /// Every feed's events, or the replay's frames.
+    pub events: UnboundedReceiver<FeedEvent>,
+    pub replay: Option<Replay>,
+    /// Where the replay's frames are sent to come back on `events`.
+    pub replay_tx: Option<UnboundedSender<FeedEvent>>,
+    pub recorder: Option<RecorderHandle>,
+    pub order_events_tx: UnboundedSender<OrderEvent>,
+    pub order_events: UnboundedReceiver<OrderEvent>,
+    /// Refreshed instrument lists; closed in a replay, which keeps its specs.
+    pub instrument_updates: UnboundedReceiver<Vec<Instrument>>,
+}
+
+impl Engine {
+    /// Run until the feed stops, the replay ends or Ctrl-C; returns the
+    /// backtest, if running one, with its last equity point taken.
+    pub async fn run(mut self) -> Option<Backtest> {
+        let mut ticker = time::interval(TIMER_PERIOD);
+        loop {
+            tokio::select! {
+                // arms are polled in order: order results go before more market data, and a
+                // replayed frame is fully handled (feed event, then the paper venue's answers)
+                // before the next one is read
+                biased;
+
+                Some(event) = self.order_events.recv() => self.on_order_event(event),
+                event = self.events.recv() => {
+                    let Some(event) = event else {
+                        break; // feed task stopped
+                    };
+                    if self.on_feed_event(event).is_break() {
+                        break;
+                    }
+                }
+                // a replay ticks on its own clock
+                _ = ticker.tick(), if self.replay.is_none() => {
+                    self.on_timer(Instant::now());
+                    if let Some(recorder) = &self.recorder {
+                        recorder.flush();
+                    }
+                }
+                Some(list) = self.instrument_updates.recv() => self.on_instruments(list),
+                // stop cleanly so recordings are finished and indexed
+                _ = tokio::signal::ctrl_c() => break,
+                event = next_replay(&mut self.replay), if self.replay.is_some() => match event {
+                    Some(ReplayEvent::Timer(now)) => {
+                        self.on_timer(now);
+                        // one equity point per timer tick
+                        if let (Some(backtest), Some(replay)) = (&mut self.backtest, &self.replay) {
+                            backtest.sample(replay.now_ms());
+                        }
+                    }
+                    // the next recorded frame goes through `on_feed_event` like a live one
+                    Some(ReplayEvent::Frame(frame)) => {
+                        let event = match OkxWsMessage::parse(&frame.text) {
+                            Ok(msg) => FeedEvent::Message(msg),
+                            Err(_) => FeedEvent::Unparsed(frame.text),
+                        };
+                        if let Some(tx) = &self.replay_tx {
+                            let _ = tx.send(event);
+                        }
+                    }
+                    None => {
+                        println!("⏹ Replay finished");
+                        break;
+                    }
+                },
+            }
+        }
+
+        if let (Some(backtest), Some(replay)) = (&mut self.backtest, &self.replay) {
+            backtest.sample(replay.now_ms());
+        }
+        self.backtest
+    }
+
+    /// Gateway results: move orders through their lifecycle.
+    fn on_order_event(&mut self, event: OrderEvent) {
+        let oms = &mut self.oms;
+        match event {
+            OrderEvent::Placed(ack) => {
+                if let Some(order) = oms.on_place_ack(&ack) {
+                    match &order.reject_reason {
+                        None => println!("📨 {} {:?} {} @ {} accepted as {} ({:?})", order.cl_ord_id, order.side, order.size, order.price, ack.ord_id, ack.route),
+                        Some(reason) => eprintln!("⚠️ {} {:?} {} @ {} rejected: {}", order.cl_ord_id, order.side, order.size, order.price, reason),
+                    }
+                }
+            }
+            OrderEvent::Changed(ack) => {
+                if !ack.is_ok() {
+                    eprintln!("⚠️ Change to {} rejected ({}): {}", ack.cl_ord_id, ack.code, ack.msg);
+                }
+                oms.on_change_ack(&ack);
+            }
+            OrderEvent::ChangeFailed(id) => oms.on_change_failed(&id),
+            OrderEvent::State(_, Some(update)) => {
+                oms.on_update(&update);
+            }
+            OrderEvent::State(id, None) => oms.reject(id, "never reached OKX".to_string()),
+            OrderEvent::Reconciled { orders, as_of } => {
+                let strats = &self.strats;
+                let changed = oms.reconcile(&orders, as_of, |inst_id| strats.contains_key(inst_id).then(|| inst_id.to_string()));
+                for id in changed {
+                    if let Some(order) = oms.get(&id) {
+                        println!("🔁 Reconciled {} {} {}", order.inst_id, order.cl_ord_id, order.status);
+                    }
+                }
+            }
+        }
+    }
+
+    /// One feed event; breaks once there's nothing left to trade, or live
+    /// without the private socket.
+    fn on_feed_event(&mut self, event: FeedEvent) -> ControlFlow<()> {
+        match event {
+            FeedEvent::Message(OkxWsMessage::Books(push)) => self.on_book(push),
+            FeedEvent::Message(OkxWsMessage::Event(event)) => return self.on_ws_event(event),
+            FeedEvent::Message(OkxWsMessage::Candles(push)) => dispatch_candles(&push, &mut self.bars, &mut self.strats),
+            FeedEvent::Message(
+                msg @ (OkxWsMessage::Orders(_)
+                | OkxWsMessage::Positions(_)
+                | OkxWsMessage::Account(_)
+                | OkxWsMessage::BalanceAndPosition(_)),
+            ) => dispatch_account(&msg, &mut self.oms, &mut self.strats),
+            FeedEvent::Message(msg) => {
+                // Paper: prints at or through a resting paper order fill it
+                if let (Some(Venue::Paper(paper)), OkxWsMessage::Trades(push)) = (&mut self.venue, &msg) {
+                    for trade in push.data.iter().filter_map(Trade::from_okx) {
+                        paper.on_trade(&trade);
+                    }
+                    self.dispatch_paper();
+                }
+                dispatch_market_data(&msg, &mut self.strats, &self.indices);
+            }
+            FeedEvent::Connected { url } => {
+                println!("🔌 Connected to {}", url);
+                if url == self.private_url {
+                    self.reconcile();
+                }
+            }
+            FeedEvent::Disconnected { url, reason, retry_in } => {
+                eprintln!("⚠️ Disconnected from {} ({}), reconnecting in {:?}", url, reason, retry_in);
+                if url != self.public_url {
+                    return ControlFlow::Continue(());
+                }
+                // every book is stale until its post-reconnect snapshot arrives
+                for inst_id in self.books.invalidate_all() {
+                    self.pull_quotes(&inst_id);
+                    if let Some(strat) = self.strats.get_mut(&inst_id) {
+                        strat.on_book_status(&inst_id, BookStatus::Invalid);
+                    }
+                }
+                self.dispatch_paper();
+            }
+            FeedEvent::Stopped { url, reason } => {
+                eprintln!("❌ Gave up on {} ({})", url, reason);
+                // live, without the private socket we can neither trade nor see our orders
+                if url == self.private_url && matches!(self.venue, Some(Venue::Live(_))) {
+                    return ControlFlow::Break(());
+                }
+            }
+            FeedEvent::Unparsed(txt) => eprintln!("⚠️ Couldn't parse WS message: {}", txt),
+            FeedEvent::Raw(frame) => {
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(frame);
+                }
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Event replies and notices; a rejected book subscription drops its instrument.
+    fn on_ws_event(&mut self, event: WsEvent) -> ControlFlow<()> {
+        match event {
+            WsEvent::Error { code, msg } if code == ws_codes::BAD_CHANNEL_OR_INST => {
+                // Without its book an instrument can't be traded: stop trading it, keep the
+                // rest. Any other channel (trades, tickers, candles) is only missing data.
+                eprintln!("❌ Subscription rejected ({}): {}", code, msg);
+                let bad = rejected_subscription(&msg)
+                    .filter(|s| s.channel == self.channel.name())
+                    .and_then(|s| s.inst_id);
+                if let Some(inst_id) = bad.filter(|id| self.strats.contains_key(id)) {
+                    eprintln!("⚠️ Dropping {}", inst_id);
+                    self.pull_quotes(&inst_id);
+                    self.strats.remove(&inst_id);
+                    self.quoters.remove(&inst_id);
+                    self.books.untrack(&inst_id);
+                }
+                if self.strats.is_empty() {
+                    eprintln!("❌ No instruments left to trade");
+                    return ControlFlow::Break(());
+                }
+            }
+            WsEvent::Error { code, msg } if code == ws_codes::LOGIN_FAILED => {
+                eprintln!("❌ Private login failed ({}): {}", code, msg);
+            }
+            WsEvent::Error { code, msg } => eprintln!("⚠️ OKX error {}: {}", code, msg),
+            WsEvent::Login { .. } => println!("🔑 Logged in"),
+            WsEvent::Notice { code, msg } => eprintln!("📢 OKX notice {}: {}", code, msg),
+            WsEvent::Subscribe { arg } => println!("📡 Subscribed to {} | {}", arg.channel, arg.key()),
+            WsEvent::Unsubscribe { arg } => println!("📡 Unsubscribed from {} | {}", arg.channel, arg.key()),
+            WsEvent::ChannelConnCount { channel, connCount } => {
+                println!("🔌 {} connection(s) on {}", connCount, channel);
+            }
+            WsEvent::ChannelConnCountError { channel, connCount } => {
+                eprintln!("⚠️ Too many connections on {} ({})", channel, connCount);
+            }
+        }
+        ControlFlow::Continue(())
+    }
+
+    /// Our orders may have changed while the private socket was down: check them against OKX.
+    fn reconcile(&self) {
+        let Some(Venue::Live(gateway)) = &self.venue else {
+            return;
+        };
+        let gateway = gateway.clone();
+        let local: Vec<_> = self.oms.all_open().map(|o| (o.inst_id.clone(), o.cl_ord_id.clone())).collect();
+        let as_of = unix_ms();
+        let order_events = self.order_events_tx.clone();
+        tokio::spawn(async move {
+            match exchange_orders(&gateway, local).await {
+                Ok(orders) => {
+                    let _ = order_events.send(OrderEvent::Reconciled { orders, as_of });
+                }
+                Err(e) => eprintln!("⚠️ Order reconciliation failed: {}", e),
+            }
+        });
+    }
+
+    /// A book push: update the instrument's book, then its strategy, then move
+    /// our working orders to the strategy's quote.
+    fn on_book(&mut self, push: WsBookPush) {
+        let inst_id = push.arg.instId.as_str();
+
+        // Route to the instrument's book; never quote off a corrupted one
+        match self.books.apply(&push) {
+            BookUpdate::Ignored | BookUpdate::Skipped => return,
+            BookUpdate::Resync(e) => {
+                eprintln!("⚠️ {} {}, resubscribing", inst_id, e);
+                if let Some(feed) = &self.feed {
+                    feed.resubscribe(Subscription::new(push.arg.channel.as_str(), inst_id));
+                }
+                self.pull_quotes(inst_id);
+                if let Some(strat) = self.strats.get_mut(inst_id) {
+                    strat.on_book_status(inst_id, BookStatus::Invalid);
+                }
+                self.dispatch_paper();
+                return;
+            }
+            BookUpdate::Applied { recovered } => {
+                if recovered {
+                    if let Some(strat) = self.strats.get_mut(inst_id) {
+                        strat.on_book_status(inst_id, BookStatus::Valid);
+                    }
+                }
+            }
+        }
+
+        // Paper: the book moving through a resting paper order fills it
+        if let (Some(Venue::Paper(paper)), Some(book)) = (&mut self.venue, self.books.get(inst_id)) {
+            let ts = push.data.first().and_then(|d| d.ts.parse().ok()).unwrap_or_else(|| self.clock.now_ms());
+            paper.on_book(inst_id, book, ts);
+            self.dispatch_paper();
+        }
+        if let (Some(backtest), Some(book)) = (&mut self.backtest, self.books.get(inst_id)) {
+            backtest.on_book(inst_id, book);
+        }
+
+        let (Some(strat), Some(book)) = (self.strats.get_mut(inst_id), self.books.get(inst_id)) else {
+            return;
+        };
+        let Some(mid) = book.mid_price() else {
+            return;
+        };
+        let now = self.replay.as_ref().map_or_else(Instant::now, Replay::now);
+
+        // book update first, so book signals (microprice) are fresh
+        for req in strat.on_book_update(inst_id, &self.books) {
+            println!("▶️  {} OrderRequest from on_book_update: {:?}", inst_id, req);
+        }
+        let reqs = strat.on_price_tick(mid, now);
+        for req in &reqs {
+            println!("▶️  {} OrderRequest from on_price_tick: {:?}", inst_id, req);
+        }
+
+        // Live or paper: treat the requests as the desired quote and move our
+        // working orders to it with as few places, amends and cancels as possible
+        let (Some(target), Some(quoter)) = (&mut self.venue, self.quoters.get(inst_id)) else {
+            return;
+        };
+        if self.halted.contains(inst_id) {
+            return;
+        }
+        let diff = quoter.diff(&Quote::from_requests(&reqs), &self.oms.open_orders(inst_id));
+        let derivative = self.registry.get(inst_id).is_some_and(InstrumentSpec::is_derivative);
+        let td_mode = if derivative { TdMode::Cross } else { TdMode::Cash };
+        let (ord_type, reduce_only) = (self.ord_type, self.reduce_only);
+        let new_order = |req: &OrderRequest, cl_ord_id: &str| {
+            let order = NewOrder::from_request(inst_id, cl_ord_id, req, &book.precision, td_mode).with_ord_type(ord_type);
+            if reduce_only && derivative {
+                order.reduce_only()
+            } else {
+                order
+            }
+        };
+        send_places(target, &mut self.oms, inst_id, diff.place, book, new_order, &self.order_events_tx);
+        send_changes(target, &mut self.oms, diff.modify, Some(book), &self.order_events_tx);
+        self.dispatch_paper();
+    }
+
+    /// Timer tick: `on_timer` for every strategy, then (live or paper) let each
+    /// cancel or amend its working orders; forget long-finished orders.
+    fn on_timer(&mut self, now: Instant) {
+        // by instId, so a replay runs them in the same order every time
+        let mut by_inst: Vec<_> = self.strats.iter_mut().collect();
+        by_inst.sort_by(|a, b| a.0.cmp(b.0));
+        for (inst_id, strat) in by_inst {
+            for req in strat.on_timer(now) {
+                println!("⏲️  {} OrderRequest from on_timer: {:?}", inst_id, req);
+            }
+            let Some(target) = self.venue.as_mut() else {
+                continue;
+            };
+            let actions = strat.manage_orders(&self.oms.open_orders(inst_id));
+            send_changes(target, &mut self.oms, actions, self.books.get(inst_id), &self.order_events_tx);
+        }
+        self.dispatch_paper();
+        self.oms.prune(self.clock.now_ms().saturating_sub(FINISHED_ORDER_TTL.as_millis() as u64));
+    }
+
+    /// Instrument refresh: report changes to what we trade, stop quoting what
+    /// isn't live, and move its book, quoter and normalizer onto a changed
+    /// tick/lot grid.
+    fn on_instruments(&mut self, list: Vec<Instrument>) {
+        for change in self.registry.update(list) {
+            let inst_id = change.inst_id().to_string();
+            if !self.strats.contains_key(&inst_id) {
+                continue;
+            }
+            eprintln!("📋 {}", change);
+            let book_sub = Subscription::new(self.channel.name(), &inst_id);
+            let Some(spec) = self.registry.get(&inst_id).filter(|_| !matches!(change, InstrumentChange::Delisted(_))) else {
+                // delisted: its book goes quiet until (unless) it's listed again
+                if self.halted.insert(inst_id.clone()) {
+                    self.pull_quotes(&inst_id);
+                }
+                self.books.untrack(&inst_id);
+                if let Some(feed) = &self.feed {
+                    feed.unsubscribe(vec![book_sub]);
+                }
+                if let Some(strat) = self.strats.get_mut(&inst_id) {
+                    strat.on_book_status(&inst_id, BookStatus::Invalid);
+                }
+                continue;
+            };
+            let spec = spec.clone();
+            if let Some(strat) = self.strats.get_mut(&inst_id) {
+                strat.on_instrument(&spec);
+            }
+            if spec.is_tradable() {
+                if self.halted.remove(&inst_id) {
+                    eprintln!("▶️ {} is live again", inst_id);
+                }
+            } else if self.halted.insert(inst_id.clone()) {
+                eprintln!("⏸ {} is {}: pulling its quotes until it's live", inst_id, spec.state);
+                self.pull_quotes(&inst_id);
+            }
+            if self.books.get(&inst_id).map(|b| b.precision) == Some(spec.precision) {
+                continue;
+            }
+            // levels on the old grid can't be carried over, and a relisted book has none:
+            // rebuild from a fresh snapshot
+            let relisted = self.books.get(&inst_id).is_none();
+            eprintln!("📏 {} on tick {} / lot {}, resyncing its book", inst_id, spec.precision.tick, spec.precision.lot);
+            self.quoters.insert(
+                inst_id.clone(),
+                QuoteEngine::new(spec.precision).with_price_tolerance(self.price_tolerance).with_size_tolerance(self.size_tolerance),
+            );
+            self.books.track(inst_id.clone(), self.channel, OrderBook::for_instrument(&spec));
+            match &self.feed {
+                Some(feed) if relisted => feed.subscribe(vec![book_sub]),
+                Some(feed) => feed.resubscribe(book_sub),
+                None => {}
+            }
+            self.pull_quotes(&inst_id);
+            if let Some(strat) = self.strats.get_mut(&inst_id) {
+                strat.on_book_status(&inst_id, BookStatus::Invalid);
+            }
+        }
+        self.dispatch_paper();
+    }
+
+    /// Cancel every working order on `inst_id`, e.g. while its book can't be trusted.
+    fn pull_quotes(&mut self, inst_id: &str) {
+        let Some(target) = self.venue.as_mut() else {
+            return;
+        };
+        let cancels = self.oms.open_orders(inst_id).iter().map(|o| OrderAction::Cancel(o.id)).collect();
+        send_changes(target, &mut self.oms, cancels, None, &self.order_events_tx);
+    }
+
+    /// Hand the paper venue's pending order updates and fills on (to the backtest
+    /// first, if running one), if trading on paper.
+    fn dispatch_paper(&mut self) {
+        if let Some(Venue::Paper(paper)) = &mut self.venue {
+            let updates = paper.drain_updates();
+            if let Some(backtest) = &mut self.backtest {
+                updates.iter().for_each(|u| backtest.on_order_update(u));
+            }
+            dispatch_order_updates(updates, &mut self.oms, &mut self.strats);
+        }
+    }
+}
+
+/// Hand public market data (everything except books) to the strategy trading that instrument.
+fn dispatch_market_data(
+    msg: &OkxWsMessage,
+    strats: &mut HashMap<String, Box<dyn Strategy>>,
+    indices: &HashMap<String, String>,
+) {
+    fn each<T>(
+        strats: &mut HashMap<String, Box<dyn Strategy>>,
+        items: impl Iterator<Item = T>,
+        inst_id: impl Fn(&T) -> &str,
+        hook: impl Fn(&mut dyn Strategy, &T) -> Vec<OrderRequest>,
+    ) {
+        for item in items {
+            let id = inst_id(&item);
+            if let Some(strat) = strats.get_mut(id) {
+                for req in hook(strat.as_mut(), &item) {
+                    println!("▶️  {} OrderRequest from market data: {:?}", id, req);
+                }
+            }
+        }
+    }
+
+    match msg {
+        OkxWsMessage::Trades(push) => {
+            each(strats, push.data.iter().filter_map(Trade::from_okx), |t| &t.inst_id, |s, t| s.on_trade(t))
+        }
+        OkxWsMessage::Tickers(push) => {
+            each(strats, push.data.iter().filter_map(Ticker::from_okx), |t| &t.inst_id, |s, t| s.on_ticker(t))
+        }
+        OkxWsMessage::MarkPrice(push) => {
+            each(strats, push.data.iter().filter_map(MarkPrice::from_okx), |m| &m.inst_id, |s, m| s.on_mark_price(m))
+        }
+        OkxWsMessage::FundingRate(push) => {
+            each(strats, push.data.iter().filter_map(FundingRate::from_okx), |f| &f.inst_id, |s, f| s.on_funding_rate(f))
+        }
+        OkxWsMessage::OpenInterest(push) => {
+            each(strats, push.data.iter().filter_map(OpenInterest::from_okx), |o| &o.inst_id, |s, o| {
+                println!("📈 {}", o);
+                s.on_open_interest(o)
+            })
+        }
+        OkxWsMessage::IndexTickers(push) => {
+            // one index feeds every instrument on that underlying
+            for index in push.data.iter().filter_map(IndexPrice::from_okx) {
+                for (inst_id, _) in indices.iter().filter(|(_, idx)| **idx == index.index) {
+                    if let Some(strat) = strats.get_mut(inst_id) {
+                        for req in strat.on_index_price(&index) {
+                            println!("▶️  {} OrderRequest from on_index_price: {:?}", inst_id, req);
+                        }
+                    }
+                }
+            }
+        }
+        OkxWsMessage::Event(_)
+        | OkxWsMessage::Books(_)
+        | OkxWsMessage::Candles(_)
+        | OkxWsMessage::Orders(_)
+        | OkxWsMessage::Positions(_)
+        | OkxWsMessage::Account(_)
+        | OkxWsMessage::BalanceAndPosition(_)
+        | OkxWsMessage::OpReply(_) => {}
+    }
+}
+
+/// Hand our own orders, fills and positions to the strategy trading that instrument;
+/// balances go to every strategy. Order updates go through the OMS first.
+fn dispatch_account(msg: &OkxWsMessage, oms: &mut OrderManager, strats: &mut HashMap<String, Box<dyn Strategy>>) {
+    fn positions(positions: Vec<Position>, strats: &mut HashMap<String, Box<dyn Strategy>>) {
+        for position in positions {
+            println!("📍 {}", position);
+            if let Some(strat) = strats.get_mut(&position.inst_id) {
+                strat.on_position(&position);
+            }
+        }
+    }
+    fn account(account: AccountUpdate, strats: &mut HashMap<String, Box<dyn Strategy>>) {
+        println!("💰 {}", account);
+        for strat in strats.values_mut() {
+            strat.on_account(&account);
+        }
+    }
+
+    match msg {
+        OkxWsMessage::Orders(push) => dispatch_order_updates(push.data.iter().filter_map(OrderUpdate::from_okx), oms, strats),
+        OkxWsMessage::Positions(push) => positions(push.data.iter().filter_map(Position::from_okx).collect(), strats),
+        OkxWsMessage::Account(push) => {
+            for update in push.data.iter().filter_map(AccountUpdate::from_okx) {
+                account(update, strats);
+            }
+        }
+        OkxWsMessage::BalanceAndPosition(push) => {
+            for (update, pos) in push.data.iter().filter_map(AccountUpdate::from_balance_and_position) {
+                account(update, strats);
+                positions(pos, strats);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Move our orders through the OMS, then hand each update (and any fill) to the
+/// strategy trading that instrument.
+fn dispatch_order_updates(
+    updates: impl IntoIterator<Item = OrderUpdate>,
+    oms: &mut OrderManager,
+    strats: &mut HashMap<String, Box<dyn Strategy>>,
+) {
+    for update in updates {
+        if let Some(order) = oms.on_update(&update) {
+            println!("🧾 {} {} {}", order.inst_id, order.cl_ord_id, order.status);
+        }
+        let Some(strat) = strats.get_mut(&update.inst_id) else {
+            continue;
+        };
+        strat.on_order_update(&update);
+        if let (Some(fill), Some(exec)) = (update.to_fill(), &update.execution) {
+            println!(
+                "✅ {} filled {:?} {} @ {} (trade {}, {}, fee {} {})",
+                update.inst_id,
+                fill.side,
+                fill.size,
+                fill.entry_price,
+                exec.trade_id,
+                if exec.maker { "maker" } else { "taker" },
+                exec.fee,
+                exec.fee_ccy
+            );
+            strat.on_order_filled(fill);
+        }
+    }
+}
+
+/// Where orders go: OKX, or the in-process paper venue.
+pub enum Venue {
+    Live(ExecutionGateway),
+    Paper(PaperVenue),
+}
+
+/// Fold `candle*` rows into the bar store, then hand each to the instrument's strategy.
+fn dispatch_candles(
+    push: &WsPush<Vec<String>>,
+    bars: &mut BarStore,
+    strats: &mut HashMap<String, Box<dyn Strategy>>,
+) {
+    let Some(bar) = Bar::from_channel(&push.arg.channel) else {
+        return;
+    };
+    let inst_id = push.arg.instId.as_str();
+    for candle in push.data.iter().filter_map(|row| Candle::from_okx(row)) {
+        bars.insert(inst_id, bar, candle);
+        if let Some(strat) = strats.get_mut(inst_id) {
+            for req in strat.on_candle(inst_id, bar, &candle, bars) {
+                println!("▶️  {} OrderRequest from on_candle: {:?}", inst_id, req);
+            }
+        }
+    }
+}
+
+/// How often, and how far apart, to ask OKX about a place that got no answer
+/// before taking it as never having arrived.
+const PLACE_QUERIES: usize = 3;
+const PLACE_QUERY_DELAY: Duration = Duration::from_secs(1);
+
+/// Results of gateway calls, handed back to the OMS by the tasks that made them.
+pub enum OrderEvent {
+    Placed(OrderAck),
+    /// Answer to an amend or cancel.
+    Changed(OrderAck),
+    /// An amend or cancel got no answer; the order may be changed again.
+    ChangeFailed(Uuid),
+    /// OKX's view of an order whose request failed; `None` if it never got there.
+    State(Uuid, Option<OrderUpdate>),
+    /// Every order OKX lists as live, plus the latest state of the local open
+    /// orders (as of `as_of`) it doesn't list.
+    Reconciled { orders: Vec<OrderUpdate>, as_of: u64 },
+}
+
+/// OKX's side of a reconciliation: its live orders, then each of `local` it didn't list.
+async fn exchange_orders(gateway: &ExecutionGateway, local: Vec<(String, String)>) -> Result<Vec<OrderUpdate>, ExecError> {
+    let mut orders = gateway.pending_orders().await?;
+    for (inst_id, cl_ord_id) in local {
+        if !orders.iter().any(|o| o.cl_ord_id == cl_ord_id) {
+            orders.extend(gateway.order_state(&inst_id, &cl_ord_id).await?);
+        }
+    }
+    Ok(orders)
+}
+
+/// Register `reqs` with the OMS and send them as one batch, each built by
+/// `new_order` from the request and its `clOrdId`; the venue's answers come
+/// back on `order_events`.
+fn send_places(
+    venue: &mut Venue,
+    oms: &mut OrderManager,
+    inst_id: &str,
+    reqs: Vec<OrderRequest>,
+    book: &OrderBook,
+    new_order: impl Fn(&OrderRequest, &str) -> NewOrder,
+    order_events: &UnboundedSender<OrderEvent>,
+) {
+    let mut ids = Vec::new();
+    let mut orders = Vec::new();
+    for req in &reqs {
+        let managed = oms.submit(inst_id, inst_id, req);
+        ids.push(managed.id);
+        orders.push(new_order(req, &managed.cl_ord_id));
+    }
+    let gateway = match venue {
+        Venue::Live(gateway) => gateway.clone(),
+        Venue::Paper(paper) => {
+            for order in &orders {
+                let _ = order_events.send(OrderEvent::Placed(paper.place(order, Some(book))));
+            }
+            return;
+        }
+    };
+    if orders.is_empty() {
+        return;
+    }
+    let order_events = order_events.clone();
+    tokio::spawn(async move {
+        let results = gateway.place_batch(&orders).await;
+        join_all(ids.into_iter().zip(orders).zip(reqs).zip(results).map(|(((id, order), req), result)| {
+            let gateway = &gateway;
+            let order_events = &order_events;
+            async move {
+                let event = match result {
+                    Ok(ack) => OrderEvent::Placed(ack),
+                    Err(e) => {
+                        // timed out or the socket dropped: ask OKX whether it got there,
+                        // giving a request still in flight time to land first
+                        eprintln!("⚠️ {} {:?} failed: {}", order.cl_ord_id, req, e);
+                        match place_outcome(gateway, &order).await {
+                            Some(state) => OrderEvent::State(id, state),
+                            None => return,
+                        }
+                    }
+                };
+                let _ = order_events.send(event);
+            }
+        }))
+        .await;
+    });
+}
+
+/// What became of a place that got no answer: OKX's view of the order, or
+/// `Some(None)` if it never got there. `None` if OKX can't be asked.
+async fn place_outcome(gateway: &ExecutionGateway, order: &NewOrder) -> Option<Option<OrderUpdate>> {
+    for _ in 0..PLACE_QUERIES {
+        tokio::time::sleep(PLACE_QUERY_DELAY).await;
+        match gateway.order_state(&order.inst_id, &order.cl_ord_id).await {
+            Ok(None) => continue,
+            Ok(found) => return Some(found),
+            Err(e) => {
+                eprintln!("⚠️ {} state unknown until reconnect: {}", order.cl_ord_id, e);
+                return None;
+            }
+        }
+    }
+    Some(None)
+}
+
+/// Send amends and cancels of working orders, batched per kind when live;
+/// answers come back on `order_events`.
+fn send_changes(
+    venue: &mut Venue,
+    oms: &mut OrderManager,
+    actions: Vec<OrderAction>,
+    book: Option<&OrderBook>,
+    order_events: &UnboundedSender<OrderEvent>,
+) {
+    let precision = book.map(|b| b.precision);
+    let (mut cancels, mut amends) = (Vec::new(), Vec::new());
+    for action in actions {
+        match action {
+            OrderAction::Cancel(id) => {
+                let Some(cancel) = oms.request_cancel(&id) else {
+                    continue;
+                };
+                match venue {
+                    Venue::Live(_) => cancels.push((id, cancel)),
+                    Venue::Paper(paper) => {
+                        let _ = order_events.send(OrderEvent::Changed(paper.cancel(&cancel)));
+                    }
+                }
+            }
+            OrderAction::Amend { id, price, size } => {
+                // amends are priced on the book's grid; without a book only cancels go out
+                let Some(amend) = precision.and_then(|p| oms.request_amend(&id, price, size, &p)) else {
+                    continue;
+                };
+                match venue {
+                    Venue::Live(_) => amends.push((id, amend)),
+                    Venue::Paper(paper) => {
+                        let _ = order_events.send(OrderEvent::Changed(paper.amend(&amend, book)));
+                    }
+                }
+            }
+        }
+    }
+    let Venue::Live(gateway) = venue else {
+        return;
+    };
+    if !cancels.is_empty() {
+        let (gateway, order_events) = (gateway.clone(), order_events.clone());
+        tokio::spawn(async move {
+            let (ids, cancels): (Vec<_>, Vec<_>) = cancels.into_iter().unzip();
+            let results = gateway.cancel_batch(&cancels).await;
+            changed("Cancel", ids, results, &order_events);
+        });
+    }
+    if !amends.is_empty() {
+        let (gateway, order_events) = (gateway.clone(), order_events.clone());
+        tokio::spawn(async move {
+            let (ids, amends): (Vec<_>, Vec<_>) = amends.into_iter().unzip();
+            let results = gateway.amend_batch(&amends).await;
+            changed("Amend", ids, results, &order_events);
+        });
+    }
+}
+
+/// Hand each order's answer to an amend or cancel back to the OMS.
+fn changed(what: &str, ids: Vec<Uuid>, results: Vec<Result<OrderAck, ExecError>>, order_events: &UnboundedSender<OrderEvent>) {
+    for (id, result) in ids.into_iter().zip(results) {
+        let event = match result {
+            Ok(ack) => OrderEvent::Changed(ack),
+            Err(e) => {
+                eprintln!("⚠️ {} of {} failed: {}", what, id, e);
+                OrderEvent::ChangeFailed(id)
+            }
+        };
+        let _ = order_events.send(event);
+    }
+}
+
+/// The replay's next event; only polled while there is a replay.
+async fn next_replay(replay: &mut Option<Replay>) -> Option<ReplayEvent> {
+    match replay {
+        Some(replay) => replay.next().await,
+        None => None,
+    }
+}
+
+/// Wall clock, Unix ms.
+pub fn unix_ms() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
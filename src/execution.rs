@@ -0,0 +1,611 @@
+//! Order entry: place, amend and cancel orders, singly or in batches, over
+//! OKX's private WebSocket trading ops with the signed REST endpoints as a
+//! fallback when the socket is down. Every order carries a client order id
+//! (`clOrdId`) so acknowledgements can be matched back to the `OrderRequest`
+//! that produced them.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures::future::join_all;
+use serde_json::{json, Value};
+
+use crate::account::OrderUpdate;
+use crate::models::{OrderAckData, OrderData};
+use crate::precision::Precision;
+use crate::sources::okx::OkxFeed;
+use crate::sources::okx_rest::{rest_codes, OkxError, OkxRestClient};
+use crate::strategy::{GridPoint, OrderRequest, Side};
+
+/// OKX accepts at most 20 orders per batch op; longer batches are split.
+pub const MAX_BATCH: usize = 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrdType {
+    Limit,
+    /// Rejected instead of taking liquidity.
+    PostOnly,
+    Ioc,
+    Fok,
+    Market,
+}
+
+impl OrdType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "limit" => Some(OrdType::Limit),
+            "post_only" => Some(OrdType::PostOnly),
+            "ioc" => Some(OrdType::Ioc),
+            "fok" => Some(OrdType::Fok),
+            "market" => Some(OrdType::Market),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrdType::Limit => "limit",
+            OrdType::PostOnly => "post_only",
+            OrdType::Ioc => "ioc",
+            OrdType::Fok => "fok",
+            OrdType::Market => "market",
+        }
+    }
+}
+
+/// Margin mode (`tdMode`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TdMode {
+    /// Spot without margin.
+    Cash,
+    Cross,
+}
+
+impl TdMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TdMode::Cash => "cash",
+            TdMode::Cross => "cross",
+        }
+    }
+}
+
+/// Identifies an existing order by OKX's id or ours.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderRef {
+    OrdId(String),
+    ClOrdId(String),
+}
+
+impl OrderRef {
+    fn insert_into(&self, args: &mut Value) {
+        match self {
+            OrderRef::OrdId(id) => args["ordId"] = id.as_str().into(),
+            OrderRef::ClOrdId(id) => args["clOrdId"] = id.as_str().into(),
+        }
+    }
+
+    fn answered_by(&self, ack: &OrderAckData) -> bool {
+        match self {
+            OrderRef::OrdId(id) => ack.ordId == *id,
+            OrderRef::ClOrdId(id) => ack.clOrdId == *id,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct NewOrder {
+    pub inst_id: String,
+    /// Up to 32 alphanumerics, unique among our live orders.
+    pub cl_ord_id: String,
+    pub side: Side,
+    pub ord_type: OrdType,
+    pub td_mode: TdMode,
+    /// Exact decimal strings on the instrument's grid; `px` is left out of market orders.
+    pub px: String,
+    pub sz: String,
+    pub reduce_only: bool,
+}
+
+impl NewOrder {
+    /// Post-only order for a strategy's request. A normalised request (see
+    /// `OrderNormalizer`) is written out from its ticks and lots as they are;
+    /// any other is rounded to the nearest step of `precision`.
+    pub fn from_request(inst_id: impl Into<String>, cl_ord_id: impl Into<String>, req: &OrderRequest, precision: &Precision, td_mode: TdMode) -> Self {
+        let grid = req.grid.unwrap_or_else(|| GridPoint {
+            ticks: precision.tick.steps_f64(req.price),
+            lots: precision.lot.steps_f64(req.size),
+        });
+        Self {
+            inst_id: inst_id.into(),
+            cl_ord_id: cl_ord_id.into(),
+            side: req.side,
+            ord_type: OrdType::PostOnly,
+            td_mode,
+            px: precision.tick.format(grid.ticks),
+            sz: precision.lot.format(grid.lots),
+            reduce_only: false,
+        }
+    }
+
+    pub fn with_ord_type(mut self, ord_type: OrdType) -> Self {
+        self.ord_type = ord_type;
+        self
+    }
+
+    /// Only ever shrink the position (derivatives and margin).
+    pub fn reduce_only(mut self) -> Self {
+        self.reduce_only = true;
+        self
+    }
+}
+
+impl OpArgs for NewOrder {
+    fn args(&self) -> Value {
+        let mut args = json!({
+            "instId": self.inst_id,
+            "clOrdId": self.cl_ord_id,
+            "side": match self.side { Side::Buy => "buy", Side::Sell => "sell" },
+            "ordType": self.ord_type.as_str(),
+            "tdMode": self.td_mode.as_str(),
+            "sz": self.sz,
+        });
+        if self.ord_type != OrdType::Market {
+            args["px"] = self.px.as_str().into();
+        }
+        if self.reduce_only {
+            args["reduceOnly"] = true.into();
+        }
+        args
+    }
+
+    fn answered_by(&self, ack: &OrderAckData) -> bool {
+        ack.clOrdId == self.cl_ord_id
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AmendOrder {
+    pub inst_id: String,
+    pub order: OrderRef,
+    /// Echoed back in the ack, so an amend's answer can't be mistaken for another's.
+    pub req_id: Option<String>,
+    pub new_px: Option<String>,
+    pub new_sz: Option<String>,
+    /// Cancel instead of leaving the order untouched if the amend fails.
+    pub cancel_on_fail: bool,
+}
+
+impl OpArgs for AmendOrder {
+    fn args(&self) -> Value {
+        let mut args = json!({ "instId": self.inst_id, "cxlOnFail": self.cancel_on_fail });
+        self.order.insert_into(&mut args);
+        if let Some(req_id) = &self.req_id {
+            args["reqId"] = req_id.as_str().into();
+        }
+        if let Some(px) = &self.new_px {
+            args["newPx"] = px.as_str().into();
+        }
+        if let Some(sz) = &self.new_sz {
+            args["newSz"] = sz.as_str().into();
+        }
+        args
+    }
+
+    fn answered_by(&self, ack: &OrderAckData) -> bool {
+        match &self.req_id {
+            Some(req_id) => ack.reqId == *req_id,
+            None => self.order.answered_by(ack),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CancelOrder {
+    pub inst_id: String,
+    pub order: OrderRef,
+}
+
+impl OpArgs for CancelOrder {
+    fn args(&self) -> Value {
+        let mut args = json!({ "instId": self.inst_id });
+        self.order.insert_into(&mut args);
+        args
+    }
+
+    fn answered_by(&self, ack: &OrderAckData) -> bool {
+        self.order.answered_by(ack)
+    }
+}
+
+/// One item of a trading op: its arguments, and which result in OKX's reply is its own.
+trait OpArgs {
+    fn args(&self) -> Value;
+    fn answered_by(&self, ack: &OrderAckData) -> bool;
+}
+
+/// How a request reached OKX.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Route {
+    WebSocket,
+    Rest,
+    /// Answered by the paper venue; never left the process.
+    Paper,
+}
+
+/// OKX's verdict on one order in a request.
+#[derive(Debug, Clone)]
+pub struct OrderAck {
+    pub cl_ord_id: String,
+    pub ord_id: String,
+    /// The amend's `reqId`; empty otherwise.
+    pub req_id: String,
+    /// `0` on success, otherwise an OKX error code such as `51008` (insufficient balance).
+    pub code: String,
+    pub msg: String,
+    pub route: Route,
+}
+
+impl OrderAck {
+    fn from_okx(data: OrderAckData, route: Route) -> Self {
+        Self {
+            cl_ord_id: data.clOrdId,
+            ord_id: data.ordId,
+            req_id: data.reqId,
+            code: data.sCode,
+            msg: data.sMsg,
+            route,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.code == "0"
+    }
+}
+
+/// Cheap to clone, so a failed batch can fail each of its orders.
+#[derive(Debug, Clone)]
+pub enum ExecError {
+    /// The request was sent but no reply came back in time, or the socket
+    /// dropped first. The order may or may not exist; reconcile before retrying.
+    Unknown(String),
+    /// OKX rejected the whole request (bad parameters, auth, rate limit ...).
+    Api { code: String, msg: String },
+    Rest(Arc<OkxError>),
+}
+
+impl fmt::Display for ExecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecError::Unknown(why) => write!(f, "outcome unknown: {}", why),
+            ExecError::Api { code, msg } => write!(f, "OKX error {}: {}", code, msg),
+            ExecError::Rest(e) => write!(f, "REST: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExecError {}
+
+impl From<OkxError> for ExecError {
+    fn from(e: OkxError) -> Self {
+        match e {
+            OkxError::Api { code, msg } => ExecError::Api { code, msg },
+            e => ExecError::Rest(Arc::new(e)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Op {
+    Place,
+    Amend,
+    Cancel,
+}
+
+impl Op {
+    /// WebSocket op and REST path, single or batch.
+    fn endpoint(&self, batch: bool) -> (&'static str, &'static str) {
+        match (self, batch) {
+            (Op::Place, false) => ("order", "/api/v5/trade/order"),
+            (Op::Place, true) => ("batch-orders", "/api/v5/trade/batch-orders"),
+            (Op::Amend, false) => ("amend-order", "/api/v5/trade/amend-order"),
+            (Op::Amend, true) => ("batch-amend-orders", "/api/v5/trade/amend-batch-orders"),
+            (Op::Cancel, false) => ("cancel-order", "/api/v5/trade/cancel-order"),
+            (Op::Cancel, true) => ("batch-cancel-orders", "/api/v5/trade/cancel-batch-orders"),
+        }
+    }
+}
+
+/// How long to wait for a WebSocket reply; OKX also drops the request if it
+/// arrives later than this.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Sends orders over a logged-in private `OkxFeed` when it's connected, and
+/// over signed REST otherwise. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct ExecutionGateway {
+    feed: Option<OkxFeed>,
+    rest: OkxRestClient,
+}
+
+impl ExecutionGateway {
+    /// `rest` must carry credentials; `feed`, if any, must be a logged-in private connection.
+    pub fn new(feed: Option<OkxFeed>, rest: OkxRestClient) -> Self {
+        Self { feed, rest }
+    }
+
+    /// One result per order, in order; see `submit_batch`.
+    pub async fn place_batch(&self, orders: &[NewOrder]) -> Vec<Result<OrderAck, ExecError>> {
+        self.submit_batch(Op::Place, orders).await
+    }
+
+    pub async fn amend_batch(&self, amends: &[AmendOrder]) -> Vec<Result<OrderAck, ExecError>> {
+        self.submit_batch(Op::Amend, amends).await
+    }
+
+    pub async fn cancel_batch(&self, cancels: &[CancelOrder]) -> Vec<Result<OrderAck, ExecError>> {
+        self.submit_batch(Op::Cancel, cancels).await
+    }
+
+    /// Every live order on the account, from REST (`orders-pending`, paged by `ordId`).
+    pub async fn pending_orders(&self) -> Result<Vec<OrderUpdate>, ExecError> {
+        const PAGE: usize = 100;
+        let mut orders = Vec::new();
+        let mut after: Option<String> = None;
+        loop {
+            let mut query = vec![("limit", PAGE.to_string())];
+            if let Some(after) = &after {
+                query.push(("after", after.clone()));
+            }
+            let rows: Vec<OrderData> = self.rest.get_signed("/api/v5/trade/orders-pending", &query).await?;
+            orders.extend(rows.iter().filter_map(OrderUpdate::from_okx));
+            match rows.last() {
+                Some(last) if rows.len() == PAGE => after = Some(last.ordId.clone()),
+                _ => return Ok(orders),
+            }
+        }
+    }
+
+    /// Latest state of one of our orders; `None` if OKX has never seen it.
+    pub async fn order_state(&self, inst_id: &str, cl_ord_id: &str) -> Result<Option<OrderUpdate>, ExecError> {
+        let query = [("instId", inst_id.to_string()), ("clOrdId", cl_ord_id.to_string())];
+        match self.rest.get_signed::<OrderData>("/api/v5/trade/order", &query).await {
+            Ok(rows) => Ok(rows.iter().find_map(OrderUpdate::from_okx)),
+            Err(OkxError::Api { code, .. }) if code == rest_codes::ORDER_NOT_FOUND => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Send `items` in batches of up to `MAX_BATCH`, all at once; a lone item
+    /// goes out as a single op. An error for a whole batch (timeout, rejected
+    /// request) fails each of its items; otherwise each item gets its own
+    /// result, `sCode` and all.
+    async fn submit_batch<T: OpArgs>(&self, op: Op, items: &[T]) -> Vec<Result<OrderAck, ExecError>> {
+        let batches = join_all(items.chunks(MAX_BATCH).map(|chunk| self.submit(op, chunk, chunk.len() > 1))).await;
+        batches.into_iter().flatten().collect()
+    }
+
+    /// One result per item: its own ack from OKX's reply, found by `clOrdId`,
+    /// `ordId` or `reqId`.
+    async fn submit<T: OpArgs>(&self, op: Op, items: &[T], batch: bool) -> Vec<Result<OrderAck, ExecError>> {
+        let (ws_op, path) = op.endpoint(batch);
+        let args: Vec<Value> = items.iter().map(OpArgs::args).collect();
+
+        let exp_time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default() + REPLY_TIMEOUT;
+        let reply = self.feed.as_ref().and_then(|feed| feed.request(ws_op, args.clone(), Some(exp_time.as_millis() as u64)));
+        let (rows, route) = match reply {
+            Some(reply) => match tokio::time::timeout(REPLY_TIMEOUT, reply).await {
+                // `1`/`2` still list each order's own result
+                Ok(Ok(reply)) if reply.code != "0" && reply.data.is_empty() => {
+                    (Err(ExecError::Api { code: reply.code, msg: reply.msg }), Route::WebSocket)
+                }
+                Ok(Ok(reply)) => (Ok(reply.data), Route::WebSocket),
+                Ok(Err(_)) => (Err(ExecError::Unknown("connection lost before reply".to_string())), Route::WebSocket),
+                Err(_) => (Err(ExecError::Unknown(format!("no reply within {:?}", REPLY_TIMEOUT))), Route::WebSocket),
+            },
+            // Socket down: REST takes an object for single ops and an array for batches
+            None => {
+                let body = if batch { Value::Array(args) } else { args.into_iter().next().unwrap_or_default() };
+                (self.rest.post_signed::<OrderAckData>(path, &body).await.map_err(ExecError::from), Route::Rest)
+            }
+        };
+        match rows {
+            Ok(rows) => match_acks(items, rows, route),
+            Err(e) => vec![Err(e); items.len()],
+        }
+    }
+}
+
+/// Pair each item with its ack. OKX leaves the ids out of some rejections, so
+/// an unclaimed ack without ids goes to the item in its position.
+fn match_acks<T: OpArgs>(items: &[T], rows: Vec<OrderAckData>, route: Route) -> Vec<Result<OrderAck, ExecError>> {
+    let mut rows: Vec<Option<OrderAckData>> = rows.into_iter().map(Some).collect();
+    let mut acks: Vec<Option<OrderAckData>> = items
+        .iter()
+        .map(|item| rows.iter_mut().find(|row| row.as_ref().is_some_and(|r| item.answered_by(r))).and_then(Option::take))
+        .collect();
+    for (ack, row) in acks.iter_mut().zip(rows.iter_mut()) {
+        if ack.is_none() && row.as_ref().is_some_and(|r| r.clOrdId.is_empty() && r.ordId.is_empty() && r.reqId.is_empty()) {
+            *ack = row.take();
+        }
+    }
+    acks.into_iter()
+        .map(|ack| match ack {
+            Some(data) => Ok(OrderAck::from_okx(data, route)),
+            None => Err(ExecError::Unknown("no result for this order in the reply".to_string())),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_util::{SinkExt, StreamExt};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+    use tokio_tungstenite::tungstenite::Message;
+
+    use crate::sources::okx::FeedConfig;
+    use crate::sources::okx_auth::Credentials;
+    use crate::sources::okx_rest::MockRest;
+
+    fn order(cl_ord_id: &str) -> NewOrder {
+        NewOrder {
+            inst_id: "BTC-USDT".to_string(),
+            cl_ord_id: cl_ord_id.to_string(),
+            side: Side::Buy,
+            ord_type: OrdType::PostOnly,
+            td_mode: TdMode::Cash,
+            px: "100".to_string(),
+            sz: "1".to_string(),
+            reduce_only: false,
+        }
+    }
+
+    fn ack(cl_ord_id: &str, code: &str) -> Value {
+        json!({ "clOrdId": cl_ord_id, "ordId": format!("9{}", cl_ord_id), "sCode": code, "sMsg": "" })
+    }
+
+    fn rest(mock: &MockRest) -> OkxRestClient {
+        OkxRestClient::new().with_base_url(mock.url.as_str()).with_credentials(Credentials::new("key", "secret", "pass"))
+    }
+
+    /// A feed whose connection never completes: the listener accepts nothing.
+    async fn down_feed() -> (OkxFeed, TcpListener, mpsc::UnboundedReceiver<crate::sources::okx::FeedEvent>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (events_tx, events) = mpsc::unbounded_channel();
+        let feed = OkxFeed::spawn_into(FeedConfig::with_url(format!("ws://{}", listener.local_addr().unwrap())), Vec::new(), events_tx);
+        (feed, listener, events)
+    }
+
+    #[test]
+    fn orders_carry_their_type_and_reduce_only() {
+        let args = order("a").args();
+        assert_eq!(args["ordType"], "post_only");
+        assert_eq!(args["px"], "100");
+        assert!(args.get("reduceOnly").is_none());
+
+        let market = order("a").with_ord_type(OrdType::Market).reduce_only().args();
+        assert_eq!(market["ordType"], "market");
+        assert!(market.get("px").is_none());
+        assert_eq!(market["reduceOnly"], true);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_rest_while_the_socket_is_down() {
+        let (feed, _listener, _events) = down_feed().await;
+        assert!(!feed.is_connected());
+        let mock = MockRest::start(vec![MockRest::ok(json!([ack("a", "0")]))]).await;
+        let gateway = ExecutionGateway::new(Some(feed), rest(&mock));
+
+        let acks = gateway.place_batch(&[order("a")]).await;
+        let ack = acks[0].as_ref().unwrap();
+        assert!(ack.is_ok());
+        assert_eq!((ack.ord_id.as_str(), ack.route), ("9a", Route::Rest));
+
+        // a lone order goes to the single endpoint, as an object
+        let requests = mock.requests();
+        assert_eq!((requests[0].method.as_str(), requests[0].target.as_str()), ("POST", "/api/v5/trade/order"));
+        let body: Value = serde_json::from_str(&requests[0].body).unwrap();
+        assert_eq!(body["clOrdId"], "a");
+        assert_eq!(body["ordType"], "post_only");
+    }
+
+    #[tokio::test]
+    async fn each_order_in_a_batch_gets_its_own_result() {
+        // partial success: OKX answers `2`, out of order, with each order's own sCode
+        let reply = json!({ "code": "2", "msg": "", "data": [ack("b", "51008"), ack("a", "0")] }).to_string();
+        let mock = MockRest::start(vec![(200, reply)]).await;
+        let gateway = ExecutionGateway::new(None, rest(&mock));
+
+        let acks = gateway.place_batch(&[order("a"), order("b"), order("c")]).await;
+        assert_eq!(mock.requests()[0].target, "/api/v5/trade/batch-orders");
+        let a = acks[0].as_ref().unwrap();
+        assert!(a.is_ok());
+        assert_eq!(a.cl_ord_id, "a");
+        let b = acks[1].as_ref().unwrap();
+        assert!(!b.is_ok());
+        assert_eq!((b.cl_ord_id.as_str(), b.code.as_str()), ("b", "51008"));
+        // no answer for `c`: its fate is unknown
+        assert!(matches!(acks[2], Err(ExecError::Unknown(_))));
+    }
+
+    #[tokio::test]
+    async fn a_rejected_batch_fails_every_order_in_it() {
+        let reply = json!({ "code": "50011", "msg": "Too Many Requests", "data": [] }).to_string();
+        let mock = MockRest::start(vec![(200, reply)]).await;
+        let gateway = ExecutionGateway::new(None, rest(&mock));
+
+        let acks = gateway.place_batch(&[order("a"), order("b")]).await;
+        assert_eq!(acks.len(), 2);
+        assert!(acks.iter().all(|ack| matches!(ack, Err(ExecError::Api { code, .. }) if code == "50011")));
+    }
+
+    #[tokio::test]
+    async fn batches_split_at_the_okx_limit() {
+        let orders: Vec<_> = (0..MAX_BATCH + 5).map(|i| order(&format!("o{}", i))).collect();
+        let every_ack = MockRest::ok(Value::Array(orders.iter().map(|o| ack(&o.cl_ord_id, "0")).collect()));
+        let mock = MockRest::start(vec![every_ack.clone(), every_ack]).await;
+        let gateway = ExecutionGateway::new(None, rest(&mock));
+
+        let acks = gateway.place_batch(&orders).await;
+        assert!(acks.iter().all(|ack| ack.as_ref().is_ok_and(OrderAck::is_ok)));
+        let mut sizes: Vec<_> = mock
+            .requests()
+            .iter()
+            .map(|r| serde_json::from_str::<Value>(&r.body).unwrap().as_array().unwrap().len())
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![5, MAX_BATCH]);
+    }
+
+    #[tokio::test]
+    async fn websocket_replies_are_matched_to_amends_by_req_id() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let (events_tx, _events) = mpsc::unbounded_channel();
+        let feed = OkxFeed::spawn_into(FeedConfig::with_url(format!("ws://{}", listener.local_addr().unwrap())), Vec::new(), events_tx);
+        let mut ws = tokio_tungstenite::accept_async(listener.accept().await.unwrap().0).await.unwrap();
+        while !feed.is_connected() {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+        let gateway = ExecutionGateway::new(Some(feed), OkxRestClient::new());
+
+        let amend = |req_id: &str| AmendOrder {
+            inst_id: "BTC-USDT".to_string(),
+            order: OrderRef::ClOrdId("a".to_string()),
+            req_id: Some(req_id.to_string()),
+            new_px: Some("101".to_string()),
+            new_sz: None,
+            cancel_on_fail: false,
+        };
+        let amends = [amend("1"), amend("2")];
+        let results = tokio::spawn(async move { gateway.amend_batch(&amends).await });
+
+        let request: Value = loop {
+            if let Message::Text(txt) = ws.next().await.unwrap().unwrap() {
+                break serde_json::from_str(&txt).unwrap();
+            }
+        };
+        assert_eq!(request["op"], "batch-amend-orders");
+        assert_eq!(request["args"][1]["reqId"], "2");
+        // both answers name the same order; only reqId tells them apart
+        let reply = json!({
+            "id": request["id"],
+            "op": "batch-amend-orders",
+            "code": "2",
+            "msg": "",
+            "data": [
+                { "clOrdId": "a", "ordId": "9a", "reqId": "2", "sCode": "0", "sMsg": "" },
+                { "clOrdId": "a", "ordId": "9a", "reqId": "1", "sCode": "51503", "sMsg": "amend failed" },
+            ],
+        });
+        ws.send(Message::Text(reply.to_string().into())).await.unwrap();
+
+        let results = results.await.unwrap();
+        let first = results[0].as_ref().unwrap();
+        assert_eq!((first.req_id.as_str(), first.code.as_str(), first.route), ("1", "51503", Route::WebSocket));
+        let second = results[1].as_ref().unwrap();
+        assert_eq!((second.req_id.as_str(), second.code.as_str()), ("2", "0"));
+    }
+}
@@ -0,0 +1,107 @@
+//! OKX trading fee tiers. Rates are fractions of notional; a negative maker
+//! rate is a rebate. Spot/margin and derivatives have separate schedules.
+
+use crate::sources::okx_rest::InstType;
+
+/// Maker and taker rates for one instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct FeeRates {
+    pub maker: f64,
+    pub taker: f64,
+}
+
+impl FeeRates {
+    pub fn new(maker: f64, taker: f64) -> Self {
+        Self { maker, taker }
+    }
+
+    /// Fee on a fill of `notional` in OKX's sign convention: negative when
+    /// charged, positive for a rebate.
+    pub fn fee(&self, notional: f64, maker: bool) -> f64 {
+        -notional.abs() * if maker { self.maker } else { self.taker }
+    }
+}
+
+/// Account fee level: regular users are Lv1, VIP levels follow 30-day volume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FeeTier {
+    #[default]
+    Lv1,
+    Vip1,
+    Vip2,
+    Vip3,
+    Vip4,
+    Vip5,
+}
+
+impl FeeTier {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "lv1" => Some(FeeTier::Lv1),
+            "vip1" => Some(FeeTier::Vip1),
+            "vip2" => Some(FeeTier::Vip2),
+            "vip3" => Some(FeeTier::Vip3),
+            "vip4" => Some(FeeTier::Vip4),
+            "vip5" => Some(FeeTier::Vip5),
+            _ => None,
+        }
+    }
+
+    /// OKX's published rates for this tier on `inst_type`.
+    pub fn rates(&self, inst_type: InstType) -> FeeRates {
+        let spot = matches!(inst_type, InstType::Spot | InstType::Margin);
+        let (maker, taker) = match (self, spot) {
+            (FeeTier::Lv1, true) => (0.0008, 0.0010),
+            (FeeTier::Vip1, true) => (0.00045, 0.0005),
+            (FeeTier::Vip2, true) => (0.0004, 0.00045),
+            (FeeTier::Vip3, true) => (0.0003, 0.0004),
+            (FeeTier::Vip4, true) => (0.0002, 0.00035),
+            (FeeTier::Vip5, true) => (0.0, 0.0003),
+            (FeeTier::Lv1, false) => (0.0002, 0.0005),
+            (FeeTier::Vip1, false) => (0.00015, 0.0004),
+            (FeeTier::Vip2, false) => (0.0001, 0.00035),
+            (FeeTier::Vip3, false) => (0.00005, 0.0003),
+            (FeeTier::Vip4, false) => (0.0, 0.00027),
+            (FeeTier::Vip5, false) => (-0.00005, 0.00025),
+        };
+        FeeRates::new(maker, taker)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tiers_carry_okx_published_rates() {
+        assert_eq!(FeeTier::Lv1.rates(InstType::Spot), FeeRates::new(0.0008, 0.0010));
+        assert_eq!(FeeTier::Vip5.rates(InstType::Spot), FeeRates::new(0.0, 0.0003));
+        assert_eq!(FeeTier::Lv1.rates(InstType::Swap), FeeRates::new(0.0002, 0.0005));
+        assert_eq!(FeeTier::Vip5.rates(InstType::Swap), FeeRates::new(-0.00005, 0.00025));
+        // margin follows spot, futures follow swaps
+        assert_eq!(FeeTier::Vip3.rates(InstType::Margin), FeeTier::Vip3.rates(InstType::Spot));
+        assert_eq!(FeeTier::Vip3.rates(InstType::Futures), FeeTier::Vip3.rates(InstType::Swap));
+    }
+
+    #[test]
+    fn tiers_parse_case_insensitively() {
+        assert_eq!(FeeTier::from_name("VIP5"), Some(FeeTier::Vip5));
+        assert_eq!(FeeTier::from_name("lv1"), Some(FeeTier::Lv1));
+        assert_eq!(FeeTier::from_name("vip6"), None);
+        assert_eq!(FeeTier::default(), FeeTier::Lv1);
+    }
+
+    #[test]
+    fn fees_are_negative_when_charged_and_positive_when_rebated() {
+        let lv1 = FeeTier::Lv1.rates(InstType::Swap);
+        assert!((lv1.fee(10_000.0, true) - -2.0).abs() < 1e-9);
+        assert!((lv1.fee(10_000.0, false) - -5.0).abs() < 1e-9);
+        // the notional's sign (a sell) doesn't flip the fee
+        assert!((lv1.fee(-10_000.0, false) - -5.0).abs() < 1e-9);
+
+        let vip5 = FeeTier::Vip5.rates(InstType::Swap);
+        assert!((vip5.fee(10_000.0, true) - 0.5).abs() < 1e-9);
+        assert!(vip5.fee(10_000.0, false) < 0.0);
+        assert_eq!(FeeTier::Vip5.rates(InstType::Spot).fee(10_000.0, true), 0.0);
+    }
+}
@@ -0,0 +1,504 @@
+//! Typed instrument metadata and a registry that loads it from OKX, answers
+//! lookups by instId, currency or family, and diffs periodic refreshes into
+//! listings, delistings and state changes.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::time::Duration;
+
+use tokio::sync::mpsc;
+
+use crate::models::Instrument;
+use crate::precision::Precision;
+use crate::sources::okx_rest::{InstType, OkxRestClient};
+
+/// Contract type of a swap or future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CtType {
+    /// Margined and settled in the quote currency (e.g. USDT).
+    Linear,
+    /// Margined and settled in the base currency (e.g. BTC for BTC-USD-SWAP).
+    Inverse,
+}
+
+impl CtType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(CtType::Linear),
+            "inverse" => Some(CtType::Inverse),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InstState {
+    Live,
+    Suspend,
+    /// Listed but not yet trading (e.g. pre-market or a future before its list time).
+    Preopen,
+    Test,
+    /// A state this build doesn't know; still listed, but not treated as tradable.
+    Other(String),
+}
+
+impl InstState {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "live" => InstState::Live,
+            "suspend" => InstState::Suspend,
+            "preopen" => InstState::Preopen,
+            "test" => InstState::Test,
+            other => InstState::Other(other.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            InstState::Live => "live",
+            InstState::Suspend => "suspend",
+            InstState::Preopen => "preopen",
+            InstState::Test => "test",
+            InstState::Other(name) => name,
+        }
+    }
+}
+
+impl fmt::Display for InstState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptType {
+    Call,
+    Put,
+}
+
+/// One instrument with OKX's string fields parsed. Currency fields are empty
+/// strings where OKX leaves them blank (e.g. `baseCcy` on derivatives).
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstrumentSpec {
+    pub inst_id: String,
+    pub inst_type: InstType,
+    pub state: InstState,
+    pub base_ccy: String,
+    pub quote_ccy: String,
+    pub settle_ccy: String,
+    /// e.g. `BTC-USD` for BTC-USD-SWAP and its futures/options; empty for spot.
+    pub inst_family: String,
+    pub uly: String,
+    /// `tickSz` and `lotSz` as exact steps.
+    pub precision: Precision,
+    pub min_sz: f64,
+    pub max_lmt_sz: Option<f64>,
+    pub max_mkt_sz: Option<f64>,
+    /// Contract value (derivatives): one contract is `ct_val × ct_mult` of `ct_val_ccy`.
+    pub ct_val: Option<f64>,
+    pub ct_mult: Option<f64>,
+    pub ct_val_ccy: String,
+    pub ct_type: Option<CtType>,
+    pub opt_type: Option<OptType>,
+    pub strike: Option<f64>,
+    pub max_leverage: Option<f64>,
+    /// Unix milliseconds.
+    pub list_time: Option<u64>,
+    /// Expiry (futures/options), Unix milliseconds.
+    pub exp_time: Option<u64>,
+}
+
+impl InstrumentSpec {
+    /// `None` if the instrument lacks an id, a known type, a state, or a valid tick/lot grid.
+    pub fn from_okx(inst: &Instrument) -> Option<Self> {
+        Some(Self {
+            inst_id: field(&inst.instId)?.to_string(),
+            inst_type: InstType::from_name(field(&inst.instType)?)?,
+            state: InstState::from_name(field(&inst.state)?),
+            base_ccy: text(&inst.baseCcy),
+            quote_ccy: text(&inst.quoteCcy),
+            settle_ccy: text(&inst.settleCcy),
+            inst_family: text(&inst.instFamily),
+            uly: text(&inst.uly),
+            precision: Precision::from_instrument(inst)?,
+            min_sz: number(&inst.minSz).unwrap_or(0.0),
+            max_lmt_sz: number(&inst.maxLmtSz),
+            max_mkt_sz: number(&inst.maxMktSz),
+            ct_val: number(&inst.ctVal),
+            ct_mult: number(&inst.ctMult),
+            ct_val_ccy: text(&inst.ctValCcy),
+            ct_type: field(&inst.ctType).and_then(CtType::from_name),
+            opt_type: match field(&inst.optType) {
+                Some("C") => Some(OptType::Call),
+                Some("P") => Some(OptType::Put),
+                _ => None,
+            },
+            strike: number(&inst.stk),
+            max_leverage: number(&inst.lever),
+            list_time: number(&inst.listTime),
+            exp_time: number(&inst.expTime),
+        })
+    }
+
+    pub fn is_derivative(&self) -> bool {
+        !matches!(self.inst_type, InstType::Spot | InstType::Margin)
+    }
+
+    pub fn is_tradable(&self) -> bool {
+        self.state == InstState::Live
+    }
+
+    /// Underlying quantity of one contract (`ctVal × ctMult`); 1 for spot.
+    pub fn contract_size(&self) -> f64 {
+        self.ct_val.unwrap_or(1.0) * self.ct_mult.unwrap_or(1.0)
+    }
+
+    /// Underlying index, e.g. `BTC-USDT` for BTC-USDT-SWAP.
+    pub fn index(&self) -> Option<&str> {
+        if !self.uly.is_empty() {
+            Some(&self.uly)
+        } else if !self.inst_family.is_empty() {
+            Some(&self.inst_family)
+        } else {
+            None
+        }
+    }
+}
+
+/// OKX sends `""` for fields that don't apply.
+fn field(value: &Option<String>) -> Option<&str> {
+    value.as_deref().filter(|v| !v.is_empty())
+}
+
+fn text(value: &Option<String>) -> String {
+    value.clone().unwrap_or_default()
+}
+
+fn number<T: std::str::FromStr>(value: &Option<String>) -> Option<T> {
+    field(value)?.parse().ok()
+}
+
+/// What a refresh found.
+#[derive(Debug, Clone)]
+pub enum InstrumentChange {
+    Listed(InstrumentSpec),
+    /// Gone from the instrument list (delisted or expired).
+    Delisted(InstrumentSpec),
+    StateChanged { inst_id: String, from: InstState, to: InstState },
+    /// Same state, but other metadata (tick size, limits, ...) changed.
+    Updated { old: Box<InstrumentSpec>, new: Box<InstrumentSpec> },
+}
+
+impl InstrumentChange {
+    pub fn inst_id(&self) -> &str {
+        match self {
+            InstrumentChange::Listed(spec) | InstrumentChange::Delisted(spec) => &spec.inst_id,
+            InstrumentChange::StateChanged { inst_id, .. } => inst_id,
+            InstrumentChange::Updated { new, .. } => &new.inst_id,
+        }
+    }
+}
+
+impl fmt::Display for InstrumentChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InstrumentChange::Listed(spec) => write!(f, "{} listed ({})", spec.inst_id, spec.state),
+            InstrumentChange::Delisted(spec) => write!(f, "{} delisted", spec.inst_id),
+            InstrumentChange::StateChanged { inst_id, from, to } => write!(f, "{} {} → {}", inst_id, from, to),
+            InstrumentChange::Updated { old, new } if old.precision != new.precision => write!(
+                f,
+                "{} moved from tick {} / lot {} to tick {} / lot {}",
+                new.inst_id, old.precision.tick, old.precision.lot, new.precision.tick, new.precision.lot
+            ),
+            InstrumentChange::Updated { new, .. } => write!(f, "{} metadata updated", new.inst_id),
+        }
+    }
+}
+
+/// Every known instrument, keyed by instId.
+#[derive(Debug, Default)]
+pub struct InstrumentRegistry {
+    specs: HashMap<String, InstrumentSpec>,
+}
+
+impl InstrumentRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the registry's contents with a full instrument list and report
+    /// what changed. Rows that don't parse are ignored; the first load into an
+    /// empty registry reports nothing. A type (or option family) with no rows
+    /// at all is taken as a failed fetch and keeps its previous instruments.
+    pub fn update(&mut self, instruments: Vec<Instrument>) -> Vec<InstrumentChange> {
+        let mut fresh: HashMap<String, InstrumentSpec> = instruments
+            .iter()
+            .filter_map(InstrumentSpec::from_okx)
+            .map(|spec| (spec.inst_id.clone(), spec))
+            .collect();
+        let fetched: HashSet<(InstType, String)> = fresh.values().map(|s| (s.inst_type, fetch_group(s).to_string())).collect();
+        for (inst_id, old) in &self.specs {
+            if !fetched.contains(&(old.inst_type, fetch_group(old).to_string())) {
+                fresh.insert(inst_id.clone(), old.clone());
+            }
+        }
+
+        let mut changes = Vec::new();
+        if !self.specs.is_empty() {
+            for (inst_id, old) in &self.specs {
+                match fresh.get(inst_id) {
+                    None => changes.push(InstrumentChange::Delisted(old.clone())),
+                    Some(new) if new.state != old.state => changes.push(InstrumentChange::StateChanged {
+                        inst_id: inst_id.clone(),
+                        from: old.state.clone(),
+                        to: new.state.clone(),
+                    }),
+                    Some(new) if new != old => changes.push(InstrumentChange::Updated { old: Box::new(old.clone()), new: Box::new(new.clone()) }),
+                    Some(_) => {}
+                }
+            }
+            for (inst_id, spec) in &fresh {
+                if !self.specs.contains_key(inst_id) {
+                    changes.push(InstrumentChange::Listed(spec.clone()));
+                }
+            }
+        }
+        self.specs = fresh;
+        changes
+    }
+
+    pub fn get(&self, inst_id: &str) -> Option<&InstrumentSpec> {
+        self.specs.get(inst_id)
+    }
+
+    pub fn len(&self) -> usize {
+        self.specs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.specs.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &InstrumentSpec> {
+        self.specs.values()
+    }
+
+    pub fn by_type(&self, inst_type: InstType) -> impl Iterator<Item = &InstrumentSpec> {
+        self.iter().filter(move |s| s.inst_type == inst_type)
+    }
+
+    /// Instruments on `ccy` as base currency. Derivatives match on their family's base (`BTC` in `BTC-USDT`).
+    pub fn by_base<'a>(&'a self, ccy: &'a str) -> impl Iterator<Item = &'a InstrumentSpec> {
+        self.iter().filter(move |s| s.base_ccy == ccy || family_part(&s.inst_family, 0) == Some(ccy))
+    }
+
+    /// Instruments quoted in `ccy`. Derivatives match on their family's quote (`USDT` in `BTC-USDT`).
+    pub fn by_quote<'a>(&'a self, ccy: &'a str) -> impl Iterator<Item = &'a InstrumentSpec> {
+        self.iter().filter(move |s| s.quote_ccy == ccy || family_part(&s.inst_family, 1) == Some(ccy))
+    }
+
+    /// Swaps, futures and options of one family, e.g. `BTC-USD`.
+    pub fn by_family<'a>(&'a self, family: &'a str) -> impl Iterator<Item = &'a InstrumentSpec> {
+        self.iter().filter(move |s| s.inst_family == family)
+    }
+
+    /// Live instruments in `family`, on `base`, quoted in `quote` and of
+    /// `inst_type`: every one of those given. Sorted by instId.
+    pub fn select(&self, family: Option<&str>, base: Option<&str>, quote: Option<&str>, inst_type: Option<InstType>) -> Vec<String> {
+        let mut criteria: Vec<HashSet<&str>> = Vec::new();
+        if let Some(family) = family {
+            criteria.push(self.by_family(family).map(|s| s.inst_id.as_str()).collect());
+        }
+        if let Some(base) = base {
+            criteria.push(self.by_base(base).map(|s| s.inst_id.as_str()).collect());
+        }
+        if let Some(quote) = quote {
+            criteria.push(self.by_quote(quote).map(|s| s.inst_id.as_str()).collect());
+        }
+        let mut selected: Vec<_> = self
+            .iter()
+            .filter(|s| s.is_tradable() && inst_type.is_none_or(|t| s.inst_type == t))
+            .filter(|s| criteria.iter().all(|ids| ids.contains(s.inst_id.as_str())))
+            .map(|s| s.inst_id.clone())
+            .collect();
+        selected.sort();
+        selected
+    }
+}
+
+/// Fetch the full instrument list every `every` on a background task. Apply
+/// each batch with `InstrumentRegistry::update`; failed fetches are logged and skipped.
+pub fn spawn_refresh(rest: OkxRestClient, every: Duration) -> mpsc::UnboundedReceiver<Vec<Instrument>> {
+    let (tx, rx) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(every);
+        ticker.tick().await; // the caller has just loaded
+        loop {
+            ticker.tick().await;
+            match rest.all_instruments().await {
+                Ok(instruments) => {
+                    if tx.send(instruments).is_err() {
+                        return;
+                    }
+                }
+                Err(e) => eprintln!("⚠️ Instrument refresh failed: {}", e),
+            }
+        }
+    });
+    rx
+}
+
+/// What `all_instruments` fetches `spec` with: its family for options, nothing else otherwise.
+fn fetch_group(spec: &InstrumentSpec) -> &str {
+    match spec.inst_type {
+        InstType::Option => &spec.inst_family,
+        _ => "",
+    }
+}
+
+fn family_part(family: &str, i: usize) -> Option<&str> {
+    family.split('-').nth(i)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inst(inst_id: &str, inst_type: &str, state: &str, tick: &str) -> Instrument {
+        let (base, quote, family) = match inst_type {
+            "SPOT" => {
+                let mut parts = inst_id.split('-');
+                (parts.next().unwrap(), parts.next().unwrap(), "")
+            }
+            _ => ("", "", &inst_id[..inst_id.match_indices('-').nth(1).map_or(inst_id.len(), |(i, _)| i)]),
+        };
+        serde_json::from_value(serde_json::json!({
+            "instType": inst_type,
+            "instId": inst_id,
+            "instFamily": family,
+            "uly": family,
+            "baseCcy": base,
+            "quoteCcy": quote,
+            "settleCcy": if inst_type == "SPOT" { "" } else { "USDT" },
+            "ctVal": if inst_type == "SPOT" { "" } else { "0.01" },
+            "ctType": if inst_type == "SPOT" { "" } else { "linear" },
+            "state": state,
+            "tickSz": tick,
+            "lotSz": "1",
+            "minSz": "1",
+        }))
+        .unwrap()
+    }
+
+    fn listing() -> Vec<Instrument> {
+        vec![
+            inst("BTC-USDT", "SPOT", "live", "0.1"),
+            inst("ETH-USDT", "SPOT", "live", "0.01"),
+            inst("ETH-BTC", "SPOT", "live", "0.00001"),
+            inst("BTC-USDT-SWAP", "SWAP", "live", "0.1"),
+            inst("BTC-USDT-250926", "FUTURES", "live", "0.1"),
+            inst("ETH-USDT-SWAP", "SWAP", "live", "0.01"),
+        ]
+    }
+
+    fn ids<'a>(specs: impl Iterator<Item = &'a InstrumentSpec>) -> Vec<&'a str> {
+        let mut ids: Vec<_> = specs.map(|s| s.inst_id.as_str()).collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn the_first_load_reports_nothing_and_answers_lookups() {
+        let mut registry = InstrumentRegistry::new();
+        assert!(registry.is_empty());
+        assert!(registry.update(listing()).is_empty());
+        assert_eq!(registry.len(), 6);
+
+        let swap = registry.get("BTC-USDT-SWAP").unwrap();
+        assert_eq!((swap.inst_type, swap.index(), swap.contract_size()), (InstType::Swap, Some("BTC-USDT"), 0.01));
+        assert!(swap.is_derivative() && swap.is_tradable());
+        assert!(registry.get("SOL-USDT").is_none());
+
+        assert_eq!(ids(registry.by_type(InstType::Spot)), vec!["BTC-USDT", "ETH-BTC", "ETH-USDT"]);
+        assert_eq!(ids(registry.by_base("BTC")), vec!["BTC-USDT", "BTC-USDT-250926", "BTC-USDT-SWAP"]);
+        assert_eq!(ids(registry.by_quote("BTC")), vec!["ETH-BTC"]);
+        assert_eq!(ids(registry.by_quote("USDT")).len(), 5);
+        assert_eq!(ids(registry.by_family("ETH-USDT")), vec!["ETH-USDT-SWAP"]);
+    }
+
+    #[test]
+    fn selections_match_every_criterion_given_and_only_live_instruments() {
+        let mut listing = listing();
+        listing.push(inst("BTC-USDT-251226", "FUTURES", "preopen", "0.1"));
+        let mut registry = InstrumentRegistry::new();
+        registry.update(listing);
+
+        assert_eq!(registry.select(Some("BTC-USDT"), None, None, None), vec!["BTC-USDT-250926", "BTC-USDT-SWAP"]);
+        assert_eq!(registry.select(None, Some("BTC"), Some("USDT"), None), vec!["BTC-USDT", "BTC-USDT-250926", "BTC-USDT-SWAP"]);
+        assert_eq!(registry.select(None, Some("BTC"), None, Some(InstType::Swap)), vec!["BTC-USDT-SWAP"]);
+        assert_eq!(registry.select(None, None, Some("BTC"), None), vec!["ETH-BTC"]);
+        assert!(registry.select(Some("SOL-USDT"), None, None, None).is_empty());
+    }
+
+    #[test]
+    fn refreshes_report_listings_delistings_and_changes() {
+        let mut registry = InstrumentRegistry::new();
+        registry.update(listing());
+
+        let mut refresh = listing();
+        refresh.retain(|i| i.instId.as_deref() != Some("ETH-BTC"));
+        refresh.push(inst("SOL-USDT", "SPOT", "preopen", "0.001"));
+        refresh[0] = inst("BTC-USDT", "SPOT", "suspend", "0.1");
+        refresh[1] = inst("ETH-USDT", "SPOT", "live", "0.001");
+        let mut changes: Vec<_> = registry.update(refresh).into_iter().map(|c| c.to_string()).collect();
+        changes.sort();
+        assert_eq!(
+            changes,
+            vec![
+                "BTC-USDT live → suspend",
+                "ETH-BTC delisted",
+                "ETH-USDT moved from tick 0.01 / lot 1 to tick 0.001 / lot 1",
+                "SOL-USDT listed (preopen)",
+            ]
+        );
+        assert!(!registry.get("BTC-USDT").unwrap().is_tradable());
+        assert!(registry.get("ETH-BTC").is_none());
+    }
+
+    #[test]
+    fn an_unknown_state_is_a_state_change_not_a_delisting() {
+        let mut registry = InstrumentRegistry::new();
+        registry.update(listing());
+
+        let mut refresh = listing();
+        refresh[3] = inst("BTC-USDT-SWAP", "SWAP", "rebalancing", "0.1");
+        let changes = registry.update(refresh);
+        assert_eq!(changes.len(), 1);
+        let InstrumentChange::StateChanged { inst_id, from, to } = &changes[0] else {
+            panic!("expected a state change, got {}", changes[0]);
+        };
+        assert_eq!((inst_id.as_str(), from, to), ("BTC-USDT-SWAP", &InstState::Live, &InstState::Other("rebalancing".to_string())));
+        let spec = registry.get("BTC-USDT-SWAP").unwrap();
+        assert!(!spec.is_tradable());
+        assert_eq!(spec.state.to_string(), "rebalancing");
+    }
+
+    #[test]
+    fn a_partial_fetch_keeps_the_types_it_is_missing() {
+        let mut registry = InstrumentRegistry::new();
+        let mut first = listing();
+        first.push(inst("BTC-USD-250926-60000-C", "OPTION", "live", "0.0005"));
+        first.push(inst("ETH-USD-250926-3000-C", "OPTION", "live", "0.0005"));
+        registry.update(first);
+
+        // the swap and ETH-USD option fetches failed: only spot, futures and BTC-USD options came back
+        let mut refresh: Vec<_> = listing().into_iter().filter(|i| i.instType.as_deref() != Some("SWAP")).collect();
+        refresh.push(inst("BTC-USD-250926-65000-C", "OPTION", "live", "0.0005"));
+        let mut changes: Vec<_> = registry.update(refresh).into_iter().map(|c| c.to_string()).collect();
+        changes.sort();
+        assert_eq!(changes, vec!["BTC-USD-250926-60000-C delisted", "BTC-USD-250926-65000-C listed (live)"]);
+        assert!(registry.get("BTC-USDT-SWAP").is_some());
+        assert!(registry.get("ETH-USD-250926-3000-C").is_some());
+        assert_eq!(registry.len(), 8);
+    }
+}
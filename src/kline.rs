@@ -0,0 +1,278 @@
+//! Candles: the typed `Candle` model and an in-memory bar store per instrument
+//! and timeframe, fed by REST backfill (`OkxRestClient::candles`) and the live
+//! `candle*` WebSocket channels (business endpoint).
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// OKX bar sizes. Hour-and-above bars open at Hong Kong time (UTC+8); the
+/// `…Utc` variants open at UTC midnight instead.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Bar {
+    S1,
+    M1,
+    M3,
+    M5,
+    M15,
+    M30,
+    H1,
+    H2,
+    H4,
+    H6,
+    H12,
+    D1,
+    D2,
+    D3,
+    W1,
+    Mo1,
+    Mo3,
+    H6Utc,
+    H12Utc,
+    D1Utc,
+    D2Utc,
+    D3Utc,
+    W1Utc,
+    Mo1Utc,
+    Mo3Utc,
+}
+
+impl Bar {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Bar::S1 => "1s",
+            Bar::M1 => "1m",
+            Bar::M3 => "3m",
+            Bar::M5 => "5m",
+            Bar::M15 => "15m",
+            Bar::M30 => "30m",
+            Bar::H1 => "1H",
+            Bar::H2 => "2H",
+            Bar::H4 => "4H",
+            Bar::H6 => "6H",
+            Bar::H12 => "12H",
+            Bar::D1 => "1D",
+            Bar::D2 => "2D",
+            Bar::D3 => "3D",
+            Bar::W1 => "1W",
+            Bar::Mo1 => "1M",
+            Bar::Mo3 => "3M",
+            Bar::H6Utc => "6Hutc",
+            Bar::H12Utc => "12Hutc",
+            Bar::D1Utc => "1Dutc",
+            Bar::D2Utc => "2Dutc",
+            Bar::D3Utc => "3Dutc",
+            Bar::W1Utc => "1Wutc",
+            Bar::Mo1Utc => "1Mutc",
+            Bar::Mo3Utc => "3Mutc",
+        }
+    }
+
+    pub const ALL: [Bar; 25] = [
+        Bar::S1, Bar::M1, Bar::M3, Bar::M5, Bar::M15, Bar::M30,
+        Bar::H1, Bar::H2, Bar::H4, Bar::H6, Bar::H12,
+        Bar::D1, Bar::D2, Bar::D3, Bar::W1, Bar::Mo1, Bar::Mo3,
+        Bar::H6Utc, Bar::H12Utc, Bar::D1Utc, Bar::D2Utc, Bar::D3Utc, Bar::W1Utc, Bar::Mo1Utc, Bar::Mo3Utc,
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Bar::ALL.into_iter().find(|b| b.as_str() == name)
+    }
+
+    /// WebSocket channel name, e.g. `candle1H`.
+    pub fn channel(&self) -> String {
+        format!("candle{}", self.as_str())
+    }
+
+    /// Parse a `candle*` channel name back into a bar.
+    pub fn from_channel(channel: &str) -> Option<Self> {
+        Bar::from_name(channel.strip_prefix("candle")?)
+    }
+
+    /// Nominal length in milliseconds (months count as 30 days).
+    pub fn duration_ms(&self) -> u64 {
+        const MIN: u64 = 60_000;
+        const HOUR: u64 = 60 * MIN;
+        const DAY: u64 = 24 * HOUR;
+        match self {
+            Bar::S1 => 1_000,
+            Bar::M1 => MIN,
+            Bar::M3 => 3 * MIN,
+            Bar::M5 => 5 * MIN,
+            Bar::M15 => 15 * MIN,
+            Bar::M30 => 30 * MIN,
+            Bar::H1 => HOUR,
+            Bar::H2 => 2 * HOUR,
+            Bar::H4 => 4 * HOUR,
+            Bar::H6 | Bar::H6Utc => 6 * HOUR,
+            Bar::H12 | Bar::H12Utc => 12 * HOUR,
+            Bar::D1 | Bar::D1Utc => DAY,
+            Bar::D2 | Bar::D2Utc => 2 * DAY,
+            Bar::D3 | Bar::D3Utc => 3 * DAY,
+            Bar::W1 | Bar::W1Utc => 7 * DAY,
+            Bar::Mo1 | Bar::Mo1Utc => 30 * DAY,
+            Bar::Mo3 | Bar::Mo3Utc => 90 * DAY,
+        }
+    }
+
+    /// Number of bars covering `hours`, at least one.
+    pub fn bars_in_hours(&self, hours: usize) -> usize {
+        ((hours as u64 * 3_600_000) / self.duration_ms()).max(1) as usize
+    }
+}
+
+impl fmt::Display for Bar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candle {
+    /// Bar open time, Unix milliseconds.
+    pub ts: u64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+    /// Volume in contracts (derivatives) or base currency (spot).
+    pub vol: f64,
+    /// Volume in base currency (derivatives) or quote currency (spot).
+    pub vol_ccy: f64,
+    /// Volume in quote currency.
+    pub vol_ccy_quote: f64,
+    /// False while the bar is still forming.
+    pub confirmed: bool,
+}
+
+impl Candle {
+    /// OKX rows are `[ts, o, h, l, c, vol, volCcy, volCcyQuote, confirm]`.
+    pub fn from_okx(row: &[String]) -> Option<Self> {
+        let num = |i: usize| row.get(i)?.parse::<f64>().ok();
+        Some(Self {
+            ts: row.first()?.parse().ok()?,
+            open: num(1)?,
+            high: num(2)?,
+            low: num(3)?,
+            close: num(4)?,
+            vol: num(5)?,
+            vol_ccy: num(6).unwrap_or(0.0),
+            vol_ccy_quote: num(7).unwrap_or(0.0),
+            confirmed: row.get(8).is_none_or(|c| c == "1"),
+        })
+    }
+}
+
+/// Bars per instrument and timeframe, keyed by open time. Live updates to a
+/// forming bar replace it in place.
+#[derive(Debug)]
+pub struct BarStore {
+    series: HashMap<(String, Bar), BTreeMap<u64, Candle>>,
+    capacity: usize,
+}
+
+impl BarStore {
+    /// Keep at most `capacity` bars per series, dropping the oldest.
+    pub fn new(capacity: usize) -> Self {
+        Self { series: HashMap::new(), capacity }
+    }
+
+    pub fn insert(&mut self, inst_id: &str, bar: Bar, candle: Candle) {
+        let series = self.series.entry((inst_id.to_string(), bar)).or_default();
+        series.insert(candle.ts, candle);
+        while series.len() > self.capacity {
+            series.pop_first();
+        }
+    }
+
+    pub fn extend(&mut self, inst_id: &str, bar: Bar, candles: impl IntoIterator<Item = Candle>) {
+        for candle in candles {
+            self.insert(inst_id, bar, candle);
+        }
+    }
+
+    /// Latest bar, forming or not.
+    pub fn latest(&self, inst_id: &str, bar: Bar) -> Option<&Candle> {
+        self.get(inst_id, bar)?.values().next_back()
+    }
+
+    /// Up to the last `n` confirmed bars, oldest first.
+    pub fn closed(&self, inst_id: &str, bar: Bar, n: usize) -> Vec<&Candle> {
+        let Some(series) = self.get(inst_id, bar) else {
+            return Vec::new();
+        };
+        let mut out: Vec<_> = series.values().rev().filter(|c| c.confirmed).take(n).collect();
+        out.reverse();
+        out
+    }
+
+    fn get(&self, inst_id: &str, bar: Bar) -> Option<&BTreeMap<u64, Candle>> {
+        self.series.get(&(inst_id.to_string(), bar))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(fields: &[&str]) -> Vec<String> {
+        fields.iter().map(|f| f.to_string()).collect()
+    }
+
+    fn candle(ts: u64, close: f64, confirmed: bool) -> Candle {
+        Candle { ts, open: close, high: close, low: close, close, vol: 1.0, vol_ccy: 1.0, vol_ccy_quote: close, confirmed }
+    }
+
+    #[test]
+    fn candles_parse_okx_rows() {
+        let forming = row(&["1597026383085", "8533.02", "8553.74", "8527.17", "8548.26", "45247", "529.5858061", "4523.1", "0"]);
+        let candle = Candle::from_okx(&forming).unwrap();
+        assert_eq!(candle.ts, 1597026383085);
+        assert_eq!((candle.open, candle.high, candle.low, candle.close), (8533.02, 8553.74, 8527.17, 8548.26));
+        assert_eq!((candle.vol, candle.vol_ccy, candle.vol_ccy_quote), (45247.0, 529.5858061, 4523.1));
+        assert!(!candle.confirmed);
+
+        let mut closed = forming.clone();
+        closed[8] = "1".to_string();
+        assert!(Candle::from_okx(&closed).unwrap().confirmed);
+
+        // without the volume breakdown and confirm flag, the bar is taken as closed
+        let short = row(&["1597026383085", "8533.02", "8553.74", "8527.17", "8548.26", "45247"]);
+        let candle = Candle::from_okx(&short).unwrap();
+        assert_eq!((candle.vol_ccy, candle.vol_ccy_quote, candle.confirmed), (0.0, 0.0, true));
+
+        assert_eq!(Candle::from_okx(&forming[..5]), None);
+        assert_eq!(Candle::from_okx(&row(&["ts", "1", "1", "1", "1", "1"])), None);
+    }
+
+    #[test]
+    fn every_bar_round_trips_through_its_channel() {
+        for bar in Bar::ALL {
+            assert_eq!(Bar::from_channel(&bar.channel()), Some(bar));
+        }
+        assert_eq!(Bar::from_channel("candle1H"), Some(Bar::H1));
+        assert_eq!(Bar::from_channel("candle2m"), None);
+        assert_eq!(Bar::from_channel("1H"), None);
+    }
+
+    #[test]
+    fn the_store_keeps_the_newest_bars_and_updates_a_forming_one_in_place() {
+        let mut bars = BarStore::new(3);
+        bars.extend("BTC-USDT", Bar::M1, (0..5).map(|i| candle(i * 60_000, i as f64, true)));
+        let closed: Vec<u64> = bars.closed("BTC-USDT", Bar::M1, 10).iter().map(|c| c.ts).collect();
+        assert_eq!(closed, vec![120_000, 180_000, 240_000]);
+
+        bars.insert("BTC-USDT", Bar::M1, candle(300_000, 5.0, false));
+        bars.insert("BTC-USDT", Bar::M1, candle(300_000, 5.5, false));
+        assert_eq!(bars.latest("BTC-USDT", Bar::M1).map(|c| c.close), Some(5.5));
+        assert_eq!(bars.closed("BTC-USDT", Bar::M1, 10).len(), 2);
+        bars.insert("BTC-USDT", Bar::M1, candle(300_000, 6.0, true));
+        assert_eq!(bars.closed("BTC-USDT", Bar::M1, 10).len(), 3);
+
+        // closed() skips the forming bar and counts back from the latest closed one
+        bars.insert("BTC-USDT", Bar::M1, candle(360_000, 7.0, false));
+        let closes: Vec<f64> = bars.closed("BTC-USDT", Bar::M1, 2).iter().map(|c| c.close).collect();
+        assert_eq!(closes, vec![4.0, 6.0]);
+        assert!(bars.closed("BTC-USDT", Bar::H1, 2).is_empty());
+        assert!(bars.latest("ETH-USDT", Bar::M1).is_none());
+    }
+}
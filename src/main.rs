@@ -1,202 +1,541 @@
+mod account;
+mod backtest;
+mod cli;
+mod clock;
+mod engine;
+mod execution;
+mod fees;
+mod instruments;
+mod kline;
+mod market;
+mod normalize;
 mod models;
+mod oms;
 mod orderbook;
+mod paper;
+mod precision;
+mod quoting;
+mod recorder;
+mod replay;
 mod sources;
 mod strategy;
 mod strategies;
 
-use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-
-use models::{Instrument, OkxResponse, WsBookPush};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use backtest::Backtest;
+use cli::Args;
+use clock::Clock;
+use engine::{unix_ms, Engine, Venue, TIMER_PERIOD};
+use execution::ExecutionGateway;
+use instruments::{CtType, InstrumentRegistry, InstrumentSpec};
+use kline::BarStore;
+use normalize::{NormalizedStrategy, OrderNormalizer};
+use oms::OrderManager;
+use orderbook::manager::BookManager;
 use orderbook::OrderBook;
-use serde_json::json;
+use paper::PaperVenue;
+use quoting::QuoteEngine;
+use recorder::{Recorder, INSTRUMENTS_FILE};
+use replay::{read_instruments, FrameReader, Replay};
+use strategies::mmxms::MMXMStrategy;
 use strategies::statmm::StatMM;
+use sources::okx::{FeedConfig, OkxFeed, Subscription, BUSINESS_URL};
+use sources::okx_auth::Credentials;
+use sources::okx_rest::{InstType, OkxRestClient};
 use strategy::Strategy;
-use tokio::{runtime::Runtime, time};
-use tokio_tungstenite::connect_async;
-use websocket::ClientBuilder;
-use futures_util::{SinkExt, StreamExt};
 
 
-fn fetch_instruments() -> Vec<Instrument> {
-    let mut instruments = vec![];
-    let client = reqwest::blocking::Client::new();
-    let mut req = client.get("https://www.okx.com/api/v5/public/instruments");
+/// Instrument type from metadata, or guessed from the id without it.
+fn inst_type_of(inst_id: &str, inst: Option<&InstrumentSpec>) -> InstType {
+    inst.map(|i| i.inst_type).unwrap_or_else(|| {
+        if inst_id.ends_with("-SWAP") { InstType::Swap } else { InstType::Spot }
+    })
+}
 
-    for inst_type in ["SPOT", "SWAP"].iter() {
-        let req_with_query = req.try_clone().expect("Failed to clone request").query(&[("instType", inst_type)]);
+/// Ticker for every instrument, plus mark price, index, open interest and
+/// (for perpetuals) funding for derivatives. Also returns the underlying index id.
+fn market_subscriptions(inst_id: &str, inst: Option<&InstrumentSpec>) -> (Vec<Subscription>, Option<String>) {
+    let mut subs = vec![Subscription::new("tickers", inst_id)];
+    let inst_type = inst_type_of(inst_id, inst);
+    if inst_type == InstType::Spot || inst_type == InstType::Margin {
+        return (subs, None);
+    }
 
-        let resp = req_with_query.send().unwrap();
-        if resp.status().is_success() {
-            let parsed: OkxResponse<Instrument> = resp.json().unwrap();
-            instruments.extend(parsed.data);
-        } else {
-            eprintln!("❌ Failed to fetch instruments: {}", resp.status());
-        }
+    subs.push(Subscription::new("mark-price", inst_id));
+    subs.push(Subscription::new("open-interest", inst_id));
+    if inst_type == InstType::Swap {
+        subs.push(Subscription::new("funding-rate", inst_id));
     }
-    instruments
+    let index = inst
+        .and_then(|i| i.index().map(str::to_string))
+        .or_else(|| inst_id.strip_suffix("-SWAP").map(str::to_string));
+    if let Some(index) = &index {
+        subs.push(Subscription::new("index-tickers", index.as_str()));
+    }
+    (subs, index)
 }
 
-// #[tokio::main]
-// async fn main() {
-
-//     let instruments = fetch_instruments();
-
-//     let ai16z_matches = instruments.iter()
-//         .filter(|inst| inst.instId.as_ref().map_or(false, |id| id.contains("AI16Z")))
-//         .collect::<Vec<_>>();
-
-//     let ai16z = ai16z_matches.get(0).expect("No AI16Z instrument found");
-
+/// How often the instrument list is re-fetched to catch listings, delistings and suspensions.
+const INSTRUMENT_REFRESH: Duration = Duration::from_secs(300);
+
+/// Bars kept (and backfilled) per instrument and timeframe.
+const BAR_HISTORY: usize = 500;
+
+/// Levels a side of the REST snapshot strategies are warmed up with.
+const WARM_UP_DEPTH: usize = 50;
+
+/// How far our clock may be from OKX's before order expiry times are off enough to warn about.
+const MAX_CLOCK_SKEW: Duration = Duration::from_secs(1);
+
+/// Seed each strategy from REST before the feed starts: its ticker (one call per
+/// type when several instruments share it), a book snapshot and, for
+/// derivatives, the mark price; log the last funding settled on swaps. Returns
+/// the instruments OKX doesn't list.
+async fn warm_up(
+    rest: &OkxRestClient,
+    inst_ids: &[String],
+    registry: &InstrumentRegistry,
+    strats: &mut HashMap<String, Box<dyn Strategy>>,
+) -> Vec<String> {
+    let mut per_type: HashMap<InstType, usize> = HashMap::new();
+    for inst_id in inst_ids {
+        *per_type.entry(inst_type_of(inst_id, registry.get(inst_id))).or_default() += 1;
+    }
+    let mut tickers = HashMap::new();
+    for (inst_type, _) in per_type.into_iter().filter(|(_, n)| *n > 1) {
+        match rest.tickers(inst_type).await {
+            Ok(all) => tickers.extend(all.into_iter().filter(|t| inst_ids.contains(&t.inst_id)).map(|t| (t.inst_id.clone(), t))),
+            Err(e) => eprintln!("⚠️ Fetching {} tickers failed: {}", inst_type, e),
+        }
+    }
 
-//     sources::okx::subscribe_to_books(
-//         &ai16z.instId.as_ref().unwrap(),
-//         "books",
-//     ).await;
+    let mut unknown = Vec::new();
+    for inst_id in inst_ids {
+        let Some(strat) = strats.get_mut(inst_id) else {
+            continue;
+        };
+        let inst = registry.get(inst_id);
+        let ticker = match tickers.remove(inst_id) {
+            Some(ticker) => Ok(ticker),
+            None => rest.ticker(inst_id).await,
+        };
+        match ticker {
+            Ok(ticker) => {
+                println!("📈 {}", ticker);
+                for req in strat.on_ticker(&ticker) {
+                    println!("▶️  {} OrderRequest from on_ticker (warm-up): {:?}", inst_id, req);
+                }
+            }
+            Err(e) if e.is_unknown_instrument() => {
+                unknown.push(inst_id.clone());
+                continue;
+            }
+            Err(e) => eprintln!("⚠️ Ticker for {} failed: {}", inst_id, e),
+        }
 
-//     loop {
+        let mut book = inst.map_or_else(OrderBook::new, OrderBook::for_instrument);
+        match rest.order_book(inst_id, WARM_UP_DEPTH).await.map(|data| book.apply_snapshot(&data)) {
+            Ok(Ok(())) => {
+                for req in strat.on_order_book(&book, &book) {
+                    println!("▶️  {} OrderRequest from on_order_book (warm-up): {:?}", inst_id, req);
+                }
+            }
+            Ok(Err(e)) => eprintln!("⚠️ Book snapshot for {} unusable: {}", inst_id, e),
+            Err(e) => eprintln!("⚠️ Book snapshot for {} failed: {}", inst_id, e),
+        }
 
-//     }
+        let inst_type = inst_type_of(inst_id, inst);
+        if inst_type == InstType::Spot || inst_type == InstType::Margin {
+            continue;
+        }
+        match rest.mark_prices(inst_type, Some(inst_id)).await {
+            Ok(marks) => {
+                for mark in &marks {
+                    for req in strat.on_mark_price(mark) {
+                        println!("▶️  {} OrderRequest from on_mark_price (warm-up): {:?}", inst_id, req);
+                    }
+                }
+            }
+            Err(e) => eprintln!("⚠️ Mark price for {} failed: {}", inst_id, e),
+        }
+        if inst_type == InstType::Swap {
+            match rest.funding_history(inst_id, 1).await {
+                Ok(history) => {
+                    if let Some(last) = history.last() {
+                        println!(
+                            "💸 {} last funding at {}: {:+.4}% charged ({:+.4}% published)",
+                            last.inst_id,
+                            last.funding_time,
+                            last.realized_rate * 100.0,
+                            last.rate * 100.0
+                        );
+                    }
+                }
+                Err(e) => eprintln!("⚠️ Funding history for {} failed: {}", inst_id, e),
+            }
+        }
+    }
+    unknown
+}
 
-// }
+/// First order id of a replay; ids count up from it, so every run of a
+/// recording gives its orders the same `clOrdId`s.
+const REPLAY_ID_SEED: u128 = 1;
 
-fn calc_latency(ts_str: &str) -> Option<Duration> {
-    if let Ok(ts_millis) = ts_str.parse::<u128>() {
-        let book_time = UNIX_EPOCH + Duration::from_millis(ts_millis as u64);
-        let now = SystemTime::now();
 
-        now.duration_since(book_time).ok()
-    } else {
-        None
-    }
-}
 #[tokio::main]
 async fn main() {
-    // ─── 1) Instantiate your strategy ────────────────────────────────────────
+    // ─── 1) Instruments to watch; each gets its own book and strategy (see cli.rs) ─
+    let args = Args::parse();
+    let selecting = args.selecting();
+    let Args {
+        channel,
+        trades_all,
+        bar,
+        mmxm,
+        centre,
+        size_unit,
+        live,
+        paper,
+        ord_type,
+        reduce_only,
+        record_dir,
+        replay_dir,
+        replay_speed,
+        replay_clock,
+        instruments_file,
+        backtest_dir,
+        capital,
+        fee_tier,
+        cancel_model,
+        price_tolerance,
+        size_tolerance,
+        private_url,
+        rest_url,
+        family,
+        base,
+        quote,
+        inst_type,
+        mut inst_ids,
+    } = args;
+
+    // ─── 2) One strategy per instrument, once the instruments are known ─────
     // gamma=0.1, kappa=1.0, T=1.0 are hyperparameters for the Avellaneda-Stoikov model
-    let mut strat = StatMM::new(0.1, 100.0, 1.0, 50);
-
-    // ─── 2) Prepare an OrderBook to accumulate snapshots & updates ──────────
-    let mut book = OrderBook::new();
-
-    // ─── 3) Open a WebSocket to OKX ────────────────────────────────────────
-    let url = "wss://ws.okx.com:8443/ws/v5/public";
-    let (mut ws_stream, _) = connect_async(url).await.expect("WS connect failed");
-
-    // ─── 4) Send the subscribe message ─────────────────────────────────────
-    let subscribe = serde_json::json!({
-        "op": "subscribe",
-        "args": [{ "channel": "books", "instId": "AI16Z-USDT-SWAP" }]
-    })
-    .to_string();
-    ws_stream.send(tokio_tungstenite::tungstenite::Message::Text(subscribe.into())).await.unwrap();
-
-    // ─── 5) Timer for on_timer hooks (e.g. periodic PnL checks) ───────────
-    let mut ticker = time::interval(Duration::from_secs(1));
-
-    loop {
-        tokio::select! {
-            // ─── 6a) Incoming WS messages ───────────────────────────────────
-            msg = ws_stream.next() => {
-                let msg = match msg {
-                    Some(Ok(m)) => m,
-                    Some(Err(e)) => { eprintln!("WS error: {}", e); continue; },
-                    None => break, // connection closed
-                };
-
-                if let tokio_tungstenite::tungstenite::Message::Text(txt) = msg {
-                    // 6a.i) Parse into your push struct
-                    if let Ok(parsed) = serde_json::from_str::<WsBookPush>(&txt) {
-                        // 6a.ii) Apply snapshot or update
-                        for data in parsed.data {
-                            match parsed.action.as_deref() {
-                                Some("snapshot") => book.apply_snapshot(&data),
-                                Some("update")   => book.apply_update(&data),
-                                _ => {}
-                            }
-                        }
-
-                        // 6a.iii) Compute mid‐price = (best_bid + best_ask)/2
-                        if let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) {
-                            let mid = (bid + ask) / 2.0;
-                            let now = Instant::now();
-
-
-                            // 6a.iv) Strategy: on_price_tick
-                            for req in strat.on_price_tick(mid, now) {
-                                println!("▶️  OrderRequest from on_price_tick: {:?}", req);
-                                // → here you'd actually send the order to OKX
-                            }
+    let new_strategy = |inst_id: &str| -> Box<dyn Strategy> {
+        match bar {
+            // PD arrays over the last 24h of HTF bars
+            Some(bar) if mmxm => Box::new(MMXMStrategy::new(inst_id, 10_000.0, bar, 24)),
+            _ => Box::new(
+                StatMM::new(0.1, 100.0, 1.0, 50)
+                    .with_centre(centre.clone())
+                    .with_adaptive_kappa()
+                    .with_funding_skew(1.0)
+                    .with_flow_skew(0.5)
+                    .with_depth_cap(10.0, 0.5),
+            ),
+        }
+    };
+
+    // ─── 3) Prepare an OrderBook per instrument on its tick/lot grid, and put
+    //        every order its strategy emits onto the same grid ───────────────
+    let rest = match rest_url {
+        Some(url) => OkxRestClient::new().with_base_url(url),
+        None => OkxRestClient::new(),
+    };
+    let instruments = match &replay_dir {
+        // a replay trades the specs it was recorded with, not today's
+        Some(dir) => {
+            let path = instruments_file.map_or_else(|| Path::new(dir).join(INSTRUMENTS_FILE), PathBuf::from);
+            read_instruments(&path).unwrap_or_else(|e| {
+                eprintln!("⚠️ No instruments for the replay in {}: {}", path.display(), e);
+                Vec::new()
+            })
+        }
+        None => rest.all_instruments().await.unwrap_or_else(|e| {
+            eprintln!("❌ Failed to fetch instruments: {}", e);
+            Vec::new()
+        }),
+    };
+    let mut registry = InstrumentRegistry::new();
+    registry.update(instruments.clone());
+    if selecting {
+        let selected = registry.select(family.as_deref(), base.as_deref(), quote.as_deref(), inst_type);
+        println!("🎯 Selected {} live instruments: {}", selected.len(), selected.join(", "));
+        for inst_id in selected {
+            if !inst_ids.contains(&inst_id) {
+                inst_ids.push(inst_id);
+            }
+        }
+        if inst_ids.is_empty() {
+            eprintln!("❌ No live instruments match the selection");
+            std::process::exit(1);
+        }
+    }
+    let recorded_instruments: Vec<_> = match record_dir {
+        Some(_) => instruments.iter().filter(|i| i.instId.as_ref().is_some_and(|id| inst_ids.contains(id))).cloned().collect(),
+        None => Vec::new(),
+    };
+    if !registry.is_empty() {
+        let types = [InstType::Spot, InstType::Swap, InstType::Futures, InstType::Option];
+        let counts: Vec<_> = types.iter().map(|t| format!("{} {}", t, registry.by_type(*t).count())).collect();
+        println!("📋 {} instruments ({})", registry.len(), counts.join(", "));
+    }
+    if backtest_dir.is_some() {
+        let inverse = inst_ids.iter().find(|id| registry.get(id).is_some_and(|spec| spec.ct_type == Some(CtType::Inverse)));
+        if let Some(inst_id) = inverse {
+            eprintln!("❌ --backtest can't account for {}: inverse contracts settle in coin, not the quote currency", inst_id);
+            std::process::exit(1);
+        }
+    }
+    let mut books = BookManager::new();
+    let mut quoters = HashMap::new();
+    // instruments that aren't live (suspended, pre-open, delisted): strategies see their data, but nothing is quoted
+    let mut halted = HashSet::new();
+    let mut strats: HashMap<String, Box<dyn Strategy>> = HashMap::new();
+    for inst_id in &inst_ids {
+        let normalizer = registry.get(inst_id).map(OrderNormalizer::for_instrument);
+        let strat = NormalizedStrategy::new(new_strategy(inst_id), normalizer).with_size_unit(size_unit);
+        strats.insert(inst_id.clone(), Box::new(strat));
+        let book = match registry.get(inst_id) {
+            Some(spec) => {
+                if !spec.is_tradable() {
+                    eprintln!("⚠️ {} is {}, not live: not quoting it until it is", inst_id, spec.state);
+                    halted.insert(inst_id.clone());
+                }
+                OrderBook::for_instrument(spec)
+            }
+            None => {
+                eprintln!("⚠️ No instrument metadata for {}: taking its tick and lot from the book data, and sending its orders unnormalised", inst_id);
+                OrderBook::new()
+            }
+        };
+        let quoter = QuoteEngine::new(book.precision)
+            .with_price_tolerance(price_tolerance)
+            .with_size_tolerance(size_tolerance);
+        quoters.insert(inst_id.clone(), quoter);
+        books.track(inst_id.clone(), channel, book);
+    }
 
-                            // 6a.v) Strategy: on_order_book (if you need raw‐book signals)
-                            for req in strat.on_order_book(&book, &book) {
-                                println!("▶️  OrderRequest from on_order_book: {:?}", req);
-                            }
+    // ─── 3b) Backfill candles so bar-based strategies start warm (not in a
+    //        replay: today's candles would be from the replay's future) ─────
+    let mut bars = BarStore::new(BAR_HISTORY);
+    if let Some(bar) = bar.filter(|_| replay_dir.is_none()) {
+        for inst_id in &inst_ids {
+            match rest.candles(inst_id, bar, BAR_HISTORY).await {
+                Ok(candles) => {
+                    println!("🕯 Backfilled {} {} candles for {}", candles.len(), bar, inst_id);
+                    bars.extend(inst_id, bar, candles);
+                    // seed the strategy with the latest bar
+                    if let (Some(strat), Some(last)) = (strats.get_mut(inst_id), bars.latest(inst_id, bar)) {
+                        for req in strat.on_candle(inst_id, bar, last, &bars) {
+                            println!("▶️  {} OrderRequest from on_candle (backfill): {:?}", inst_id, req);
                         }
-                    } else {
-                        eprintln!("⚠️ Couldn't parse WS message: {}", txt);
                     }
                 }
+                Err(e) => eprintln!("⚠️ Candle backfill for {} failed: {}", inst_id, e),
             }
+        }
+    }
 
-            // ─── 6b) Timer event for on_timer ────────────────────────────────
-            _ = ticker.tick() => {
-                let now = Instant::now();
-                for req in strat.on_timer(now) {
-                    println!("⏲️  OrderRequest from on_timer: {:?}", req);
+    // ─── 3c) Check our clock against OKX's and warm strategies up from REST
+    //        (not in a replay either), dropping instruments OKX doesn't list ─
+    if replay_dir.is_none() {
+        match rest.system_time().await {
+            Ok(okx) => {
+                let skew = unix_ms() as i64 - okx as i64;
+                if skew.unsigned_abs() > MAX_CLOCK_SKEW.as_millis() as u64 {
+                    eprintln!("⚠️ Local clock is {} ms off OKX's: order expiry times will be too", skew);
                 }
             }
+            Err(e) => eprintln!("⚠️ Fetching OKX's time failed: {}", e),
+        }
+        for inst_id in warm_up(&rest, &inst_ids, &registry, &mut strats).await {
+            eprintln!("⚠️ OKX doesn't list {}, dropping it", inst_id);
+            strats.remove(&inst_id);
+            quoters.remove(&inst_id);
+            books.untrack(&inst_id);
+            inst_ids.retain(|id| *id != inst_id);
+        }
+        if inst_ids.is_empty() {
+            eprintln!("❌ No instruments left to trade");
+            std::process::exit(1);
+        }
+    }
+
+    // ─── 4) Start the OKX feed; it keeps the connection alive and replays subscriptions ─
+    let mut subscriptions: Vec<_> = inst_ids.iter().map(|id| Subscription::new(channel.name(), id.as_str())).collect();
+    let mut indices = HashMap::new();
+    for inst_id in &inst_ids {
+        let (subs, index) = market_subscriptions(inst_id, registry.get(inst_id));
+        subscriptions.extend(subs);
+        if let Some(index) = index {
+            indices.insert(inst_id.clone(), index);
+        }
+    }
+    let trade_subs: Vec<_> = inst_ids
+        .iter()
+        .map(|id| Subscription::new(if trades_all { "trades-all" } else { "trades" }, id.as_str()))
+        .collect();
+    // candles and trades-all only exist on the business endpoint
+    let mut business_subs = Vec::new();
+    if trades_all {
+        business_subs.extend(trade_subs);
+    } else {
+        subscriptions.extend(trade_subs);
+    }
+    if let Some(bar) = bar {
+        business_subs.extend(inst_ids.iter().map(|id| Subscription::new(bar.channel(), id.as_str())));
+    }
+    let (events_tx, events) = tokio::sync::mpsc::unbounded_channel();
+    // a replay stands in for every feed, reading the same instruments and indices back from disk
+    let replay = replay_dir.map(|dir| {
+        let mut recorded = inst_ids.clone();
+        recorded.extend(indices.values().cloned());
+        let frames = FrameReader::open(dir.as_ref(), &recorded, replay_clock).unwrap_or_else(|e| {
+            eprintln!("❌ Can't replay {}: {}", dir, e);
+            std::process::exit(1);
+        });
+        println!("⏯ Replaying {} recorded parts from {}", frames.parts(), dir);
+        Replay::new(frames, replay_speed, TIMER_PERIOD)
+    });
+    let (public, business) = match record_dir {
+        Some(_) => (FeedConfig::default().with_raw_frames(), FeedConfig::with_url(BUSINESS_URL).with_raw_frames()),
+        None => (FeedConfig::default(), FeedConfig::with_url(BUSINESS_URL)),
+    };
+    let public_url = public.url.clone();
+    let _business_feed = (!business_subs.is_empty() && replay.is_none())
+        .then(|| OkxFeed::spawn_into(business, business_subs, events_tx.clone()));
+    let credentials = Credentials::from_env();
+    let private_feed = credentials.clone().filter(|_| replay.is_none()).map(|credentials| {
+        let account_subs = vec![
+            Subscription::by_type("orders", "ANY"),
+            Subscription::by_type("positions", "ANY"),
+            Subscription::account("account"),
+            Subscription::account("balance_and_position"),
+        ];
+        let config = FeedConfig::with_url(private_url.as_str()).with_credentials(credentials);
+        OkxFeed::spawn_into(config, account_subs, events_tx.clone())
+    });
+    let (feed, replay_tx) = match replay {
+        Some(_) => (None, Some(events_tx)),
+        None => (Some(OkxFeed::spawn_into(public, subscriptions, events_tx)), None),
+    };
+
+    // ─── 4b) Order entry over the private socket (REST when it's down), or on paper ─
+    let clock = replay.as_ref().map_or_else(Clock::default, Replay::clock);
+    let contract_size = |inst_id: &str| registry.get(inst_id).map_or(1.0, InstrumentSpec::contract_size);
+    let venue = match (live, credentials) {
+        _ if paper => {
+            let paper = PaperVenue::new().with_clock(clock.clone()).with_cancel_model(cancel_model);
+            let paper = inst_ids.iter().fold(paper, |paper, inst_id| {
+                let rates = fee_tier.rates(inst_type_of(inst_id, registry.get(inst_id)));
+                paper.with_fees(inst_id.as_str(), rates, contract_size(inst_id))
+            });
+            Some(Venue::Paper(paper))
+        }
+        (false, _) => None,
+        (true, Some(credentials)) => {
+            Some(Venue::Live(ExecutionGateway::new(private_feed.clone(), rest.clone().with_credentials(credentials))))
+        }
+        (true, None) => {
+            eprintln!("❌ --live needs OKX_API_KEY, OKX_API_SECRET and OKX_API_PASSPHRASE");
+            std::process::exit(1);
+        }
+    };
+    // every order sent gets a clOrdId and a lifecycle in the OMS; venue answers report back here
+    let oms = match replay {
+        Some(_) => OrderManager::new().with_clock(clock.clone()).with_id_seed(REPLAY_ID_SEED),
+        None => OrderManager::new(),
+    };
+    let (order_events_tx, order_events) = tokio::sync::mpsc::unbounded_channel();
+    let backtest = backtest_dir.as_ref().map(|_| {
+        let mut backtest = Backtest::new(capital);
+        for inst_id in &inst_ids {
+            backtest.track(inst_id.as_str(), contract_size(inst_id));
+        }
+        backtest
+    });
+
+    // opened last, once nothing can exit before the shutdown below closes and indexes it
+    let (recorder, recorder_task) = match record_dir.map(|dir| (Recorder::open(&dir), dir)) {
+        Some((Ok(recorder), dir)) => {
+            if let Err(e) = recorder.write_instruments(&recorded_instruments) {
+                eprintln!("⚠️ Saving instrument specs to {} failed: {}", dir, e);
+            }
+            let (handle, task) = recorder.spawn();
+            (Some(handle), Some(task))
+        }
+        Some((Err(e), dir)) => {
+            eprintln!("❌ Can't record to {}: {}", dir, e);
+            std::process::exit(1);
+        }
+        None => (None, None),
+    };
+
+    // ─── 5) Route events until the feed stops, the replay ends or Ctrl-C ──
+    let instrument_updates = match replay {
+        // a replay keeps its specs throughout: a closed channel
+        Some(_) => tokio::sync::mpsc::unbounded_channel().1,
+        None => instruments::spawn_refresh(rest.clone(), INSTRUMENT_REFRESH),
+    };
+    let engine = Engine {
+        strats,
+        books,
+        quoters,
+        halted,
+        registry,
+        bars,
+        indices,
+        venue,
+        oms,
+        backtest,
+        clock,
+        channel,
+        ord_type,
+        reduce_only,
+        price_tolerance,
+        size_tolerance,
+        feed,
+        public_url,
+        private_url,
+        events,
+        replay,
+        replay_tx,
+        recorder,
+        order_events_tx,
+        order_events,
+        instrument_updates,
+    };
+    let backtest = engine.run().await;
+
+    // ─── 6) Shutdown ────────────────────────────────────────────────────────
+    // the engine dropped its recorder handle: wait for the files to be closed and indexed
+    if let Some(task) = recorder_task {
+        match task.await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => eprintln!("⚠️ Closing recording failed: {}", e),
+            Err(e) => eprintln!("⚠️ Recording writer died: {}", e),
+        }
+    }
+    if let (Some(backtest), Some(dir)) = (backtest, backtest_dir) {
+        match backtest.write(dir.as_ref()) {
+            Ok(report) => println!(
+                "📊 PnL {:.4} ({:.4} fees, {:.2}%) | {} fills on {} orders | turnover {:.2} | Sharpe {} | max drawdown {:.4} ({:.2}%) | written to {}",
+                report.pnl,
+                report.fees,
+                report.return_pct,
+                report.fills,
+                report.orders,
+                report.turnover,
+                report.sharpe.map_or("-".to_string(), |s| format!("{:.2}", s)),
+                report.max_drawdown,
+                report.max_drawdown_pct,
+                dir
+            ),
+            Err(e) => eprintln!("⚠️ Writing backtest report to {} failed: {}", dir, e),
         }
     }
 }
-// fn main() {
-//     let mut strat = StatMM::new(0.1, 1.0, 1.0);
-    
-//     let mut book = OrderBook::new();
-//     let rt = Runtime::new().unwrap();
-//     rt.block_on(async {
-//         let url = "wss://ws.okx.com:8443/ws/v5/public";
-//         let (mut ws_stream, _) = connect_async(url).await.expect("connect failed");
-
-//         let msg = json!({
-//             "op": "subscribe",
-//             "args": [{
-//                 "channel": "books",
-//                 "instId": "AI16Z-USDT-SWAP"
-//             }]
-//         })
-//         .to_string();
-
-//         ws_stream.send(tokio_tungstenite::tungstenite::Message::Text(msg.into())).await.unwrap();
-
-//         while let Some(msg) = ws_stream.next().await {
-//             let msg = msg.unwrap();
-//             if let tokio_tungstenite::tungstenite::Message::Text(txt) = msg {
-//                 if let Ok(parsed) = serde_json::from_str::<WsBookPush>(&txt) {
-//                     // println!("📥 Received: {:?}", parsed);
-//                     for data in parsed.data {
-//                         match parsed.action.as_deref() {
-//                             Some("snapshot") => book.apply_snapshot(&data),
-//                             Some("update") => book.apply_update(&data),
-//                             _ => {}
-//                         }
-//                         if let (Some((bid, _)), Some((ask, _))) = (book.best_bid(), book.best_ask()) {
-//                             let mid = (bid + ask) / 2.0;
-//                             let now = Instant::now();
-
-//                             // 6a.iv) Strategy: on_price_tick
-//                             for req in strat.on_price_tick(mid, now) {
-//                                 println!("▶️  OrderRequest from on_price_tick: {:?}", req);
-//                                 // → here you'd actually send the order to OKX
-//                             }
-
-//                         }
-                
-//                         // 🎯 START ACTING HERE (next step)
-//                     }
-//                 } else {
-//                     println!("⚠️ Couldn't parse: {}", txt);
-//                 }
-//             }
-//         }
-//     });
-// }
\ No newline at end of file
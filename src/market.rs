@@ -0,0 +1,297 @@
+//! Typed public market data, converted from OKX's string-encoded pushes.
+//! Timestamps are Unix milliseconds.
+
+use std::fmt;
+
+use crate::models::{FundingHistoryData, FundingRateData, IndexTickerData, MarkPriceData, OpenInterestData, TickerData, TradeData};
+use crate::strategy::Side;
+
+/// A public trade print.
+#[derive(Debug, Clone)]
+pub struct Trade {
+    pub inst_id: String,
+    /// For an aggregated print, the id of its first fill.
+    pub trade_id: String,
+    pub price: f64,
+    pub size: f64,
+    /// Side of the aggressor (taker).
+    pub side: Side,
+    pub ts: u64,
+    /// Fills aggregated into this print at one price; always 1 on `trades-all`.
+    pub count: u32,
+}
+
+impl Trade {
+    pub fn from_okx(data: &TradeData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            trade_id: data.tradeId.clone(),
+            price: data.px.parse().ok()?,
+            size: data.sz.parse().ok()?,
+            side: match data.side.as_str() {
+                "buy" => Side::Buy,
+                "sell" => Side::Sell,
+                _ => return None,
+            },
+            ts: data.ts.parse().ok()?,
+            count: match &data.count {
+                Some(count) => count.parse().ok()?,
+                None => 1,
+            },
+        })
+    }
+}
+
+/// Last trade, top of book and 24h volume.
+#[derive(Debug, Clone)]
+pub struct Ticker {
+    pub inst_id: String,
+    pub last: f64,
+    pub bid: f64,
+    pub bid_size: f64,
+    pub ask: f64,
+    pub ask_size: f64,
+    /// 24h volume in contracts (derivatives) or base currency (spot).
+    pub volume_24h: f64,
+}
+
+impl Ticker {
+    pub fn from_okx(data: &TickerData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            last: data.last.parse().ok()?,
+            bid: data.bidPx.parse().ok()?,
+            bid_size: data.bidSz.parse().ok()?,
+            ask: data.askPx.parse().ok()?,
+            ask_size: data.askSz.parse().ok()?,
+            volume_24h: data.vol24h.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for Ticker {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} last {}, {} @ {} / {} @ {}, 24h volume {}",
+            self.inst_id, self.last, self.bid_size, self.bid, self.ask_size, self.ask, self.volume_24h
+        )
+    }
+}
+
+/// Mark price, the reference OKX uses for unrealised PnL and liquidation.
+#[derive(Debug, Clone)]
+pub struct MarkPrice {
+    pub inst_id: String,
+    pub price: f64,
+}
+
+impl MarkPrice {
+    pub fn from_okx(data: &MarkPriceData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            price: data.markPx.parse().ok()?,
+        })
+    }
+}
+
+/// Spot index price of an underlying, e.g. `BTC-USDT`.
+#[derive(Debug, Clone)]
+pub struct IndexPrice {
+    pub index: String,
+    pub price: f64,
+}
+
+impl IndexPrice {
+    pub fn from_okx(data: &IndexTickerData) -> Option<Self> {
+        Some(Self {
+            index: data.instId.clone(),
+            price: data.idxPx.parse().ok()?,
+        })
+    }
+}
+
+/// Perpetual swap funding for the current period. A positive rate means longs
+/// pay shorts at the next settlement.
+#[derive(Debug, Clone)]
+pub struct FundingRate {
+    pub inst_id: String,
+    pub rate: f64,
+}
+
+impl FundingRate {
+    pub fn from_okx(data: &FundingRateData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            rate: data.fundingRate.parse().ok()?,
+        })
+    }
+}
+
+/// A settled funding period from the funding history.
+#[derive(Debug, Clone)]
+pub struct FundingPayment {
+    pub inst_id: String,
+    /// Rate published ahead of settlement.
+    pub rate: f64,
+    /// Rate actually charged at `funding_time`.
+    pub realized_rate: f64,
+    pub funding_time: u64,
+}
+
+impl FundingPayment {
+    pub fn from_okx(data: &FundingHistoryData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            rate: data.fundingRate.parse().ok()?,
+            realized_rate: data.realizedRate.parse().ok()?,
+            funding_time: data.fundingTime.parse().ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OpenInterest {
+    pub inst_id: String,
+    /// Open interest in contracts.
+    pub contracts: f64,
+    /// Open interest in the underlying currency.
+    pub ccy: f64,
+    pub usd: Option<f64>,
+}
+
+impl OpenInterest {
+    pub fn from_okx(data: &OpenInterestData) -> Option<Self> {
+        Some(Self {
+            inst_id: data.instId.clone(),
+            contracts: data.oi.parse().ok()?,
+            ccy: data.oiCcy.parse().ok()?,
+            usd: data.oiUsd.as_deref().and_then(|u| u.parse().ok()),
+        })
+    }
+}
+
+impl fmt::Display for OpenInterest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} open interest {} ({} in coin", self.inst_id, self.contracts, self.ccy)?;
+        if let Some(usd) = self.usd {
+            write!(f, ", {:.0} USD", usd)?;
+        }
+        write!(f, ")")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data<T: serde::de::DeserializeOwned>(value: serde_json::Value) -> T {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn trades_keep_their_id_and_aggregation_count() {
+        let aggregated: TradeData = data(serde_json::json!({
+            "instId": "BTC-USDT",
+            "tradeId": "130639474",
+            "px": "42219.9",
+            "sz": "0.12060306",
+            "side": "buy",
+            "ts": "1630048897897",
+            "count": "3",
+        }));
+        let trade = Trade::from_okx(&aggregated).unwrap();
+        assert_eq!(trade.trade_id, "130639474");
+        assert_eq!((trade.price, trade.size, trade.side, trade.ts, trade.count), (42219.9, 0.12060306, Side::Buy, 1630048897897, 3));
+
+        // trades-all sends every fill on its own, without a count
+        let single: TradeData = data(serde_json::json!({
+            "instId": "BTC-USDT", "tradeId": "130639475", "px": "42219.8", "sz": "1", "side": "sell", "ts": "1630048897898",
+        }));
+        let trade = Trade::from_okx(&single).unwrap();
+        assert_eq!((trade.side, trade.count), (Side::Sell, 1));
+
+        let unknown_side: TradeData = data(serde_json::json!({
+            "instId": "BTC-USDT", "tradeId": "1", "px": "1", "sz": "1", "side": "", "ts": "1",
+        }));
+        assert!(Trade::from_okx(&unknown_side).is_none());
+    }
+
+    #[test]
+    fn tickers_parse_top_of_book_and_24h_volume() {
+        let ticker: TickerData = data(serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "last": "9999.99",
+            "lastSz": "0.1",
+            "askPx": "10000.5",
+            "askSz": "11",
+            "bidPx": "9999.5",
+            "bidSz": "5",
+            "open24h": "9000",
+            "high24h": "10100",
+            "low24h": "8888.88",
+            "volCcy24h": "2.222",
+            "vol24h": "2222",
+            "ts": "1597026383085",
+        }));
+        let ticker = Ticker::from_okx(&ticker).unwrap();
+        assert_eq!((ticker.bid, ticker.bid_size, ticker.ask, ticker.ask_size), (9999.5, 5.0, 10000.5, 11.0));
+        assert_eq!((ticker.last, ticker.volume_24h), (9999.99, 2222.0));
+    }
+
+    #[test]
+    fn tickers_show_the_touch_and_24h_volume() {
+        let ticker = Ticker { inst_id: "BTC-USDT-SWAP".to_string(), last: 9999.99, bid: 9999.5, bid_size: 5.0, ask: 10000.5, ask_size: 11.0, volume_24h: 2222.0 };
+        assert_eq!(ticker.to_string(), "BTC-USDT-SWAP last 9999.99, 5 @ 9999.5 / 11 @ 10000.5, 24h volume 2222");
+    }
+
+    #[test]
+    fn mark_and_index_prices_parse() {
+        let mark: MarkPriceData = data(serde_json::json!({ "instType": "SWAP", "instId": "BTC-USDT-SWAP", "markPx": "42310.6", "ts": "1630049139746" }));
+        let mark = MarkPrice::from_okx(&mark).unwrap();
+        assert_eq!((mark.inst_id.as_str(), mark.price), ("BTC-USDT-SWAP", 42310.6));
+
+        let index: IndexTickerData = data(serde_json::json!({
+            "instId": "BTC-USDT", "idxPx": "42305.1", "open24h": "41000", "high24h": "43000", "low24h": "40500", "ts": "1630049139746",
+        }));
+        let index = IndexPrice::from_okx(&index).unwrap();
+        assert_eq!((index.index.as_str(), index.price), ("BTC-USDT", 42305.1));
+
+        let no_price: MarkPriceData = data(serde_json::json!({ "instType": "SWAP", "instId": "BTC-USDT-SWAP", "markPx": "", "ts": "1" }));
+        assert!(MarkPrice::from_okx(&no_price).is_none());
+    }
+
+    #[test]
+    fn funding_rates_parse_and_ignore_the_forecast() {
+        let funding: FundingRateData = data(serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "fundingRate": "0.0001",
+            "fundingTime": "1630051200000",
+            "nextFundingRate": "",
+            "nextFundingTime": "1630080000000",
+            "ts": "1630049139746",
+        }));
+        let funding = FundingRate::from_okx(&funding).unwrap();
+        assert_eq!((funding.inst_id.as_str(), funding.rate), ("BTC-USDT-SWAP", 0.0001));
+
+        let no_rate: FundingRateData = data(serde_json::json!({ "instId": "BTC-USDT-SWAP", "fundingRate": "" }));
+        assert!(FundingRate::from_okx(&no_rate).is_none());
+    }
+
+    #[test]
+    fn open_interest_parses_contracts_coin_and_usd() {
+        let oi: OpenInterestData = data(serde_json::json!({
+            "instType": "SWAP", "instId": "BTC-USDT-SWAP", "oi": "2216113.01", "oiCcy": "22161.1301", "oiUsd": "937000000", "ts": "1630049139746",
+        }));
+        let oi = OpenInterest::from_okx(&oi).unwrap();
+        assert_eq!((oi.contracts, oi.ccy, oi.usd), (2216113.01, 22161.1301, Some(937000000.0)));
+        assert_eq!(oi.to_string(), "BTC-USDT-SWAP open interest 2216113.01 (22161.1301 in coin, 937000000 USD)");
+
+        let no_usd: OpenInterestData = data(serde_json::json!({
+            "instType": "SWAP", "instId": "BTC-USDT-SWAP", "oi": "1", "oiCcy": "0.01", "ts": "1",
+        }));
+        assert_eq!(OpenInterest::from_okx(&no_usd).unwrap().to_string(), "BTC-USDT-SWAP open interest 1 (0.01 in coin)");
+    }
+}
@@ -1,6 +1,10 @@
-use serde::Deserialize;
+// Field names mirror OKX's JSON keys.
+#![allow(non_snake_case)]
 
+use serde::{Deserialize, Serialize};
 
+use crate::kline::Bar;
+use crate::orderbook::BookChannel;
 
 #[derive(Debug, Deserialize)]
 pub struct OkxResponse<T> {
@@ -32,7 +36,7 @@ pub struct Leg {
     pub side: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Instrument {
     pub instType: Option<String>,
     pub instId: Option<String>,
@@ -103,3 +107,518 @@ pub struct BookData {
     pub prevSeqId: Option<i64>,
 }
 
+/// Channel argument echoed in event replies. Public market channels carry
+/// `instId`; some channels are keyed by `instType`/`instFamily` instead.
+#[derive(Debug, Clone, Deserialize)]
+pub struct WsArg {
+    pub channel: String,
+    pub instId: Option<String>,
+    pub instType: Option<String>,
+    pub instFamily: Option<String>,
+}
+
+impl WsArg {
+    /// What the subscription is keyed by: the instrument, family or type, or `-` for none.
+    pub fn key(&self) -> &str {
+        self.instId.as_deref().or(self.instFamily.as_deref()).or(self.instType.as_deref()).unwrap_or("-")
+    }
+}
+
+/// `{"event": ...}` replies from the OKX WebSocket.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "event", rename_all = "kebab-case")]
+pub enum WsEvent {
+    Subscribe { arg: WsArg },
+    Unsubscribe { arg: WsArg },
+    Login { code: String, msg: String },
+    Error { code: String, msg: String },
+    /// e.g. code `64008`: the connection will be closed for a service upgrade.
+    Notice { code: String, msg: String },
+    ChannelConnCount { channel: String, connCount: String },
+    ChannelConnCountError { channel: String, connCount: String },
+}
+
+/// Data push on a channel keyed by `{channel, instId}`.
+#[derive(Debug, Deserialize)]
+pub struct WsPush<T> {
+    pub arg: BookArg,
+    pub data: Vec<T>,
+}
+
+/// One print from `trades` (possibly aggregated, see `count`) or `trades-all`.
+#[derive(Debug, Deserialize)]
+pub struct TradeData {
+    pub instId: String,
+    pub tradeId: String,
+    pub px: String,
+    pub sz: String,
+    pub side: String, // taker side: buy or sell
+    pub ts: String,
+    pub count: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TickerData {
+    pub instId: String,
+    pub last: String,
+    pub askPx: String,
+    pub askSz: String,
+    pub bidPx: String,
+    pub bidSz: String,
+    pub vol24h: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MarkPriceData {
+    pub instId: String,
+    pub markPx: String,
+}
+
+/// `index-tickers` push; `instId` is the index, e.g. `BTC-USDT`.
+#[derive(Debug, Deserialize)]
+pub struct IndexTickerData {
+    pub instId: String,
+    pub idxPx: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FundingRateData {
+    pub instId: String,
+    pub fundingRate: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OpenInterestData {
+    pub instId: String,
+    pub oi: String,
+    pub oiCcy: String,
+    pub oiUsd: Option<String>,
+}
+
+/// `GET /api/v5/public/funding-rate-history` row; `realizedRate` is what was actually settled.
+#[derive(Debug, Deserialize)]
+pub struct FundingHistoryData {
+    pub instId: String,
+    pub fundingRate: String,
+    pub realizedRate: String,
+    pub fundingTime: String,
+}
+
+/// `GET /api/v5/public/time`
+#[derive(Debug, Deserialize)]
+pub struct SystemTimeData {
+    pub ts: String,
+}
+
+/// Push on a private channel. Its `arg` is keyed by `instType` (or nothing)
+/// rather than `instId`, and every row names its own instrument, so only the
+/// rows are kept.
+#[derive(Debug, Deserialize)]
+pub struct WsPrivatePush<T> {
+    pub data: Vec<T>,
+}
+
+/// `orders` push: the order's current state plus, when `tradeId` is set, the fill that caused it.
+#[derive(Debug, Deserialize)]
+pub struct OrderData {
+    pub instId: String,
+    pub ordId: String,
+    pub clOrdId: String,
+    pub px: String,
+    pub sz: String,
+    pub side: String,
+    pub state: String,
+    pub accFillSz: String,
+    pub avgPx: String,
+    pub fillPx: String,
+    pub fillSz: String,
+    pub tradeId: String,
+    pub fillFee: Option<String>,
+    pub fillFeeCcy: Option<String>,
+    pub fillPnl: Option<String>,
+    pub execType: Option<String>,
+    pub uTime: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PositionData {
+    pub instId: String,
+    pub posSide: String,
+    pub pos: String,
+    pub avgPx: String,
+    pub upl: Option<String>,
+    pub liqPx: Option<String>,
+    pub markPx: Option<String>,
+    pub lever: Option<String>,
+    pub mgnMode: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountData {
+    pub totalEq: String,
+    pub details: Vec<AccountDetailData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountDetailData {
+    pub ccy: String,
+    pub eq: String,
+    pub cashBal: String,
+    pub availBal: String,
+    pub frozenBal: String,
+}
+
+/// `balance_and_position` push, sent once per account event (fill, transfer, funding, ...).
+#[derive(Debug, Deserialize)]
+pub struct BalanceAndPositionData {
+    pub eventType: String,
+    pub balData: Vec<BalData>,
+    pub posData: Vec<PosData>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BalData {
+    pub ccy: String,
+    pub cashBal: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PosData {
+    pub instId: String,
+    pub mgnMode: String,
+    pub posSide: String,
+    pub pos: String,
+    pub avgPx: String,
+}
+
+/// Reply to a trading op (`order`, `batch-orders`, `amend-order`, ...) sent
+/// over the WebSocket. `code` is `0` on success, `1` if every item failed and
+/// `2` if a batch partially succeeded; each item carries its own `sCode`.
+#[derive(Debug, Deserialize)]
+pub struct WsOpReply {
+    pub id: String,
+    pub code: String,
+    pub msg: String,
+    #[serde(default)]
+    pub data: Vec<OrderAckData>,
+}
+
+/// Per-order result of a place/amend/cancel, over WebSocket or REST.
+#[derive(Debug, Deserialize)]
+pub struct OrderAckData {
+    #[serde(default)]
+    pub clOrdId: String,
+    #[serde(default)]
+    pub ordId: String,
+    /// Amend request id, echoed back.
+    #[serde(default)]
+    pub reqId: String,
+    pub sCode: String,
+    pub sMsg: String,
+}
+
+/// Any text frame from the OKX WebSocket other than `pong`.
+#[derive(Debug)]
+pub enum OkxWsMessage {
+    Event(WsEvent),
+    Books(WsBookPush),
+    Trades(WsPush<TradeData>),
+    Tickers(WsPush<TickerData>),
+    MarkPrice(WsPush<MarkPriceData>),
+    IndexTickers(WsPush<IndexTickerData>),
+    FundingRate(WsPush<FundingRateData>),
+    OpenInterest(WsPush<OpenInterestData>),
+    /// `candle*` rows: `[ts, o, h, l, c, vol, volCcy, volCcyQuote, confirm]`.
+    Candles(WsPush<Vec<String>>),
+    Orders(WsPrivatePush<OrderData>),
+    Positions(WsPrivatePush<PositionData>),
+    Account(WsPrivatePush<AccountData>),
+    BalanceAndPosition(WsPrivatePush<BalanceAndPositionData>),
+    /// Reply to a trading op nobody was waiting for any more.
+    OpReply(WsOpReply),
+}
+
+impl OkxWsMessage {
+    pub fn parse(txt: &str) -> serde_json::Result<Self> {
+        #[derive(Deserialize)]
+        struct Probe {
+            event: Option<String>,
+            op: Option<String>,
+            arg: Option<WsArg>,
+        }
+
+        let probe: Probe = serde_json::from_str(txt)?;
+        if probe.event.is_some() {
+            return serde_json::from_str(txt).map(OkxWsMessage::Event);
+        }
+        if probe.op.is_some() {
+            return serde_json::from_str(txt).map(OkxWsMessage::OpReply);
+        }
+        match probe.arg.as_ref().map(|a| a.channel.as_str()) {
+            Some(channel) if BookChannel::from_name(channel).is_some() => {
+                serde_json::from_str(txt).map(OkxWsMessage::Books)
+            }
+            Some("trades" | "trades-all") => serde_json::from_str(txt).map(OkxWsMessage::Trades),
+            Some("tickers") => serde_json::from_str(txt).map(OkxWsMessage::Tickers),
+            Some("mark-price") => serde_json::from_str(txt).map(OkxWsMessage::MarkPrice),
+            Some("index-tickers") => serde_json::from_str(txt).map(OkxWsMessage::IndexTickers),
+            Some("funding-rate") => serde_json::from_str(txt).map(OkxWsMessage::FundingRate),
+            Some("open-interest") => serde_json::from_str(txt).map(OkxWsMessage::OpenInterest),
+            Some(channel) if Bar::from_channel(channel).is_some() => {
+                serde_json::from_str(txt).map(OkxWsMessage::Candles)
+            }
+            Some("orders") => serde_json::from_str(txt).map(OkxWsMessage::Orders),
+            Some("positions") => serde_json::from_str(txt).map(OkxWsMessage::Positions),
+            Some("account") => serde_json::from_str(txt).map(OkxWsMessage::Account),
+            Some("balance_and_position") => serde_json::from_str(txt).map(OkxWsMessage::BalanceAndPosition),
+            _ => Err(serde::de::Error::custom("unsupported channel")),
+        }
+    }
+}
+
+/// OKX error codes the feed reacts to.
+pub mod ws_codes {
+    /// Subscription rejected: the channel or `instId` doesn't exist.
+    pub const BAD_CHANNEL_OR_INST: &str = "60018";
+    /// Notice that the connection will be closed for a service upgrade.
+    pub const SERVICE_UPGRADE: &str = "64008";
+    /// Private channel login rejected (bad key, passphrase or signature).
+    pub const LOGIN_FAILED: &str = "60009";
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(value: serde_json::Value) -> OkxWsMessage {
+        OkxWsMessage::parse(&value.to_string()).unwrap()
+    }
+
+    fn push(channel: &str, data: serde_json::Value) -> OkxWsMessage {
+        parse(serde_json::json!({ "arg": { "channel": channel, "instId": "BTC-USDT-SWAP" }, "data": [data] }))
+    }
+
+    fn private_push(channel: &str, data: serde_json::Value) -> OkxWsMessage {
+        parse(serde_json::json!({ "arg": { "channel": channel, "instType": "ANY", "uid": "77" }, "data": [data] }))
+    }
+
+    #[test]
+    fn event_replies_parse_by_their_event() {
+        let login = parse(serde_json::json!({ "event": "login", "code": "0", "msg": "", "connId": "a4d3ae55" }));
+        assert!(matches!(login, OkxWsMessage::Event(WsEvent::Login { code, .. }) if code == "0"));
+
+        let subscribe = parse(serde_json::json!({ "event": "subscribe", "arg": { "channel": "books", "instId": "BTC-USDT" }, "connId": "a" }));
+        let OkxWsMessage::Event(WsEvent::Subscribe { arg }) = subscribe else {
+            panic!("not a subscribe reply");
+        };
+        assert_eq!((arg.channel.as_str(), arg.key()), ("books", "BTC-USDT"));
+        let by_type = parse(serde_json::json!({ "event": "unsubscribe", "arg": { "channel": "orders", "instType": "ANY" }, "connId": "a" }));
+        assert!(matches!(by_type, OkxWsMessage::Event(WsEvent::Unsubscribe { arg }) if arg.key() == "ANY"));
+
+        let error = parse(serde_json::json!({
+            "event": "error",
+            "code": ws_codes::BAD_CHANNEL_OR_INST,
+            "msg": "Wrong URL or channel:books,instId:FOO-USDT doesn't exist.",
+            "connId": "a",
+        }));
+        assert!(matches!(error, OkxWsMessage::Event(WsEvent::Error { code, .. }) if code == ws_codes::BAD_CHANNEL_OR_INST));
+
+        let notice = parse(serde_json::json!({
+            "event": "notice",
+            "code": ws_codes::SERVICE_UPGRADE,
+            "msg": "The connection will soon be closed for a service upgrade.",
+            "connId": "a",
+        }));
+        assert!(matches!(notice, OkxWsMessage::Event(WsEvent::Notice { code, .. }) if code == ws_codes::SERVICE_UPGRADE));
+
+        let count = parse(serde_json::json!({ "event": "channel-conn-count", "channel": "orders", "connCount": "2", "connId": "a" }));
+        assert!(matches!(count, OkxWsMessage::Event(WsEvent::ChannelConnCount { connCount, .. }) if connCount == "2"));
+    }
+
+    #[test]
+    fn op_replies_carry_each_orders_result() {
+        let reply = parse(serde_json::json!({
+            "id": "1512",
+            "op": "batch-orders",
+            "code": "2",
+            "msg": "",
+            "data": [
+                { "clOrdId": "a", "ordId": "12", "tag": "", "sCode": "0", "sMsg": "" },
+                { "clOrdId": "b", "ordId": "", "tag": "", "sCode": "51008", "sMsg": "Insufficient balance" },
+            ],
+        }));
+        let OkxWsMessage::OpReply(reply) = reply else {
+            panic!("not an op reply");
+        };
+        assert_eq!((reply.id.as_str(), reply.code.as_str()), ("1512", "2"));
+        assert_eq!(reply.data[1].sCode, "51008");
+
+        let rejected = parse(serde_json::json!({ "id": "1513", "op": "order", "code": "60013", "msg": "Invalid args", "data": [] }));
+        assert!(matches!(rejected, OkxWsMessage::OpReply(reply) if reply.data.is_empty()));
+    }
+
+    #[test]
+    fn market_pushes_parse_by_their_channel() {
+        for channel in ["books", "books5", "bbo-tbt", "books-l2-tbt", "books50-l2-tbt"] {
+            let book = push(channel, serde_json::json!({
+                "asks": [["8476.98", "415", "0", "13"]],
+                "bids": [["8476.97", "256", "0", "12"]],
+                "ts": "1597026383085",
+                "checksum": -855196043,
+                "seqId": 123456,
+                "prevSeqId": 123455,
+            }));
+            assert!(matches!(book, OkxWsMessage::Books(push) if push.arg.channel == channel && push.data[0].seqId == Some(123456)));
+        }
+
+        for channel in ["trades", "trades-all"] {
+            let trade = push(channel, serde_json::json!({
+                "instId": "BTC-USDT-SWAP",
+                "tradeId": "130639474",
+                "px": "42219.9",
+                "sz": "0.12060306",
+                "side": "buy",
+                "ts": "1630048897897",
+                "count": "3",
+            }));
+            assert!(matches!(trade, OkxWsMessage::Trades(push) if push.data[0].px == "42219.9"));
+        }
+
+        let ticker = push("tickers", serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "last": "9999.99",
+            "lastSz": "0.1",
+            "askPx": "9999.99",
+            "askSz": "11",
+            "bidPx": "8888.88",
+            "bidSz": "5",
+            "open24h": "9000",
+            "high24h": "10000",
+            "low24h": "8888.88",
+            "volCcy24h": "2222",
+            "vol24h": "2222",
+            "sodUtc0": "2222",
+            "sodUtc8": "2222",
+            "ts": "1597026383085",
+        }));
+        assert!(matches!(ticker, OkxWsMessage::Tickers(push) if push.data[0].bidSz == "5"));
+
+        let mark = push("mark-price", serde_json::json!({ "instType": "SWAP", "instId": "BTC-USDT-SWAP", "markPx": "42310.6", "ts": "1630049139746" }));
+        assert!(matches!(mark, OkxWsMessage::MarkPrice(push) if push.data[0].markPx == "42310.6"));
+
+        let index = parse(serde_json::json!({
+            "arg": { "channel": "index-tickers", "instId": "BTC-USDT" },
+            "data": [{ "instId": "BTC-USDT", "idxPx": "0.1", "high24h": "0.5", "low24h": "0.1", "open24h": "0.1", "sodUtc0": "0.1", "sodUtc8": "0.1", "ts": "1597026383085" }],
+        }));
+        assert!(matches!(index, OkxWsMessage::IndexTickers(push) if push.data[0].idxPx == "0.1"));
+
+        let funding = push("funding-rate", serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "fundingRate": "0.0001",
+            "fundingTime": "1630051200000",
+            "nextFundingRate": "",
+            "nextFundingTime": "1630080000000",
+            "method": "current_period",
+            "ts": "1630049139746",
+        }));
+        assert!(matches!(funding, OkxWsMessage::FundingRate(push) if push.data[0].fundingRate == "0.0001"));
+
+        let oi = push("open-interest", serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "oi": "2216113.01",
+            "oiCcy": "22161.13",
+            "oiUsd": "1000000",
+            "ts": "1630049139746",
+        }));
+        assert!(matches!(oi, OkxWsMessage::OpenInterest(push) if push.data[0].oi == "2216113.01"));
+
+        let candle = push("candle1H", serde_json::json!(["1597026383085", "8533.02", "8553.74", "8527.17", "8548.26", "45247", "529.5858061", "529.5858061", "0"]));
+        assert!(matches!(candle, OkxWsMessage::Candles(push) if push.data[0].len() == 9));
+    }
+
+    #[test]
+    fn private_pushes_parse_by_their_channel() {
+        let order = private_push("orders", serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "ordId": "312269865356374016",
+            "clOrdId": "b1",
+            "px": "42000",
+            "sz": "2",
+            "ordType": "post_only",
+            "side": "buy",
+            "posSide": "net",
+            "state": "partially_filled",
+            "accFillSz": "1",
+            "avgPx": "42000",
+            "fillPx": "42000",
+            "fillSz": "1",
+            "fillTime": "1597026383085",
+            "tradeId": "1",
+            "fillFee": "-0.08",
+            "fillFeeCcy": "USDT",
+            "execType": "M",
+            "uTime": "1597026383085",
+            "cTime": "1597026383085",
+            "code": "0",
+            "msg": "",
+        }));
+        assert!(matches!(order, OkxWsMessage::Orders(push) if push.data[0].state == "partially_filled"));
+
+        let position = private_push("positions", serde_json::json!({
+            "instType": "SWAP",
+            "instId": "BTC-USDT-SWAP",
+            "posId": "307173036051017730",
+            "posSide": "net",
+            "pos": "-3",
+            "avgPx": "42000",
+            "mgnMode": "cross",
+            "uTime": "1597026383085",
+        }));
+        assert!(matches!(position, OkxWsMessage::Positions(push) if push.data[0].pos == "-3"));
+
+        let account = parse(serde_json::json!({
+            "arg": { "channel": "account", "uid": "77" },
+            "data": [{
+                "totalEq": "41624.32",
+                "uTime": "1597026383085",
+                "details": [{ "ccy": "USDT", "eq": "41624.32", "cashBal": "41624.32", "availBal": "41000", "frozenBal": "624.32", "uTime": "1597026383085" }],
+            }],
+        }));
+        assert!(matches!(account, OkxWsMessage::Account(push) if push.data[0].details[0].ccy == "USDT"));
+
+        let balance_and_position = parse(serde_json::json!({
+            "arg": { "channel": "balance_and_position", "uid": "77" },
+            "data": [{
+                "pTime": "1597026383085",
+                "eventType": "filled",
+                "balData": [{ "ccy": "USDT", "cashBal": "41624.32", "uTime": "1597026383085" }],
+                "posData": [{
+                    "posId": "1",
+                    "instId": "BTC-USDT-SWAP",
+                    "instType": "SWAP",
+                    "mgnMode": "cross",
+                    "posSide": "net",
+                    "pos": "1",
+                    "avgPx": "42000",
+                    "uTime": "1597026383085",
+                }],
+            }],
+        }));
+        assert!(matches!(balance_and_position, OkxWsMessage::BalanceAndPosition(push) if push.data[0].posData.len() == 1));
+    }
+
+    #[test]
+    fn unknown_channels_and_malformed_frames_are_errors() {
+        let unknown = serde_json::json!({ "arg": { "channel": "liquidation-orders", "instType": "SWAP" }, "data": [] });
+        assert!(OkxWsMessage::parse(&unknown.to_string()).is_err());
+        // a known channel whose data doesn't match its shape
+        let bad = serde_json::json!({ "arg": { "channel": "mark-price", "instId": "BTC-USDT-SWAP" }, "data": [{ "instId": "BTC-USDT-SWAP" }] });
+        assert!(OkxWsMessage::parse(&bad.to_string()).is_err());
+        assert!(OkxWsMessage::parse("pong").is_err());
+        assert!(OkxWsMessage::parse("{}").is_err());
+    }
+}
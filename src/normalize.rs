@@ -0,0 +1,523 @@
+//! Order normalisation: puts strategy quotes onto the instrument's grid before
+//! they go anywhere near OKX. Prices round to `tickSz` away from crossing (bids
+//! down, asks up), and sizes round down to `lotSz` and are checked against
+//! `minSz`/`maxLmtSz`, and base-currency sizes become contracts via `ctVal`.
+
+use std::fmt;
+use std::time::Instant;
+
+use crate::account::{AccountUpdate, OrderUpdate, Position};
+use crate::instruments::InstrumentSpec;
+use crate::kline::{Bar, BarStore, Candle};
+use crate::market::{FundingRate, IndexPrice, MarkPrice, OpenInterest, Ticker, Trade};
+use crate::oms::{ManagedOrder, OrderAction};
+use crate::orderbook::{manager::BookManager, BookStatus, OrderBook};
+use crate::precision::Precision;
+use crate::strategy::{GridPoint, OrderFill, OrderRequest, Side, Strategy};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum NormalizeError {
+    /// Not finite, or not positive once rounded to the tick.
+    InvalidPrice(f64),
+    /// Not finite or not positive.
+    InvalidSize(f64),
+    /// Below `minSz` after rounding to the lot; both in contracts (base for spot).
+    BelowMinSize { size: f64, min: f64 },
+}
+
+impl fmt::Display for NormalizeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NormalizeError::InvalidPrice(p) => write!(f, "invalid price {}", p),
+            NormalizeError::InvalidSize(s) => write!(f, "invalid size {}", s),
+            NormalizeError::BelowMinSize { size, min } => write!(f, "size {} below minimum {}", size, min),
+        }
+    }
+}
+
+impl std::error::Error for NormalizeError {}
+
+/// Unit a strategy sizes its orders in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeUnit {
+    /// OKX's own `sz`: contracts for derivatives, base currency for spot.
+    Contracts,
+    /// Base currency; converted to contracts for derivatives.
+    Base,
+}
+
+impl SizeUnit {
+    /// Parse `--size-unit`: `contracts` or `base`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "contracts" => Some(SizeUnit::Contracts),
+            "base" => Some(SizeUnit::Base),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OrderNormalizer {
+    precision: Precision,
+    min_sz: f64,
+    max_lmt_sz: Option<f64>,
+    /// Base currency per contract; 1 for spot.
+    contract_size: f64,
+    unit: SizeUnit,
+}
+
+impl OrderNormalizer {
+    pub fn for_instrument(spec: &InstrumentSpec) -> Self {
+        Self {
+            precision: spec.precision,
+            min_sz: spec.min_sz,
+            max_lmt_sz: spec.max_lmt_sz,
+            contract_size: if spec.is_derivative() { spec.contract_size() } else { 1.0 },
+            unit: SizeUnit::Contracts,
+        }
+    }
+
+    pub fn with_size_unit(mut self, unit: SizeUnit) -> Self {
+        self.unit = unit;
+        self
+    }
+
+    pub fn base_to_contracts(&self, base: f64) -> f64 {
+        base / self.contract_size
+    }
+
+    pub fn contracts_to_base(&self, contracts: f64) -> f64 {
+        contracts * self.contract_size
+    }
+
+    /// The order OKX would accept, in OKX units. Sizes above `maxLmtSz` are
+    /// capped rather than rejected so a quote still goes out.
+    pub fn normalize(&self, order: &OrderRequest) -> Result<OrderRequest, NormalizeError> {
+        let ticks = self.price_ticks(order.side, order.price)?;
+        let lots = self.size_lots(order.size)?;
+        Ok(OrderRequest {
+            side: order.side,
+            price: self.precision.tick.to_f64(ticks),
+            size: self.precision.lot.to_f64(lots),
+            grid: Some(GridPoint { ticks, lots }),
+        })
+    }
+
+    /// An amend of a working `side` order, in OKX units: a new price and total
+    /// size are rounded, converted and checked like a new order's.
+    pub fn normalize_amend(&self, side: Side, price: Option<f64>, size: Option<f64>) -> Result<(Option<f64>, Option<f64>), NormalizeError> {
+        let price = price.map(|p| self.price_ticks(side, p).map(|ticks| self.precision.tick.to_f64(ticks))).transpose()?;
+        let size = size.map(|s| self.size_lots(s).map(|lots| self.precision.lot.to_f64(lots))).transpose()?;
+        Ok((price, size))
+    }
+
+    /// `price` in ticks, rounded away from crossing.
+    fn price_ticks(&self, side: Side, price: f64) -> Result<i64, NormalizeError> {
+        if !price.is_finite() || price <= 0.0 {
+            return Err(NormalizeError::InvalidPrice(price));
+        }
+        let tick = self.precision.tick;
+        let ticks = match side {
+            Side::Buy => tick.floor_steps_f64(price),
+            Side::Sell => tick.ceil_steps_f64(price),
+        };
+        if ticks <= 0 {
+            return Err(NormalizeError::InvalidPrice(price));
+        }
+        Ok(ticks)
+    }
+
+    /// `size`, in the strategy's unit, as whole lots of OKX's `sz`.
+    fn size_lots(&self, size: f64) -> Result<i64, NormalizeError> {
+        if !size.is_finite() || size <= 0.0 {
+            return Err(NormalizeError::InvalidSize(size));
+        }
+        let lot = self.precision.lot;
+        let size = match self.unit {
+            SizeUnit::Contracts => size,
+            SizeUnit::Base => self.base_to_contracts(size),
+        };
+        let size = match self.max_lmt_sz {
+            Some(max) => size.min(max),
+            None => size,
+        };
+        let lots = lot.floor_steps_f64(size);
+        let size = lot.to_f64(lots);
+        if size <= 0.0 || size < self.min_sz {
+            return Err(NormalizeError::BelowMinSize { size, min: self.min_sz });
+        }
+        Ok(lots)
+    }
+}
+
+/// Wraps a strategy so every `OrderRequest` it returns is normalised; orders
+/// that can't be made valid are logged and dropped, and fills come back in the
+/// strategy's unit. Without a normalizer (no instrument metadata yet) orders
+/// pass through until `on_instrument` brings one; base-sized orders are held
+/// back instead, as there's no `ctVal` to convert them with.
+pub struct NormalizedStrategy {
+    inner: Box<dyn Strategy>,
+    normalizer: Option<OrderNormalizer>,
+    unit: SizeUnit,
+}
+
+impl NormalizedStrategy {
+    pub fn new(inner: Box<dyn Strategy>, normalizer: Option<OrderNormalizer>) -> Self {
+        let unit = normalizer.as_ref().map_or(SizeUnit::Contracts, |n| n.unit);
+        Self { inner, normalizer, unit }
+    }
+
+    /// Size the wrapped strategy's orders in `unit`, now and on every new spec.
+    pub fn with_size_unit(mut self, unit: SizeUnit) -> Self {
+        self.unit = unit;
+        self.normalizer = self.normalizer.map(|n| n.with_size_unit(unit));
+        self
+    }
+
+    fn apply(&self, orders: Vec<OrderRequest>) -> Vec<OrderRequest> {
+        let Some(normalizer) = &self.normalizer else {
+            if self.unit == SizeUnit::Base && !orders.is_empty() {
+                eprintln!("⚠️ Holding {} base-sized order(s): no instrument spec to convert them with", orders.len());
+                return Vec::new();
+            }
+            return orders;
+        };
+        orders
+            .iter()
+            .filter_map(|order| match normalizer.normalize(order) {
+                Ok(order) => Some(order),
+                Err(e) => {
+                    eprintln!("⚠️ Dropping {:?}: {}", order, e);
+                    None
+                }
+            })
+            .collect()
+    }
+}
+
+impl Strategy for NormalizedStrategy {
+    fn on_price_tick(&mut self, price: f64, now: Instant) -> Vec<OrderRequest> {
+        let orders = self.inner.on_price_tick(price, now);
+        self.apply(orders)
+    }
+
+    fn on_order_book(&mut self, bids: &OrderBook, asks: &OrderBook) -> Vec<OrderRequest> {
+        let orders = self.inner.on_order_book(bids, asks);
+        self.apply(orders)
+    }
+
+    fn on_book_update(&mut self, inst_id: &str, books: &BookManager) -> Vec<OrderRequest> {
+        let orders = self.inner.on_book_update(inst_id, books);
+        self.apply(orders)
+    }
+
+    fn on_timer(&mut self, now: Instant) -> Vec<OrderRequest> {
+        let orders = self.inner.on_timer(now);
+        self.apply(orders)
+    }
+
+    fn on_trade(&mut self, trade: &Trade) -> Vec<OrderRequest> {
+        let orders = self.inner.on_trade(trade);
+        self.apply(orders)
+    }
+
+    fn on_ticker(&mut self, ticker: &Ticker) -> Vec<OrderRequest> {
+        let orders = self.inner.on_ticker(ticker);
+        self.apply(orders)
+    }
+
+    fn on_mark_price(&mut self, mark: &MarkPrice) -> Vec<OrderRequest> {
+        let orders = self.inner.on_mark_price(mark);
+        self.apply(orders)
+    }
+
+    fn on_index_price(&mut self, index: &IndexPrice) -> Vec<OrderRequest> {
+        let orders = self.inner.on_index_price(index);
+        self.apply(orders)
+    }
+
+    fn on_funding_rate(&mut self, funding: &FundingRate) -> Vec<OrderRequest> {
+        let orders = self.inner.on_funding_rate(funding);
+        self.apply(orders)
+    }
+
+    fn on_open_interest(&mut self, oi: &OpenInterest) -> Vec<OrderRequest> {
+        let orders = self.inner.on_open_interest(oi);
+        self.apply(orders)
+    }
+
+    fn on_candle(&mut self, inst_id: &str, bar: Bar, candle: &Candle, bars: &BarStore) -> Vec<OrderRequest> {
+        let orders = self.inner.on_candle(inst_id, bar, candle, bars);
+        self.apply(orders)
+    }
+
+    fn on_order_filled(&mut self, mut fill: OrderFill) {
+        if let (SizeUnit::Base, Some(normalizer)) = (self.unit, &self.normalizer) {
+            fill.size = normalizer.contracts_to_base(fill.size);
+        }
+        self.inner.on_order_filled(fill)
+    }
+
+    fn on_order_update(&mut self, update: &OrderUpdate) {
+        self.inner.on_order_update(update)
+    }
+
+    /// The wrapped strategy sees its working orders in its own unit, and its
+    /// amends are normalised like new orders; an amend that can't be made valid
+    /// is logged and dropped.
+    fn manage_orders(&mut self, open: &[&ManagedOrder]) -> Vec<OrderAction> {
+        let Some(normalizer) = &self.normalizer else {
+            let actions = self.inner.manage_orders(open);
+            if self.unit == SizeUnit::Contracts {
+                return actions;
+            }
+            // no ctVal to convert sizes with either way: cancels only
+            return actions.into_iter().filter(|a| matches!(a, OrderAction::Cancel(_))).collect();
+        };
+        let actions = match self.unit {
+            SizeUnit::Contracts => self.inner.manage_orders(open),
+            SizeUnit::Base => {
+                let converted: Vec<ManagedOrder> = open
+                    .iter()
+                    .map(|&o| ManagedOrder {
+                        size: normalizer.contracts_to_base(o.size),
+                        filled: normalizer.contracts_to_base(o.filled),
+                        ..o.clone()
+                    })
+                    .collect();
+                self.inner.manage_orders(&converted.iter().collect::<Vec<_>>())
+            }
+        };
+        actions
+            .into_iter()
+            .filter_map(|action| {
+                let OrderAction::Amend { id, price, size } = action else {
+                    return Some(action);
+                };
+                // the OMS ignores amends of orders it doesn't have
+                let Some(order) = open.iter().find(|o| o.id == id) else {
+                    return Some(action);
+                };
+                match normalizer.normalize_amend(order.side, price, size) {
+                    Ok((price, size)) => Some(OrderAction::Amend { id, price, size }),
+                    Err(e) => {
+                        eprintln!("⚠️ Dropping amend of {}: {}", order.cl_ord_id, e);
+                        None
+                    }
+                }
+            })
+            .collect()
+    }
+
+    fn on_position(&mut self, position: &Position) {
+        self.inner.on_position(position)
+    }
+
+    fn on_account(&mut self, account: &AccountUpdate) {
+        self.inner.on_account(account)
+    }
+
+    fn on_book_status(&mut self, inst_id: &str, status: BookStatus) {
+        self.inner.on_book_status(inst_id, status)
+    }
+
+    /// Move onto the instrument's new grid and limits.
+    fn on_instrument(&mut self, spec: &InstrumentSpec) {
+        self.normalizer = Some(OrderNormalizer::for_instrument(spec).with_size_unit(self.unit));
+        self.inner.on_instrument(spec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::models::Instrument;
+
+    fn spec(inst_type: &str, ct_val: &str, max_lmt_sz: &str) -> InstrumentSpec {
+        let inst: Instrument = serde_json::from_value(serde_json::json!({
+            "instType": inst_type,
+            "instId": "BTC-USDT-SWAP",
+            "state": "live",
+            "ctVal": ct_val,
+            "tickSz": "0.1",
+            "lotSz": "0.01",
+            "minSz": "0.05",
+            "maxLmtSz": max_lmt_sz,
+        }))
+        .unwrap();
+        InstrumentSpec::from_okx(&inst).unwrap()
+    }
+
+    fn swap() -> OrderNormalizer {
+        OrderNormalizer::for_instrument(&spec("SWAP", "0.01", "100"))
+    }
+
+    fn order(side: Side, price: f64, size: f64) -> OrderRequest {
+        OrderRequest { side, price, size, grid: None }
+    }
+
+    #[test]
+    fn bids_round_down_and_asks_round_up_to_the_tick() {
+        let bid = swap().normalize(&order(Side::Buy, 100.07, 1.0)).unwrap();
+        let ask = swap().normalize(&order(Side::Sell, 100.01, 1.0)).unwrap();
+        assert_eq!(bid.price, 100.0);
+        assert_eq!(ask.price, 100.1);
+        assert_eq!(bid.grid.unwrap().ticks, 1000);
+        assert_eq!(ask.grid.unwrap().ticks, 1001);
+    }
+
+    #[test]
+    fn sizes_round_down_to_the_lot() {
+        let normalized = swap().normalize(&order(Side::Buy, 100.0, 1.239)).unwrap();
+        assert_eq!(normalized.size, 1.23);
+        assert_eq!(normalized.grid.unwrap().lots, 123);
+    }
+
+    #[test]
+    fn sizes_below_the_minimum_are_rejected() {
+        assert_eq!(
+            swap().normalize(&order(Side::Buy, 100.0, 0.049)).unwrap_err(),
+            NormalizeError::BelowMinSize { size: 0.04, min: 0.05 }
+        );
+        assert!(swap().normalize(&order(Side::Buy, 100.0, 0.05)).is_ok());
+    }
+
+    #[test]
+    fn sizes_above_the_limit_maximum_are_capped() {
+        let normalized = swap().normalize(&order(Side::Sell, 100.0, 250.0)).unwrap();
+        assert_eq!(normalized.size, 100.0);
+    }
+
+    #[test]
+    fn non_finite_and_non_positive_inputs_are_rejected() {
+        for price in [f64::NAN, f64::INFINITY, 0.0, -1.0] {
+            assert!(matches!(swap().normalize(&order(Side::Buy, price, 1.0)), Err(NormalizeError::InvalidPrice(_))));
+        }
+        for size in [f64::NAN, f64::NEG_INFINITY, 0.0, -1.0] {
+            assert!(matches!(swap().normalize(&order(Side::Buy, 100.0, size)), Err(NormalizeError::InvalidSize(_))));
+        }
+        // a positive bid below the first tick rounds to zero
+        assert_eq!(swap().normalize(&order(Side::Buy, 0.05, 1.0)).unwrap_err(), NormalizeError::InvalidPrice(0.05));
+    }
+
+    #[test]
+    fn base_sizes_convert_to_contracts_via_ct_val() {
+        let normalizer = swap().with_size_unit(SizeUnit::Base);
+        assert_eq!(normalizer.base_to_contracts(0.5), 50.0);
+        assert_eq!(normalizer.contracts_to_base(50.0), 0.5);
+        // 0.0123 BTC is 1.23 contracts of 0.01 BTC
+        let normalized = normalizer.normalize(&order(Side::Buy, 100.0, 0.0123)).unwrap();
+        assert!((normalized.size - 1.23).abs() < 1e-9);
+        // ctVal doesn't apply to spot: base is already OKX's `sz`
+        let spot = OrderNormalizer::for_instrument(&spec("SPOT", "", "")).with_size_unit(SizeUnit::Base);
+        assert_eq!(spot.normalize(&order(Side::Buy, 100.0, 1.5)).unwrap().size, 1.5);
+    }
+
+    /// Quotes `orders` on every timer and keeps the sizes of the fills it sees.
+    struct Fixed {
+        orders: Vec<OrderRequest>,
+        fills: Rc<RefCell<Vec<f64>>>,
+    }
+
+    impl Strategy for Fixed {
+        fn on_timer(&mut self, _now: Instant) -> Vec<OrderRequest> {
+            self.orders.clone()
+        }
+
+        fn on_order_filled(&mut self, fill: OrderFill) {
+            self.fills.borrow_mut().push(fill.size);
+        }
+    }
+
+    fn fixed(orders: Vec<OrderRequest>) -> (Box<dyn Strategy>, Rc<RefCell<Vec<f64>>>) {
+        let fills = Rc::new(RefCell::new(Vec::new()));
+        (Box::new(Fixed { orders, fills: fills.clone() }), fills)
+    }
+
+    fn fill(size: f64) -> OrderFill {
+        OrderFill { id: uuid::Uuid::nil(), side: Side::Buy, entry_price: 100.0, exit_price: 100.0, size, pnl: 0.0 }
+    }
+
+    #[test]
+    fn orders_pass_through_until_the_instrument_arrives_then_are_normalised() {
+        let (inner, _) = fixed(vec![order(Side::Buy, 100.07, 1.239), order(Side::Sell, 100.0, 0.01)]);
+        let mut strat = NormalizedStrategy::new(inner, None);
+        let now = Instant::now();
+
+        let raw = strat.on_timer(now);
+        assert_eq!(raw.len(), 2);
+        assert_eq!((raw[0].price, raw[0].size, raw[0].grid), (100.07, 1.239, None));
+
+        strat.on_instrument(&spec("SWAP", "0.01", "100"));
+        let normalized = strat.on_timer(now);
+        // the second order is below minSz and dropped
+        assert_eq!(normalized.len(), 1);
+        assert_eq!((normalized[0].price, normalized[0].size), (100.0, 1.23));
+        assert!(normalized[0].grid.is_some());
+    }
+
+    #[test]
+    fn base_sized_strategies_wait_for_the_instrument_and_see_fills_in_base() {
+        let (inner, fills) = fixed(vec![order(Side::Buy, 100.0, 0.5)]);
+        let mut strat = NormalizedStrategy::new(inner, None).with_size_unit(SizeUnit::Base);
+        let now = Instant::now();
+
+        assert!(strat.on_timer(now).is_empty());
+
+        strat.on_instrument(&spec("SWAP", "0.01", "100"));
+        assert_eq!(strat.on_timer(now)[0].size, 50.0);
+        strat.on_order_filled(fill(20.0));
+        assert_eq!(*fills.borrow(), vec![0.2]);
+    }
+
+    /// Amends every working order to `to(order)`, and keeps the sizes it was shown.
+    struct Amender {
+        to: fn(&ManagedOrder) -> (Option<f64>, Option<f64>),
+        seen: Rc<RefCell<Vec<f64>>>,
+    }
+
+    impl Strategy for Amender {
+        fn manage_orders(&mut self, open: &[&ManagedOrder]) -> Vec<OrderAction> {
+            self.seen.borrow_mut().extend(open.iter().map(|o| o.size));
+            open.iter()
+                .map(|o| {
+                    let (price, size) = (self.to)(o);
+                    OrderAction::Amend { id: o.id, price, size }
+                })
+                .collect()
+        }
+    }
+
+    #[test]
+    fn base_sized_strategies_see_and_amend_working_orders_in_base() {
+        let mut oms = crate::oms::OrderManager::new();
+        let bid = oms.submit("s", "BTC-USDT-SWAP", &order(Side::Buy, 100.0, 50.0)).clone();
+        let ask = oms.submit("s", "BTC-USDT-SWAP", &order(Side::Sell, 101.0, 50.0)).clone();
+        let seen = Rc::new(RefCell::new(Vec::new()));
+        let to = |o: &ManagedOrder| match o.side {
+            // 2 BTC is 200 contracts, capped at maxLmtSz
+            Side::Buy => (Some(100.07), Some(2.0)),
+            // 0.0004 BTC is 0.04 contracts, below minSz
+            Side::Sell => (Some(101.01), Some(0.0004)),
+        };
+        let mut strat = NormalizedStrategy::new(Box::new(Amender { to, seen: seen.clone() }), None).with_size_unit(SizeUnit::Base);
+        strat.on_instrument(&spec("SWAP", "0.01", "100"));
+
+        let actions = strat.manage_orders(&[&bid, &ask]);
+        assert_eq!(*seen.borrow(), vec![0.5, 0.5]);
+        assert_eq!(actions.len(), 1);
+        let OrderAction::Amend { id, price, size } = actions[0] else {
+            panic!("expected an amend, got {:?}", actions[0]);
+        };
+        assert_eq!((id, price, size), (bid.id, Some(100.0), Some(100.0)));
+
+        // asks round up; a price-only amend leaves the size alone
+        let normalizer = swap().with_size_unit(SizeUnit::Base);
+        assert_eq!(normalizer.normalize_amend(Side::Sell, Some(101.01), None), Ok((Some(101.1), None)));
+        assert_eq!(normalizer.normalize_amend(Side::Buy, None, Some(0.0123)), Ok((None, Some(1.23))));
+    }
+}
@@ -0,0 +1,598 @@
+//! Order management: gives every order a client order id, follows it through
+//! New → Acked → PartiallyFilled → Filled/Cancelled/Rejected from gateway acks
+//! and `orders` pushes, keeps each strategy's working orders, and reconciles
+//! against OKX's view after a reconnect.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+use uuid::Uuid;
+
+use crate::account::{OrderState, OrderUpdate};
+use crate::clock::Clock;
+use crate::execution::{AmendOrder, CancelOrder, OrderAck, OrderRef};
+use crate::precision::Precision;
+use crate::strategy::{OrderRequest, Side};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OrderStatus {
+    /// Created locally, not yet acknowledged by OKX.
+    New,
+    Acked,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderStatus {
+    pub fn is_final(&self) -> bool {
+        matches!(self, OrderStatus::Filled | OrderStatus::Cancelled | OrderStatus::Rejected)
+    }
+
+    pub fn is_open(&self) -> bool {
+        !self.is_final()
+    }
+
+    /// Orders only move forward; a stale or reordered update (e.g. the `orders`
+    /// push overtaking the ack) must not move one back. Repeated partial fills are fine.
+    pub fn can_become(&self, next: OrderStatus) -> bool {
+        match (self, next) {
+            (from, _) if from.is_final() => false,
+            (OrderStatus::PartiallyFilled, OrderStatus::PartiallyFilled) => true,
+            (from, to) => to > *from,
+        }
+    }
+
+    fn from_okx(state: OrderState) -> Self {
+        match state {
+            OrderState::Live => OrderStatus::Acked,
+            OrderState::PartiallyFilled => OrderStatus::PartiallyFilled,
+            OrderState::Filled => OrderStatus::Filled,
+            OrderState::Canceled | OrderState::MmpCanceled => OrderStatus::Cancelled,
+        }
+    }
+}
+
+impl fmt::Display for OrderStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+/// One of our orders as the OMS sees it.
+#[derive(Debug, Clone)]
+pub struct ManagedOrder {
+    pub id: Uuid,
+    /// `id` in OKX's `clOrdId` format (32 hex digits).
+    pub cl_ord_id: String,
+    /// Key of the strategy that owns the order.
+    pub strategy: String,
+    pub inst_id: String,
+    pub side: Side,
+    pub price: f64,
+    pub size: f64,
+    pub filled: f64,
+    pub avg_price: f64,
+    pub status: OrderStatus,
+    /// OKX's order id, once acknowledged.
+    pub ord_id: Option<String>,
+    /// A cancel has been sent and not yet confirmed.
+    pub cancel_pending: bool,
+    /// An amend has been sent and not yet confirmed.
+    pub amend_pending: bool,
+    /// `reqId` of the latest amend sent.
+    pub amend_req_id: Option<String>,
+    pub reject_reason: Option<String>,
+    /// Unix milliseconds.
+    pub created: u64,
+    pub updated: u64,
+}
+
+impl ManagedOrder {
+    pub fn remaining(&self) -> f64 {
+        (self.size - self.filled).max(0.0)
+    }
+
+    fn order_ref(&self) -> OrderRef {
+        match &self.ord_id {
+            Some(ord_id) => OrderRef::OrdId(ord_id.clone()),
+            None => OrderRef::ClOrdId(self.cl_ord_id.clone()),
+        }
+    }
+}
+
+/// What a strategy wants done with one of its working orders.
+#[derive(Debug, Clone)]
+pub enum OrderAction {
+    Cancel(Uuid),
+    /// New price and/or total size; `None` leaves it unchanged.
+    Amend { id: Uuid, price: Option<f64>, size: Option<f64> },
+}
+
+#[derive(Debug, Default)]
+pub struct OrderManager {
+    orders: HashMap<Uuid, ManagedOrder>,
+    /// Order ids by `clOrdId`, and by OKX's `ordId` once known.
+    cl_ord_ids: HashMap<String, Uuid>,
+    ord_ids: HashMap<String, Uuid>,
+    /// Open order ids per strategy.
+    working: HashMap<String, BTreeSet<Uuid>>,
+    clock: Clock,
+    /// Next order id when numbering from a seed; ids are random otherwise.
+    next_id: Option<u128>,
+    /// Amends sent so far; numbers each amend's `reqId`.
+    amends: u64,
+}
+
+impl OrderManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Stamp orders with `clock` instead of the wall clock.
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    /// Number orders up from `seed` instead of drawing random ids, so that
+    /// runs over the same data (replays) give the same `clOrdId`s.
+    pub fn with_id_seed(mut self, seed: u128) -> Self {
+        self.next_id = Some(seed);
+        self
+    }
+
+    /// Register a new order for `strategy` and return it with its client order id.
+    pub fn submit(&mut self, strategy: &str, inst_id: &str, req: &OrderRequest) -> &ManagedOrder {
+        let id = self.new_id();
+        let now = self.clock.now_ms();
+        let order = ManagedOrder {
+            id,
+            cl_ord_id: id.simple().to_string(),
+            strategy: strategy.to_string(),
+            inst_id: inst_id.to_string(),
+            side: req.side,
+            price: req.price,
+            size: req.size,
+            filled: 0.0,
+            avg_price: 0.0,
+            status: OrderStatus::New,
+            ord_id: None,
+            cancel_pending: false,
+            amend_pending: false,
+            amend_req_id: None,
+            reject_reason: None,
+            created: now,
+            updated: now,
+        };
+        self.insert(order);
+        &self.orders[&id]
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<&ManagedOrder> {
+        self.orders.get(id)
+    }
+
+    /// Working orders of one strategy, oldest first (by id within a millisecond).
+    pub fn open_orders(&self, strategy: &str) -> Vec<&ManagedOrder> {
+        let mut open: Vec<_> = self.working.get(strategy).into_iter().flatten().filter_map(|id| self.orders.get(id)).collect();
+        open.sort_by_key(|o| (o.created, o.id));
+        open
+    }
+
+    pub fn all_open(&self) -> impl Iterator<Item = &ManagedOrder> {
+        self.orders.values().filter(|o| o.status.is_open())
+    }
+
+    /// Apply OKX's answer to a place request.
+    pub fn on_place_ack(&mut self, ack: &OrderAck) -> Option<&ManagedOrder> {
+        let id = self.ack_target(ack)?;
+        if ack.is_ok() {
+            self.transition(id, OrderStatus::Acked);
+        } else {
+            self.reject(id, format!("{}: {}", ack.code, ack.msg));
+        }
+        self.orders.get(&id)
+    }
+
+    /// Apply OKX's answer to an amend or cancel. A failed one leaves the order
+    /// as it was; the `orders` push reports the outcome of a successful one. A
+    /// failed amend that isn't the latest (by `reqId`) says nothing about the
+    /// one still in flight.
+    pub fn on_change_ack(&mut self, ack: &OrderAck) -> Option<&ManagedOrder> {
+        let id = self.ack_target(ack)?;
+        let order = self.orders.get_mut(&id)?;
+        let stale = !ack.req_id.is_empty() && order.amend_req_id.as_deref() != Some(ack.req_id.as_str());
+        if !ack.is_ok() && !stale {
+            order.cancel_pending = false;
+            order.amend_pending = false;
+        }
+        Some(order)
+    }
+
+    /// An amend or cancel got no answer; allow the order to be changed again.
+    pub fn on_change_failed(&mut self, id: &Uuid) {
+        if let Some(order) = self.orders.get_mut(id) {
+            order.cancel_pending = false;
+            order.amend_pending = false;
+        }
+    }
+
+    /// Mark an order OKX never accepted (or never received) as rejected.
+    pub fn reject(&mut self, id: Uuid, reason: String) {
+        if let Some(order) = self.orders.get_mut(&id) {
+            order.reject_reason = Some(reason);
+        }
+        self.transition(id, OrderStatus::Rejected);
+    }
+
+    /// Apply an `orders` push (or a REST order query). Orders we don't know
+    /// about are ignored; see `reconcile` for adopting them.
+    pub fn on_update(&mut self, update: &OrderUpdate) -> Option<&ManagedOrder> {
+        let id = self.find(&update.cl_ord_id, &update.ord_id)?;
+        if self.orders[&id].status.is_final() {
+            return self.orders.get(&id);
+        }
+        self.record_ord_id(id, &update.ord_id);
+        let order = self.orders.get_mut(&id)?;
+        // amends show up as a changed price/size on a live order
+        if update.price > 0.0 {
+            order.price = update.price;
+        }
+        order.size = update.size;
+        order.amend_pending = false;
+        order.filled = order.filled.max(update.filled);
+        order.avg_price = update.avg_price;
+        order.updated = update.ts;
+        self.transition(id, OrderStatus::from_okx(update.state));
+        self.orders.get(&id)
+    }
+
+    /// Mark a cancel as sent and build the request, if the order is still working.
+    pub fn request_cancel(&mut self, id: &Uuid) -> Option<CancelOrder> {
+        let order = self.orders.get_mut(id).filter(|o| o.status.is_open())?;
+        order.cancel_pending = true;
+        Some(CancelOrder { inst_id: order.inst_id.clone(), order: order.order_ref() })
+    }
+
+    /// Mark an amend as sent and build it, formatted on the instrument's grid,
+    /// if the order is still working and not being cancelled.
+    pub fn request_amend(&mut self, id: &Uuid, price: Option<f64>, size: Option<f64>, precision: &Precision) -> Option<AmendOrder> {
+        let order = self.orders.get_mut(id).filter(|o| o.status.is_open() && !o.cancel_pending)?;
+        order.amend_pending = true;
+        self.amends += 1;
+        order.amend_req_id = Some(self.amends.to_string());
+        Some(AmendOrder {
+            inst_id: order.inst_id.clone(),
+            order: order.order_ref(),
+            req_id: order.amend_req_id.clone(),
+            new_px: price.map(|p| precision.tick.format(precision.tick.steps_f64(p))),
+            new_sz: size.map(|s| precision.lot.format(precision.lot.steps_f64(s))),
+            cancel_on_fail: false,
+        })
+    }
+
+    /// Bring local state in line with OKX after a reconnect. `exchange` holds
+    /// every order OKX lists as live plus the latest state of any local open
+    /// order it no longer lists. Local open orders created before `as_of` (Unix
+    /// ms) that appear in neither never reached OKX and are rejected. Live
+    /// orders we don't know that carry our `clOrdId` format (placed by an
+    /// earlier run) are adopted under `strategy_for(inst_id)`; anyone else's,
+    /// such as manual orders, are left alone. Returns the ids whose status changed.
+    pub fn reconcile(&mut self, exchange: &[OrderUpdate], as_of: u64, strategy_for: impl Fn(&str) -> Option<String>) -> Vec<Uuid> {
+        let before: HashMap<Uuid, OrderStatus> = self.orders.iter().map(|(id, o)| (*id, o.status)).collect();
+        let mut seen = BTreeSet::new();
+
+        for update in exchange {
+            if let Some(order) = self.on_update(update) {
+                seen.insert(order.id);
+                continue;
+            }
+            let state = OrderStatus::from_okx(update.state);
+            let Some(id) = own_id(&update.cl_ord_id).filter(|_| state.is_open()) else {
+                continue;
+            };
+            let Some(strategy) = strategy_for(&update.inst_id) else {
+                continue;
+            };
+            // ours, from an earlier run: adopt it so it can be managed
+            self.insert(ManagedOrder {
+                id,
+                cl_ord_id: update.cl_ord_id.clone(),
+                strategy,
+                inst_id: update.inst_id.clone(),
+                side: update.side,
+                price: update.price,
+                size: update.size,
+                filled: update.filled,
+                avg_price: update.avg_price,
+                status: state,
+                ord_id: Some(update.ord_id.clone()),
+                cancel_pending: false,
+                amend_pending: false,
+                amend_req_id: None,
+                reject_reason: None,
+                created: update.ts,
+                updated: update.ts,
+            });
+            seen.insert(id);
+        }
+
+        let missing: Vec<Uuid> = self
+            .orders
+            .values()
+            .filter(|o| o.status.is_open() && o.created < as_of && !seen.contains(&o.id))
+            .map(|o| o.id)
+            .collect();
+        for id in missing {
+            self.reject(id, "not found on exchange".to_string());
+        }
+
+        self.orders
+            .iter()
+            .filter(|(id, o)| before.get(*id) != Some(&o.status))
+            .map(|(id, _)| *id)
+            .collect()
+    }
+
+    /// Forget finished orders last updated before `before` (Unix ms).
+    pub fn prune(&mut self, before: u64) {
+        self.orders.retain(|_, o| o.status.is_open() || o.updated >= before);
+        self.cl_ord_ids.retain(|_, id| self.orders.contains_key(id));
+        self.ord_ids.retain(|_, id| self.orders.contains_key(id));
+    }
+
+    fn new_id(&mut self) -> Uuid {
+        match &mut self.next_id {
+            Some(next) => {
+                *next += 1;
+                Uuid::from_u128(*next - 1)
+            }
+            None => Uuid::new_v4(),
+        }
+    }
+
+    fn insert(&mut self, order: ManagedOrder) {
+        let id = order.id;
+        self.cl_ord_ids.insert(order.cl_ord_id.clone(), id);
+        if let Some(ord_id) = &order.ord_id {
+            self.ord_ids.insert(ord_id.clone(), id);
+        }
+        if order.status.is_open() {
+            self.working.entry(order.strategy.clone()).or_default().insert(id);
+        }
+        self.orders.insert(id, order);
+    }
+
+    /// The order with this `clOrdId`, or failing that this `ordId`.
+    fn find(&self, cl_ord_id: &str, ord_id: &str) -> Option<Uuid> {
+        let by_cl = (!cl_ord_id.is_empty()).then(|| self.cl_ord_ids.get(cl_ord_id)).flatten();
+        let by_ord = || (!ord_id.is_empty()).then(|| self.ord_ids.get(ord_id)).flatten();
+        by_cl.or_else(by_ord).copied()
+    }
+
+    /// Note OKX's `ordId` for an order the first time it's seen.
+    fn record_ord_id(&mut self, id: Uuid, ord_id: &str) {
+        let Some(order) = self.orders.get_mut(&id) else {
+            return;
+        };
+        if order.ord_id.is_none() && !ord_id.is_empty() {
+            order.ord_id = Some(ord_id.to_string());
+            self.ord_ids.insert(ord_id.to_string(), id);
+        }
+    }
+
+    /// Order an ack refers to, recording OKX's `ordId` on the way.
+    fn ack_target(&mut self, ack: &OrderAck) -> Option<Uuid> {
+        let id = self.find(&ack.cl_ord_id, &ack.ord_id)?;
+        self.record_ord_id(id, &ack.ord_id);
+        Some(id)
+    }
+
+    fn transition(&mut self, id: Uuid, next: OrderStatus) {
+        let Some(order) = self.orders.get_mut(&id) else {
+            return;
+        };
+        if !order.status.can_become(next) {
+            return;
+        }
+        order.status = next;
+        order.updated = order.updated.max(self.clock.now_ms());
+        if next.is_final() {
+            order.cancel_pending = false;
+            order.amend_pending = false;
+            if let Some(working) = self.working.get_mut(&order.strategy) {
+                working.remove(&id);
+            }
+        }
+    }
+}
+
+/// The id behind a `clOrdId` we generated: the id's 32 hex digits.
+fn own_id(cl_ord_id: &str) -> Option<Uuid> {
+    let id = Uuid::try_parse(cl_ord_id).ok()?;
+    (id.simple().to_string() == cl_ord_id).then_some(id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::Route;
+    use crate::precision::Step;
+
+    const INST: &str = "BTC-USDT";
+
+    fn request(side: Side, price: f64, size: f64) -> OrderRequest {
+        OrderRequest::new(side, price, size)
+    }
+
+    fn update(order: &ManagedOrder, state: OrderState, filled: f64) -> OrderUpdate {
+        OrderUpdate {
+            inst_id: order.inst_id.clone(),
+            ord_id: "1001".to_string(),
+            cl_ord_id: order.cl_ord_id.clone(),
+            side: order.side,
+            price: order.price,
+            size: order.size,
+            state,
+            filled,
+            avg_price: if filled > 0.0 { order.price } else { 0.0 },
+            execution: None,
+            ts: 1,
+        }
+    }
+
+    fn ack(order: &ManagedOrder, code: &str) -> OrderAck {
+        OrderAck { cl_ord_id: order.cl_ord_id.clone(), ord_id: "1001".to_string(), req_id: String::new(), code: code.to_string(), msg: String::new(), route: Route::Paper }
+    }
+
+    #[test]
+    fn push_before_ack_is_not_undone_by_the_ack() {
+        let mut oms = OrderManager::new();
+        let order = oms.submit(INST, INST, &request(Side::Buy, 100.0, 2.0)).clone();
+
+        oms.on_update(&update(&order, OrderState::PartiallyFilled, 1.0));
+        let after_ack = oms.on_place_ack(&ack(&order, "0")).unwrap();
+        assert_eq!(after_ack.status, OrderStatus::PartiallyFilled);
+        assert_eq!(after_ack.ord_id.as_deref(), Some("1001"));
+        assert_eq!(after_ack.filled, 1.0);
+    }
+
+    #[test]
+    fn updates_after_a_final_state_are_ignored() {
+        let mut oms = OrderManager::new();
+        let order = oms.submit(INST, INST, &request(Side::Sell, 100.0, 2.0)).clone();
+
+        oms.on_update(&update(&order, OrderState::Filled, 2.0));
+        let stale = oms.on_update(&update(&order, OrderState::Live, 0.0)).unwrap();
+        assert_eq!(stale.status, OrderStatus::Filled);
+        assert_eq!(stale.filled, 2.0);
+        assert!(oms.open_orders(INST).is_empty());
+    }
+
+    #[test]
+    fn repeated_partial_fills_accumulate_and_never_shrink() {
+        let mut oms = OrderManager::new();
+        let order = oms.submit(INST, INST, &request(Side::Buy, 100.0, 3.0)).clone();
+
+        oms.on_update(&update(&order, OrderState::PartiallyFilled, 1.0));
+        oms.on_update(&update(&order, OrderState::PartiallyFilled, 2.0));
+        // reordered: the first fill's push arrives again late
+        let order = oms.on_update(&update(&order, OrderState::PartiallyFilled, 1.0)).unwrap();
+        assert_eq!(order.status, OrderStatus::PartiallyFilled);
+        assert_eq!(order.filled, 2.0);
+        assert_eq!(order.remaining(), 1.0);
+    }
+
+    #[test]
+    fn updates_find_orders_by_ord_id_without_a_cl_ord_id() {
+        let mut oms = OrderManager::new();
+        let order = oms.submit(INST, INST, &request(Side::Buy, 100.0, 1.0)).clone();
+        oms.on_place_ack(&ack(&order, "0"));
+
+        let mut by_ord_id = update(&order, OrderState::Canceled, 0.0);
+        by_ord_id.cl_ord_id.clear();
+        assert_eq!(oms.on_update(&by_ord_id).unwrap().status, OrderStatus::Cancelled);
+    }
+
+    #[test]
+    fn only_the_latest_amend_failing_clears_the_pending_amend() {
+        let mut oms = OrderManager::new();
+        let order = oms.submit(INST, INST, &request(Side::Buy, 100.0, 1.0)).clone();
+        oms.on_place_ack(&ack(&order, "0"));
+        let precision = Precision { tick: Step::parse("0.1").unwrap(), lot: Step::parse("1").unwrap() };
+
+        let first = oms.request_amend(&order.id, Some(100.1), None, &precision).unwrap();
+        let second = oms.request_amend(&order.id, Some(100.2), None, &precision).unwrap();
+        assert_ne!(first.req_id, second.req_id);
+
+        let failed = |amend: &AmendOrder| OrderAck { req_id: amend.req_id.clone().unwrap(), ..ack(&order, "51503") };
+        assert!(oms.on_change_ack(&failed(&first)).unwrap().amend_pending);
+        assert!(!oms.on_change_ack(&failed(&second)).unwrap().amend_pending);
+    }
+
+    #[test]
+    fn reconcile_rejects_only_orders_created_before_as_of() {
+        let mut oms = OrderManager::new();
+        let old = oms.submit(INST, INST, &request(Side::Buy, 100.0, 1.0)).id;
+        let new = oms.submit(INST, INST, &request(Side::Sell, 101.0, 1.0)).id;
+        oms.orders.get_mut(&old).unwrap().created = 1_000;
+        oms.orders.get_mut(&new).unwrap().created = 2_000;
+
+        let changed = oms.reconcile(&[], 1_500, |_| None);
+        assert_eq!(changed, vec![old]);
+        assert_eq!(oms.get(&old).unwrap().status, OrderStatus::Rejected);
+        assert_eq!(oms.get(&new).unwrap().status, OrderStatus::New);
+    }
+
+    #[test]
+    fn reconcile_adopts_our_own_orders_once_and_leaves_manual_ones() {
+        let mut oms = OrderManager::new();
+        let earlier_run = Uuid::new_v4();
+        let live = |cl_ord_id: String, ord_id: &str| OrderUpdate {
+            inst_id: INST.to_string(),
+            ord_id: ord_id.to_string(),
+            cl_ord_id,
+            side: Side::Buy,
+            price: 100.0,
+            size: 1.0,
+            state: OrderState::Live,
+            filled: 0.0,
+            avg_price: 0.0,
+            execution: None,
+            ts: 1,
+        };
+        let ours = live(earlier_run.simple().to_string(), "1001");
+        let exchange = [ours.clone(), live("manual1".to_string(), "2002"), live(String::new(), "3003")];
+        let strategy_for = |inst_id: &str| Some(inst_id.to_string());
+
+        assert_eq!(oms.reconcile(&exchange, 0, strategy_for), vec![earlier_run]);
+        assert_eq!(oms.open_orders(INST).len(), 1);
+        assert_eq!(oms.get(&earlier_run).unwrap().status, OrderStatus::Acked);
+
+        // a second reconnect finds the order instead of adopting it again
+        assert!(oms.reconcile(&exchange, 0, strategy_for).is_empty());
+        assert_eq!(oms.open_orders(INST).len(), 1);
+        let filled = oms.on_update(&OrderUpdate { state: OrderState::Filled, filled: 1.0, ..ours }).unwrap();
+        assert_eq!(filled.id, earlier_run);
+        assert_eq!(filled.status, OrderStatus::Filled);
+    }
+
+    #[test]
+    fn prune_forgets_only_old_finished_orders() {
+        let mut oms = OrderManager::new();
+        let open = oms.submit(INST, INST, &request(Side::Buy, 100.0, 1.0)).clone();
+        let old = oms.submit(INST, INST, &request(Side::Buy, 99.0, 1.0)).clone();
+        let recent = oms.submit(INST, INST, &request(Side::Sell, 101.0, 1.0)).clone();
+        oms.reject(old.id, "test".to_string());
+        oms.reject(recent.id, "test".to_string());
+        oms.orders.get_mut(&old.id).unwrap().updated = 1_000;
+        oms.orders.get_mut(&recent.id).unwrap().updated = 3_000;
+
+        oms.prune(2_000);
+        assert!(oms.get(&open.id).is_some());
+        assert!(oms.get(&old.id).is_none());
+        assert!(oms.get(&recent.id).is_some());
+        assert!(oms.on_update(&update(&old, OrderState::Filled, 1.0)).is_none());
+    }
+
+    #[test]
+    fn seeded_orders_are_numbered_and_stamped_by_the_clock() {
+        let clock = Clock::simulated(5_000);
+        let mut oms = OrderManager::new().with_clock(clock.clone()).with_id_seed(1);
+        let first = oms.submit(INST, INST, &request(Side::Buy, 100.0, 1.0)).clone();
+        let second = oms.submit(INST, INST, &request(Side::Sell, 101.0, 1.0)).clone();
+        assert_eq!(first.cl_ord_id, "00000000000000000000000000000001");
+        assert_eq!(second.cl_ord_id, "00000000000000000000000000000002");
+        assert_eq!(own_id(&second.cl_ord_id), Some(second.id));
+        assert_eq!((first.created, second.created), (5_000, 5_000));
+        // same millisecond: oldest first by id
+        let open: Vec<_> = oms.open_orders(INST).iter().map(|o| o.id).collect();
+        assert_eq!(open, vec![first.id, second.id]);
+
+        clock.set_ms(6_000);
+        oms.on_place_ack(&ack(&first, "0"));
+        assert_eq!(oms.get(&first.id).unwrap().updated, 6_000);
+    }
+}
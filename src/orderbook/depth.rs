@@ -0,0 +1,191 @@
+//! Walk-the-book queries: what a marketable order of a given size or notional
+//! would pay, and how much liquidity sits near the mid.
+//!
+//! Sizes and notionals are in book units (contracts for swaps, base currency
+//! for spot); prices are display prices.
+
+use super::{BookSide, OrderBook};
+use crate::strategy::Side;
+
+/// Relative tolerance when deciding whether a sweep filled completely.
+const EPSILON: f64 = 1e-9;
+
+/// Result of sweeping one side of the book with a marketable order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sweep {
+    /// Size actually available; less than requested if the book ran dry.
+    pub filled: f64,
+    /// Σ price × size over the levels consumed.
+    pub notional: f64,
+    /// Average fill price.
+    pub vwap: f64,
+    /// Price of the last (worst) level touched.
+    pub worst_price: f64,
+    /// Number of levels touched, including a partially consumed last one.
+    pub levels: usize,
+    /// Whether the whole order could be filled.
+    pub complete: bool,
+}
+
+impl OrderBook {
+    /// Levels a marketable order on `side` trades against, best first.
+    fn taker_levels(&self, side: Side) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        match side {
+            Side::Buy => Box::new(self.ask_levels()),
+            Side::Sell => Box::new(self.bid_levels()),
+        }
+    }
+
+    fn resting_levels(&self, side: BookSide) -> Box<dyn Iterator<Item = (f64, f64)> + '_> {
+        match side {
+            BookSide::Bid => Box::new(self.bid_levels()),
+            BookSide::Ask => Box::new(self.ask_levels()),
+        }
+    }
+
+    /// Walk the book to fill `size` units on `side` (buys lift asks, sells hit bids).
+    pub fn sweep_size(&self, side: Side, size: f64) -> Option<Sweep> {
+        let mut sweep = self.sweep(side, |_, filled, _| size - filled)?;
+        sweep.complete = sweep.filled >= size * (1.0 - EPSILON);
+        Some(sweep)
+    }
+
+    /// Walk the book until `notional` (price × size) has been spent on `side`.
+    pub fn sweep_notional(&self, side: Side, notional: f64) -> Option<Sweep> {
+        let mut sweep = self.sweep(side, |price, _, spent| (notional - spent) / price)?;
+        sweep.complete = sweep.notional >= notional * (1.0 - EPSILON);
+        Some(sweep)
+    }
+
+    /// Consume levels while `wanted(price, filled, spent)` asks for more size.
+    fn sweep(&self, side: Side, wanted: impl Fn(f64, f64, f64) -> f64) -> Option<Sweep> {
+        let mut filled = 0.0;
+        let mut spent = 0.0;
+        let mut worst_price = None;
+        let mut levels = 0;
+
+        for (price, level_size) in self.taker_levels(side) {
+            let qty = wanted(price, filled, spent).min(level_size);
+            if qty <= 0.0 {
+                break;
+            }
+            filled += qty;
+            spent += qty * price;
+            worst_price = Some(price);
+            levels += 1;
+        }
+
+        Some(Sweep {
+            filled,
+            notional: spent,
+            vwap: spent / filled,
+            worst_price: worst_price?,
+            levels,
+            complete: false,
+        })
+    }
+
+    /// Average fill price to trade `size` units on `side`, if the book is deep enough.
+    pub fn vwap(&self, side: Side, size: f64) -> Option<f64> {
+        self.sweep_size(side, size).filter(|s| s.complete).map(|s| s.vwap)
+    }
+
+    /// Cost of trading `size` units versus the mid, in basis points (≥ 0 for
+    /// an uncrossed book). `None` if the book is one-sided or too thin.
+    pub fn slippage_bps(&self, side: Side, size: f64) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let vwap = self.vwap(side, size)?;
+        Some(adverse_bps(side, vwap, mid))
+    }
+
+    /// How far the touch moves, in basis points from the mid, after spending
+    /// `notional` on `side`. `None` if the book is one-sided or too thin.
+    pub fn impact_bps(&self, side: Side, notional: f64) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let sweep = self.sweep_notional(side, notional).filter(|s| s.complete)?;
+        Some(adverse_bps(side, sweep.worst_price, mid))
+    }
+
+    /// Cumulative depth curve for one side: `(price, size at or better than price)`, best first.
+    pub fn cumulative_depth(&self, side: BookSide) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.resting_levels(side).scan(0.0, |cum, (price, size)| {
+            *cum += size;
+            Some((price, *cum))
+        })
+    }
+
+    /// Total resting size on `side` within `bps` basis points of the mid.
+    pub fn depth_within_bps(&self, side: BookSide, bps: f64) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let band = mid * bps / 10_000.0;
+        let depth = self
+            .cumulative_depth(side)
+            .take_while(|(price, _)| (price - mid).abs() <= band)
+            .last()
+            .map_or(0.0, |(_, cum)| cum);
+        Some(depth)
+    }
+}
+
+/// Distance of `price` from `mid` in basis points, positive when it's worse for `side`.
+fn adverse_bps(side: Side, price: f64, mid: f64) -> f64 {
+    let diff = match side {
+        Side::Buy => price - mid,
+        Side::Sell => mid - price,
+    };
+    diff / mid * 10_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::book;
+
+    /// Bids 99 × 1, 98 × 2, 97 × 3; asks 101 × 1, 102 × 2, 103 × 3; mid 100.
+    fn ladder() -> OrderBook {
+        book("1", "1", &[("99", "1"), ("98", "2"), ("97", "3")], &[("101", "1"), ("102", "2"), ("103", "3")])
+    }
+
+    #[test]
+    fn sweeps_fill_partially_across_levels() {
+        let book = ladder();
+        let buy = book.sweep_size(Side::Buy, 2.5).unwrap();
+        assert_eq!(buy, Sweep { filled: 2.5, notional: 101.0 + 1.5 * 102.0, vwap: 254.0 / 2.5, worst_price: 102.0, levels: 2, complete: true });
+        assert_eq!(book.vwap(Side::Sell, 3.0), Some((99.0 + 2.0 * 98.0) / 3.0));
+
+        let spent = book.sweep_notional(Side::Buy, 101.0 + 102.0).unwrap();
+        assert_eq!((spent.filled, spent.worst_price, spent.complete), (2.0, 102.0, true));
+    }
+
+    #[test]
+    fn sweeping_past_the_end_is_incomplete() {
+        let book = ladder();
+        let sweep = book.sweep_size(Side::Sell, 10.0).unwrap();
+        assert_eq!((sweep.filled, sweep.worst_price, sweep.levels, sweep.complete), (6.0, 97.0, 3, false));
+        assert_eq!(book.vwap(Side::Sell, 10.0), None);
+        assert_eq!(book.slippage_bps(Side::Sell, 10.0), None);
+        assert_eq!(book.impact_bps(Side::Buy, 1_000.0), None);
+
+        let empty = OrderBook::with_precision(book.precision);
+        assert_eq!(empty.sweep_size(Side::Buy, 1.0), None);
+    }
+
+    #[test]
+    fn slippage_and_impact_are_adverse_bps_from_mid() {
+        let book = ladder();
+        assert_eq!(book.slippage_bps(Side::Buy, 1.0), Some(100.0));
+        assert_eq!(book.slippage_bps(Side::Sell, 3.0), Some((100.0 - 295.0 / 3.0) / 100.0 * 10_000.0));
+        assert_eq!(book.impact_bps(Side::Sell, 99.0 + 98.0), Some(200.0));
+    }
+
+    #[test]
+    fn depth_within_bps_includes_levels_on_the_bound() {
+        let book = ladder();
+        assert_eq!(book.cumulative_depth(BookSide::Ask).collect::<Vec<_>>(), vec![(101.0, 1.0), (102.0, 3.0), (103.0, 6.0)]);
+        assert_eq!(book.depth_within_bps(BookSide::Bid, 50.0), Some(0.0));
+        assert_eq!(book.depth_within_bps(BookSide::Bid, 100.0), Some(1.0));
+        assert_eq!(book.depth_within_bps(BookSide::Ask, 299.0), Some(3.0));
+        assert_eq!(book.depth_within_bps(BookSide::Ask, 300.0), Some(6.0));
+        assert_eq!(book.depth_within_bps(BookSide::Bid, 10_000.0), Some(6.0));
+    }
+}
@@ -0,0 +1,204 @@
+//! One `OrderBook` per instrument, fed from OKX book pushes routed by `arg.instId`
+//! and ingested according to the instrument's `BookChannel`.
+
+use std::collections::HashMap;
+
+use super::{BookChannel, BookError, OrderBook};
+use crate::models::WsBookPush;
+
+/// What a push did to the book it was routed to.
+#[derive(Debug)]
+pub enum BookUpdate {
+    /// Not a book channel, or an instrument that isn't tracked.
+    Ignored,
+    /// Nothing applied: a duplicate, or an update while waiting for a resync snapshot.
+    Skipped,
+    /// The book changed; `recovered` is true when this push made it valid again.
+    Applied { recovered: bool },
+    /// The book was invalidated; resubscribe the push's channel to rebuild it.
+    Resync(BookError),
+}
+
+struct Entry {
+    channel: BookChannel,
+    book: OrderBook,
+}
+
+#[derive(Default)]
+pub struct BookManager {
+    books: HashMap<String, Entry>,
+}
+
+impl BookManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start tracking `inst_id`, fed from pushes on `channel`.
+    pub fn track(&mut self, inst_id: impl Into<String>, channel: BookChannel, book: OrderBook) {
+        self.books.insert(inst_id.into(), Entry { channel, book });
+    }
+
+    /// Stop tracking `inst_id`; later pushes for it are ignored.
+    pub fn untrack(&mut self, inst_id: &str) -> Option<OrderBook> {
+        self.books.remove(inst_id).map(|e| e.book)
+    }
+
+    pub fn get(&self, inst_id: &str) -> Option<&OrderBook> {
+        self.books.get(inst_id).map(|e| &e.book)
+    }
+
+    /// Invalidate every book, e.g. when the connection drops; returns the ids
+    /// of books that were valid until now.
+    pub fn invalidate_all(&mut self) -> Vec<String> {
+        self.books
+            .iter_mut()
+            .filter(|(_, e)| e.book.is_valid())
+            .map(|(id, e)| {
+                e.book.invalidate();
+                id.clone()
+            })
+            .collect()
+    }
+
+    /// Route a push to its book and apply it. Duplicates are skipped; any other
+    /// error invalidates the book, and updates are then ignored until the
+    /// caller's resubscription delivers a fresh snapshot.
+    pub fn apply(&mut self, push: &WsBookPush) -> BookUpdate {
+        let Some(entry) = self.books.get_mut(&push.arg.instId) else {
+            return BookUpdate::Ignored;
+        };
+        if entry.channel.name() != push.arg.channel {
+            return BookUpdate::Ignored;
+        }
+        let channel = entry.channel;
+        let book = &mut entry.book;
+
+        let is_snapshot = channel.is_snapshot_only() || push.action.as_deref() == Some("snapshot");
+        let was_valid = book.is_valid();
+        if !is_snapshot && !was_valid {
+            return BookUpdate::Skipped;
+        }
+
+        let mut applied = false;
+        for data in &push.data {
+            let result = if is_snapshot { book.apply_snapshot(data) } else { book.apply_update(data) };
+            match result {
+                Ok(()) => {
+                    book.truncate(channel.depth());
+                    applied = true;
+                }
+                Err(e) if !e.needs_resync() => {}
+                Err(e) => {
+                    book.invalidate();
+                    return BookUpdate::Resync(e);
+                }
+            }
+        }
+
+        if applied {
+            BookUpdate::Applied { recovered: !was_valid }
+        } else {
+            BookUpdate::Skipped
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{BookArg, BookData};
+    use crate::orderbook::book_data;
+    use crate::precision::{Precision, Step};
+
+    fn push(channel: &str, action: &str, bids: &[(&str, &str)], seq: (i64, i64)) -> WsBookPush {
+        WsBookPush {
+            arg: BookArg { channel: channel.to_string(), instId: "BTC-USDT".to_string() },
+            action: Some(action.to_string()),
+            data: vec![BookData { seqId: Some(seq.0), prevSeqId: Some(seq.1), ..book_data(bids, &[("101", "1")]) }],
+        }
+    }
+
+    fn manager() -> BookManager {
+        let mut books = BookManager::new();
+        let precision = Precision { tick: Step::decimal(1), lot: Step::decimal(0) };
+        books.track("BTC-USDT", BookChannel::Books, OrderBook::with_precision(precision));
+        books
+    }
+
+    #[test]
+    fn a_gap_invalidates_the_book_until_the_next_snapshot() {
+        let mut books = manager();
+        assert!(matches!(books.apply(&push("books", "update", &[("100", "1")], (11, 10))), BookUpdate::Skipped));
+
+        assert!(matches!(books.apply(&push("books", "snapshot", &[("100", "1")], (10, -1))), BookUpdate::Applied { recovered: true }));
+        assert!(matches!(books.apply(&push("books", "update", &[("100", "2")], (11, 10))), BookUpdate::Applied { recovered: false }));
+        assert!(matches!(books.apply(&push("books", "update", &[("100", "2")], (11, 10))), BookUpdate::Skipped));
+
+        let gap = books.apply(&push("books", "update", &[("100", "3")], (14, 12)));
+        assert!(matches!(gap, BookUpdate::Resync(BookError::Sequence(_))));
+        assert!(!books.get("BTC-USDT").unwrap().is_valid());
+        assert!(matches!(books.apply(&push("books", "update", &[("100", "4")], (15, 14))), BookUpdate::Skipped));
+
+        assert!(matches!(books.apply(&push("books", "snapshot", &[("99.5", "5")], (20, -1))), BookUpdate::Applied { recovered: true }));
+        let book = books.get("BTC-USDT").unwrap();
+        assert!(book.is_valid());
+        assert_eq!(book.best_bid(), Some((99.5, 5.0)));
+    }
+
+    #[test]
+    fn invalid_data_asks_for_a_resync() {
+        let mut books = manager();
+        books.apply(&push("books", "snapshot", &[("100", "1")], (10, -1)));
+        let crossed = books.apply(&push("books", "update", &[("102", "1")], (11, 10)));
+        assert!(matches!(crossed, BookUpdate::Resync(BookError::Crossed { .. })));
+
+        assert_eq!(books.invalidate_all(), Vec::<String>::new());
+        books.apply(&push("books", "snapshot", &[("100", "1")], (12, -1)));
+        assert_eq!(books.invalidate_all(), vec!["BTC-USDT".to_string()]);
+    }
+
+    #[test]
+    fn pushes_for_other_channels_or_instruments_are_ignored() {
+        let mut books = manager();
+        assert!(matches!(books.apply(&push("books5", "snapshot", &[("100", "1")], (10, -1))), BookUpdate::Ignored));
+        books.untrack("BTC-USDT");
+        assert!(matches!(books.apply(&push("books", "snapshot", &[("100", "1")], (10, -1))), BookUpdate::Ignored));
+    }
+
+    #[test]
+    fn snapshot_only_channels_replace_the_book_on_every_push() {
+        let mut books = BookManager::new();
+        let precision = Precision { tick: Step::decimal(1), lot: Step::decimal(0) };
+        books.track("BTC-USDT", BookChannel::Books5, OrderBook::with_precision(precision));
+        let books5 = |bids: &[(&str, &str)]| WsBookPush {
+            arg: BookArg { channel: "books5".to_string(), instId: "BTC-USDT".to_string() },
+            action: None,
+            data: vec![book_data(bids, &[("101", "1")])],
+        };
+
+        assert!(matches!(books.apply(&books5(&[("100", "1"), ("99", "2")])), BookUpdate::Applied { recovered: true }));
+        assert!(matches!(books.apply(&books5(&[("99.5", "3")])), BookUpdate::Applied { recovered: false }));
+        let book = books.get("BTC-USDT").unwrap();
+        assert_eq!(book.bid_levels().collect::<Vec<_>>(), vec![(99.5, 3.0)]);
+        assert_eq!(book.best_ask(), Some((101.0, 1.0)));
+    }
+
+    #[test]
+    fn books_keeps_at_most_its_channel_depth() {
+        let mut books = manager();
+        // 450 bids from 100.0 down in 0.1 steps
+        let prices: Vec<String> = (0..450).map(|i| 1000 - i).map(|t| format!("{}.{}", t / 10, t % 10)).collect();
+        let bids: Vec<(&str, &str)> = prices.iter().map(|p| (p.as_str(), "1")).collect();
+        books.apply(&push("books", "snapshot", &bids, (10, -1)));
+        assert_eq!(books.get("BTC-USDT").unwrap().bid_levels().count(), 400);
+
+        // a better bid pushes the worst one out
+        books.apply(&push("books", "update", &[("100.1", "1")], (11, 10)));
+        let book = books.get("BTC-USDT").unwrap();
+        assert_eq!(book.bid_levels().count(), 400);
+        assert_eq!(book.best_bid(), Some((100.1, 1.0)));
+        assert_eq!(book.bid_levels().last().map(|(p, _)| p), Some(60.2));
+        assert_eq!(book.ask_levels().count(), 1);
+    }
+}
@@ -0,0 +1,592 @@
+pub mod depth;
+pub mod manager;
+pub mod signals;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::instruments::InstrumentSpec;
+use crate::models::BookData;
+use crate::precision::{Precision, Step};
+use crate::strategy::Side;
+
+/// Price as a whole number of ticks.
+pub type Ticks = i64;
+/// Size as a whole number of lots.
+pub type Lots = i64;
+
+/// Number of levels per side OKX folds into the `books` checksum.
+const CHECKSUM_DEPTH: usize = 25;
+
+/// The locally computed checksum disagrees with the one OKX pushed,
+/// meaning our book has drifted and must be rebuilt from a fresh snapshot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChecksumMismatch {
+    pub expected: i32,
+    pub computed: i32,
+}
+
+impl fmt::Display for ChecksumMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "checksum mismatch: expected {}, computed {}", self.expected, self.computed)
+    }
+}
+
+impl std::error::Error for ChecksumMismatch {}
+
+/// An update that does not chain onto the last applied `seqId`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SequenceError {
+    /// An update arrived before any snapshot was applied.
+    NoSnapshot { prev_seq_id: i64 },
+    /// One or more updates were lost between `last` and `prev_seq_id`.
+    Gap { last: i64, prev_seq_id: i64 },
+    /// The update was already applied.
+    Duplicate { seq_id: i64 },
+    /// The update belongs before the last applied one.
+    OutOfOrder { last: i64, prev_seq_id: i64 },
+}
+
+impl SequenceError {
+    /// Duplicates can be dropped; everything else means the book is unusable.
+    pub fn needs_resync(&self) -> bool {
+        !matches!(self, SequenceError::Duplicate { .. })
+    }
+}
+
+impl fmt::Display for SequenceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SequenceError::NoSnapshot { prev_seq_id } => {
+                write!(f, "update with prevSeqId {} before any snapshot", prev_seq_id)
+            }
+            SequenceError::Gap { last, prev_seq_id } => {
+                write!(f, "sequence gap: last seqId {}, update prevSeqId {}", last, prev_seq_id)
+            }
+            SequenceError::Duplicate { seq_id } => write!(f, "duplicate seqId {}", seq_id),
+            SequenceError::OutOfOrder { last, prev_seq_id } => {
+                write!(f, "out of order: last seqId {}, update prevSeqId {}", last, prev_seq_id)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SequenceError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookSide {
+    Bid,
+    Ask,
+}
+
+impl fmt::Display for BookSide {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            BookSide::Bid => "bid",
+            BookSide::Ask => "ask",
+        })
+    }
+}
+
+/// Why a snapshot or update could not be applied cleanly.
+#[derive(Debug, Clone, PartialEq)]
+pub enum BookError {
+    /// A price or size string is not a decimal number; nothing was applied.
+    Parse { side: BookSide, field: &'static str, value: String },
+    /// A level carries a negative size; nothing was applied.
+    NegativeSize { side: BookSide, price: String, size: String },
+    /// After applying, the best bid is at or above the best ask.
+    Crossed { bid: f64, ask: f64 },
+    /// After applying, the book disagrees with OKX's checksum.
+    Checksum(ChecksumMismatch),
+    /// The update does not chain onto the last one; nothing was applied.
+    Sequence(SequenceError),
+}
+
+impl BookError {
+    /// Everything except a duplicate update leaves the book untrustworthy.
+    pub fn needs_resync(&self) -> bool {
+        match self {
+            BookError::Sequence(e) => e.needs_resync(),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for BookError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BookError::Parse { side, field, value } => {
+                write!(f, "unparseable {} {} {:?}", side, field, value)
+            }
+            BookError::NegativeSize { side, price, size } => {
+                write!(f, "negative {} size {} at {}", side, size, price)
+            }
+            BookError::Crossed { bid, ask } => write!(f, "crossed book: bid {} >= ask {}", bid, ask),
+            BookError::Checksum(e) => e.fmt(f),
+            BookError::Sequence(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for BookError {}
+
+impl From<ChecksumMismatch> for BookError {
+    fn from(e: ChecksumMismatch) -> Self {
+        BookError::Checksum(e)
+    }
+}
+
+impl From<SequenceError> for BookError {
+    fn from(e: SequenceError) -> Self {
+        BookError::Sequence(e)
+    }
+}
+
+/// Whether a book can be trusted; strategies should pull quotes while `Invalid`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BookStatus {
+    Valid,
+    Invalid,
+}
+
+/// OKX order book channels and how each one is ingested.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BookChannel {
+    /// 400 levels, snapshot then incremental updates every 100ms.
+    Books,
+    /// Top 5 levels, full snapshot every 100ms.
+    Books5,
+    /// Top of book, full snapshot on every change.
+    BboTbt,
+    /// 400 levels, tick-by-tick updates. Requires a logged-in, high-VIP account.
+    BooksL2Tbt,
+    /// 50 levels, tick-by-tick updates. Requires a logged-in, high-VIP account.
+    Books50L2Tbt,
+}
+
+impl BookChannel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            BookChannel::Books => "books",
+            BookChannel::Books5 => "books5",
+            BookChannel::BboTbt => "bbo-tbt",
+            BookChannel::BooksL2Tbt => "books-l2-tbt",
+            BookChannel::Books50L2Tbt => "books50-l2-tbt",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "books" => Some(BookChannel::Books),
+            "books5" => Some(BookChannel::Books5),
+            "bbo-tbt" => Some(BookChannel::BboTbt),
+            "books-l2-tbt" => Some(BookChannel::BooksL2Tbt),
+            "books50-l2-tbt" => Some(BookChannel::Books50L2Tbt),
+            _ => None,
+        }
+    }
+
+    /// Levels per side OKX maintains on this channel.
+    pub fn depth(&self) -> usize {
+        match self {
+            BookChannel::Books | BookChannel::BooksL2Tbt => 400,
+            BookChannel::Books50L2Tbt => 50,
+            BookChannel::Books5 => 5,
+            BookChannel::BboTbt => 1,
+        }
+    }
+
+    /// Every push replaces the whole book; there is no `action` and no update stream.
+    pub fn is_snapshot_only(&self) -> bool {
+        matches!(self, BookChannel::Books5 | BookChannel::BboTbt)
+    }
+
+    pub fn requires_login(&self) -> bool {
+        matches!(self, BookChannel::BooksL2Tbt | BookChannel::Books50L2Tbt)
+    }
+}
+
+impl fmt::Display for BookChannel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug)]
+pub struct OrderBook {
+    pub bids: BTreeMap<Ticks, Lots>, // ascending, best bid is last
+    pub asks: BTreeMap<Ticks, Lots>, // ascending, best ask is first
+    pub precision: Precision,
+    /// Each level's price and size exactly as OKX sent them, which is what its checksum is computed over.
+    wire_bids: HashMap<Ticks, (String, String)>,
+    wire_asks: HashMap<Ticks, (String, String)>,
+    /// No instrument metadata: `precision` is the finest grid seen in any snapshot so far.
+    inferred: bool,
+    last_seq_id: Option<i64>,
+    valid: bool,
+}
+
+impl OrderBook {
+    /// Book for an instrument without metadata; its grid is taken from the
+    /// decimals of each snapshot, and data off that grid forces a resync.
+    pub fn new() -> Self {
+        let unit = Step::decimal(0);
+        Self { inferred: true, ..Self::with_precision(Precision { tick: unit, lot: unit }) }
+    }
+
+    pub fn with_precision(precision: Precision) -> Self {
+        Self {
+            bids: BTreeMap::new(),
+            asks: BTreeMap::new(),
+            precision,
+            wire_bids: HashMap::new(),
+            wire_asks: HashMap::new(),
+            inferred: false,
+            last_seq_id: None,
+            valid: false,
+        }
+    }
+
+    /// Book on the instrument's own `tickSz`/`lotSz` grid.
+    pub fn for_instrument(spec: &InstrumentSpec) -> Self {
+        Self::with_precision(spec.precision)
+    }
+
+    /// True once a snapshot has been applied and nothing has invalidated it since.
+    pub fn is_valid(&self) -> bool {
+        self.valid
+    }
+
+    /// Check that an update chains onto the last applied one (`prevSeqId == seqId`).
+    /// A `seqId` lower than `prevSeqId` is OKX resetting its counter, which is fine
+    /// as long as `prevSeqId` still matches.
+    pub fn check_sequence(&self, data: &BookData) -> Result<(), SequenceError> {
+        let (Some(seq_id), Some(prev_seq_id)) = (data.seqId, data.prevSeqId) else {
+            // channels without sequence numbers can't be checked
+            return Ok(());
+        };
+        let Some(last) = self.last_seq_id else {
+            return Err(SequenceError::NoSnapshot { prev_seq_id });
+        };
+
+        if prev_seq_id == last {
+            Ok(())
+        } else if prev_seq_id > last {
+            Err(SequenceError::Gap { last, prev_seq_id })
+        } else if seq_id == last {
+            Err(SequenceError::Duplicate { seq_id })
+        } else {
+            Err(SequenceError::OutOfOrder { last, prev_seq_id })
+        }
+    }
+
+    /// Replace the book with a snapshot. Malformed levels reject the whole
+    /// snapshot before anything is touched; a crossed book or checksum
+    /// mismatch is reported after applying, and the caller should resync.
+    pub fn apply_snapshot(&mut self, data: &BookData) -> Result<(), BookError> {
+        if self.inferred {
+            // a snapshot replaces every level, so the grid can be refined here
+            let levels = || data.bids.iter().chain(&data.asks);
+            let finest = |current: Step, field: usize| {
+                levels().filter_map(|level| Step::resolution(&level[field])).fold(current, Step::finer)
+            };
+            self.precision = Precision { tick: finest(self.precision.tick, 0), lot: finest(self.precision.lot, 1) };
+        }
+        let bids = self.parse_levels(BookSide::Bid, &data.bids)?;
+        let asks = self.parse_levels(BookSide::Ask, &data.asks)?;
+
+        self.bids.clear();
+        self.asks.clear();
+        self.wire_bids.clear();
+        self.wire_asks.clear();
+        for (level, wire) in bids.into_iter().zip(&data.bids) {
+            Self::set_level(&mut self.bids, &mut self.wire_bids, level, wire);
+        }
+        for (level, wire) in asks.into_iter().zip(&data.asks) {
+            Self::set_level(&mut self.asks, &mut self.wire_asks, level, wire);
+        }
+        self.last_seq_id = data.seqId;
+        self.valid = true;
+
+        self.check_integrity(data)
+    }
+
+    /// Apply an incremental update (size 0 deletes a level). Sequence and
+    /// parse errors are raised before anything is touched; a crossed book or
+    /// checksum mismatch is reported after applying, and the caller should resync.
+    pub fn apply_update(&mut self, update: &BookData) -> Result<(), BookError> {
+        self.check_sequence(update)?;
+        let bids = self.parse_levels(BookSide::Bid, &update.bids)?;
+        let asks = self.parse_levels(BookSide::Ask, &update.asks)?;
+
+        if update.seqId.is_some() {
+            self.last_seq_id = update.seqId;
+        }
+        for (level, wire) in bids.into_iter().zip(&update.bids) {
+            Self::set_level(&mut self.bids, &mut self.wire_bids, level, wire);
+        }
+        for (level, wire) in asks.into_iter().zip(&update.asks) {
+            Self::set_level(&mut self.asks, &mut self.wire_asks, level, wire);
+        }
+
+        self.check_integrity(update)
+    }
+
+    fn set_level(
+        levels: &mut BTreeMap<Ticks, Lots>,
+        wire: &mut HashMap<Ticks, (String, String)>,
+        (price, qty): (Ticks, Lots),
+        [p, s, ..]: &[String; 4],
+    ) {
+        if qty == 0 {
+            levels.remove(&price);
+            wire.remove(&price);
+        } else {
+            levels.insert(price, qty);
+            wire.insert(price, (p.clone(), s.clone()));
+        }
+    }
+
+    fn check_integrity(&self, data: &BookData) -> Result<(), BookError> {
+        if let (Some(&bid), Some(&ask)) = (self.bids.keys().next_back(), self.asks.keys().next()) {
+            if bid >= ask {
+                return Err(BookError::Crossed {
+                    bid: self.precision.tick.to_f64(bid),
+                    ask: self.precision.tick.to_f64(ask),
+                });
+            }
+        }
+        if let Some(expected) = data.checksum {
+            self.verify_checksum(expected)?;
+        }
+        Ok(())
+    }
+
+    /// OKX `books` checksum: CRC32 of the top 25 levels interleaved as
+    /// `bid:bidSz:ask:askSz:...`, read back as a signed 32-bit integer.
+    /// When one side runs out of levels the other side keeps going alone.
+    /// Levels are written as OKX sent them, since `1.50` and `1.5` hash differently.
+    pub fn checksum(&self) -> i32 {
+        let mut bids = self.bids.iter().rev().take(CHECKSUM_DEPTH);
+        let mut asks = self.asks.iter().take(CHECKSUM_DEPTH);
+        let mut parts = Vec::with_capacity(CHECKSUM_DEPTH * 4);
+
+        loop {
+            let bid = bids.next();
+            let ask = asks.next();
+            if bid.is_none() && ask.is_none() {
+                break;
+            }
+            let bid = bid.map(|level| (level, self.wire_bids.get(level.0)));
+            let ask = ask.map(|level| (level, self.wire_asks.get(level.0)));
+            for ((p, s), wire) in bid.into_iter().chain(ask) {
+                match wire {
+                    Some((p, s)) => {
+                        parts.push(p.clone());
+                        parts.push(s.clone());
+                    }
+                    None => {
+                        parts.push(self.precision.tick.format(*p));
+                        parts.push(self.precision.lot.format(*s));
+                    }
+                }
+            }
+        }
+
+        crc32fast::hash(parts.join(":").as_bytes()) as i32
+    }
+
+    /// Compare the book against the checksum OKX sent with the last push.
+    pub fn verify_checksum(&self, expected: i64) -> Result<(), ChecksumMismatch> {
+        let expected = expected as i32;
+        let computed = self.checksum();
+        if computed == expected {
+            Ok(())
+        } else {
+            Err(ChecksumMismatch { expected, computed })
+        }
+    }
+
+    /// Drop every level and mark the book unusable until the next snapshot,
+    /// e.g. before resubscribing after a checksum failure or sequence gap.
+    pub fn invalidate(&mut self) {
+        self.bids.clear();
+        self.asks.clear();
+        self.wire_bids.clear();
+        self.wire_asks.clear();
+        self.last_seq_id = None;
+        self.valid = false;
+    }
+
+    /// Keep only the best `depth` levels per side, so levels that drift out of
+    /// a channel's window don't linger behind stale sizes.
+    pub fn truncate(&mut self, depth: usize) {
+        while self.bids.len() > depth {
+            if let Some((price, _)) = self.bids.pop_first() {
+                self.wire_bids.remove(&price);
+            }
+        }
+        while self.asks.len() > depth {
+            if let Some((price, _)) = self.asks.pop_last() {
+                self.wire_asks.remove(&price);
+            }
+        }
+    }
+
+    fn parse_levels(&self, side: BookSide, levels: &[[String; 4]]) -> Result<Vec<(Ticks, Lots)>, BookError> {
+        levels
+            .iter()
+            .map(|[price, size, ..]| {
+                let parse_err = |field, value: &String| BookError::Parse { side, field, value: value.clone() };
+                let steps = |step: Step, s: &str| if self.inferred { step.exact_steps(s) } else { step.steps(s) };
+                let p = steps(self.precision.tick, price).ok_or_else(|| parse_err("price", price))?;
+                let s = steps(self.precision.lot, size).ok_or_else(|| parse_err("size", size))?;
+                // a real level smaller than half a lot must not read as a deletion
+                if s == 0 && size.bytes().any(|b| (b'1'..=b'9').contains(&b)) {
+                    return Err(parse_err("size", size));
+                }
+                if s < 0 {
+                    return Err(BookError::NegativeSize { side, price: price.clone(), size: size.clone() });
+                }
+                Ok((p, s))
+            })
+            .collect()
+    }
+
+    /// Bids best first, as display `(price, size)`.
+    pub fn bid_levels(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.bids.iter().rev().map(|(p, s)| self.to_display(*p, *s))
+    }
+
+    /// Asks best first, as display `(price, size)`.
+    pub fn ask_levels(&self) -> impl Iterator<Item = (f64, f64)> + '_ {
+        self.asks.iter().map(|(p, s)| self.to_display(*p, *s))
+    }
+
+    fn to_display(&self, price: Ticks, size: Lots) -> (f64, f64) {
+        (self.precision.tick.to_f64(price), self.precision.lot.to_f64(size))
+    }
+
+    pub fn best_bid(&self) -> Option<(f64, f64)> {
+        self.bid_levels().next()
+    }
+
+    pub fn best_ask(&self) -> Option<(f64, f64)> {
+        self.ask_levels().next()
+    }
+
+    pub fn mid_price(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some((bid + ask) / 2.0),
+            _ => None,
+        }
+    }
+
+    pub fn spread(&self) -> Option<f64> {
+        match (self.best_bid(), self.best_ask()) {
+            (Some((bid, _)), Some((ask, _))) => Some(ask - bid),
+            _ => None,
+        }
+    }
+
+    /// Display size resting at `price` on the bid (`Buy`) or ask (`Sell`) side; 0 if the level is empty.
+    pub fn size_at(&self, side: Side, price: f64) -> f64 {
+        let levels = match side {
+            Side::Buy => &self.bids,
+            Side::Sell => &self.asks,
+        };
+        let lots = levels.get(&self.precision.tick.steps_f64(price)).copied().unwrap_or(0);
+        self.precision.lot.to_f64(lots)
+    }
+}
+
+/// `[price, size, "0", "1"]` levels as OKX pushes them.
+#[cfg(test)]
+pub(crate) fn levels(levels: &[(&str, &str)]) -> Vec<[String; 4]> {
+    levels.iter().map(|(p, s)| [p.to_string(), s.to_string(), "0".to_string(), "1".to_string()]).collect()
+}
+
+/// A push without sequence numbers or checksum.
+#[cfg(test)]
+pub(crate) fn book_data(bids: &[(&str, &str)], asks: &[(&str, &str)]) -> BookData {
+    BookData { asks: levels(asks), bids: levels(bids), ts: "0".to_string(), checksum: None, seqId: None, prevSeqId: None }
+}
+
+/// A valid book on the `tick`/`lot` grid, built from one snapshot.
+#[cfg(test)]
+pub(crate) fn book(tick: &str, lot: &str, bids: &[(&str, &str)], asks: &[(&str, &str)]) -> OrderBook {
+    let mut book = OrderBook::with_precision(Precision { tick: Step::parse(tick).unwrap(), lot: Step::parse(lot).unwrap() });
+    book.apply_snapshot(&book_data(bids, asks)).unwrap();
+    book
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data(bids: &[(&str, &str)], asks: &[(&str, &str)], seq: Option<(i64, i64)>, checksum: Option<i64>) -> BookData {
+        BookData {
+            checksum,
+            seqId: seq.map(|(seq_id, _)| seq_id),
+            prevSeqId: seq.map(|(_, prev)| prev),
+            ..book_data(bids, asks)
+        }
+    }
+
+    fn empty() -> OrderBook {
+        OrderBook::with_precision(Precision { tick: Step::parse("0.1").unwrap(), lot: Step::parse("1").unwrap() })
+    }
+
+    #[test]
+    fn checksum_matches_okx_example() {
+        // OKX's example: "3366.1:7:3366.8:9:3366:6:3368:8"
+        let mut book = empty();
+        let snapshot = data(&[("3366.1", "7"), ("3366", "6")], &[("3366.8", "9"), ("3368", "8")], None, Some(-1881014294));
+        assert_eq!(book.apply_snapshot(&snapshot), Ok(()));
+        assert_eq!(book.checksum(), -1881014294);
+    }
+
+    #[test]
+    fn checksum_continues_with_the_longer_side() {
+        // "3366.1:7:3366.8:9:3366:6"
+        let mut bids_longer = empty();
+        bids_longer.apply_snapshot(&data(&[("3366.1", "7"), ("3366", "6")], &[("3366.8", "9")], None, None)).unwrap();
+        assert_eq!(bids_longer.checksum(), 1164732920);
+
+        // "3366.1:7:3366.8:9:3368:8"
+        let mut asks_longer = empty();
+        asks_longer.apply_snapshot(&data(&[("3366.1", "7")], &[("3366.8", "9"), ("3368", "8")], None, None)).unwrap();
+        assert_eq!(asks_longer.checksum(), -1471518219);
+    }
+
+    #[test]
+    fn checksum_hashes_levels_as_sent() {
+        // "3366.10:7.0:3366.8:9:3366:6:3368:8", not the canonical "3366.1:7:..."
+        let mut book = empty();
+        let snapshot = data(&[("3366.10", "7.0"), ("3366", "6")], &[("3366.8", "9"), ("3368", "8")], Some((10, -1)), Some(-170346681));
+        assert_eq!(book.apply_snapshot(&snapshot), Ok(()));
+
+        // "3366.1:5:3366.8:9:3366:6:3368:8"
+        let update = data(&[("3366.1", "5")], &[], Some((11, 10)), Some(1457140709));
+        assert_eq!(book.apply_update(&update), Ok(()));
+    }
+
+    #[test]
+    fn updates_with_a_bad_checksum_or_a_sequence_gap_are_rejected() {
+        let mut book = empty();
+        book.apply_snapshot(&data(&[("3366.1", "7")], &[("3366.8", "9")], Some((10, -1)), None)).unwrap();
+
+        let gap = data(&[("3366", "6")], &[], Some((13, 12)), None);
+        assert_eq!(book.apply_update(&gap), Err(BookError::Sequence(SequenceError::Gap { last: 10, prev_seq_id: 12 })));
+        assert_eq!(book.bid_levels().count(), 1);
+        let duplicate = data(&[], &[], Some((10, 9)), None);
+        assert!(!book.apply_update(&duplicate).unwrap_err().needs_resync());
+
+        let bad = data(&[("3366", "6")], &[], Some((11, 10)), Some(1));
+        let Err(BookError::Checksum(mismatch)) = book.apply_update(&bad) else {
+            panic!("checksum mismatch not reported");
+        };
+        assert_eq!(mismatch, ChecksumMismatch { expected: 1, computed: 1164732920 });
+    }
+}
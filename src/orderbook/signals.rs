@@ -0,0 +1,226 @@
+//! Book-derived fair-value signals: imbalances and microprices that strategies
+//! can centre quotes on instead of the raw mid.
+
+use std::collections::VecDeque;
+
+use super::OrderBook;
+
+impl OrderBook {
+    /// Share of resting size on the bid over the best `levels` levels per side,
+    /// in `[0, 1]` (0.5 is balanced). `None` if both sides are empty.
+    pub fn imbalance(&self, levels: usize) -> Option<f64> {
+        let bid_vol: f64 = self.bid_levels().take(levels).map(|(_, s)| s).sum();
+        let ask_vol: f64 = self.ask_levels().take(levels).map(|(_, s)| s).sum();
+        ratio(bid_vol, ask_vol)
+    }
+
+    /// Like [`imbalance`](Self::imbalance), but each level's size is weighted by
+    /// `exp(-distance_from_mid_bps / decay_bps)` so liquidity far from the touch counts less.
+    pub fn weighted_imbalance(&self, levels: usize, decay_bps: f64) -> Option<f64> {
+        let mid = self.mid_price()?;
+        let weighted = |(price, size): (f64, f64)| {
+            let dist_bps = (price - mid).abs() / mid * 10_000.0;
+            size * (-dist_bps / decay_bps).exp()
+        };
+        let bid_vol: f64 = self.bid_levels().take(levels).map(weighted).sum();
+        let ask_vol: f64 = self.ask_levels().take(levels).map(weighted).sum();
+        ratio(bid_vol, ask_vol)
+    }
+
+    /// Size-weighted microprice: the touch prices weighted by the opposite side's
+    /// size, so a heavy bid pulls fair value towards the ask.
+    pub fn microprice(&self) -> Option<f64> {
+        let i = self.imbalance(1)?;
+        let ((bid, _), (ask, _)) = (self.best_bid()?, self.best_ask()?);
+        Some(ask * i + bid * (1.0 - i))
+    }
+
+    /// Microprice weighted by the [`weighted_imbalance`](Self::weighted_imbalance)
+    /// of the best `levels` levels instead of the touch sizes alone.
+    pub fn weighted_microprice(&self, levels: usize, decay_bps: f64) -> Option<f64> {
+        let i = self.weighted_imbalance(levels, decay_bps)?;
+        let ((bid, _), (ask, _)) = (self.best_bid()?, self.best_ask()?);
+        Some(ask * i + bid * (1.0 - i))
+    }
+
+    /// Spread in whole ticks, if both sides are present.
+    pub fn spread_ticks(&self) -> Option<i64> {
+        Some(self.asks.keys().next()? - self.bids.keys().next_back()?)
+    }
+}
+
+fn ratio(bid_vol: f64, ask_vol: f64) -> Option<f64> {
+    if bid_vol + ask_vol == 0.0 {
+        None
+    } else {
+        Some(bid_vol / (bid_vol + ask_vol))
+    }
+}
+
+/// Stoikov-style microprice (Stoikov, 2018): mid plus the expected mid move
+/// conditional on the current (spread, top-of-book imbalance) state, learned
+/// online from the book itself.
+///
+/// This is the first-order term `g1(I, S) = E[M(τ) - M(t) | I, S]`, where `τ` is
+/// the next mid change. Every state observed before a mid change is credited
+/// with that change, and the data is symmetrised (`I → 1 - I`, move → -move)
+/// as in the paper, so the estimate is unbiased by trend. Only the last
+/// `window` states before a change are credited, so a long quiet spell
+/// neither grows memory nor outweighs everything learned before it.
+#[derive(Debug, Clone)]
+pub struct StoikovMicroprice {
+    imbalance_buckets: usize,
+    max_spread_ticks: usize,
+    window: usize,
+    sums: Vec<f64>,
+    counts: Vec<u64>,
+    last_mid: Option<f64>,
+    since_change: VecDeque<usize>,
+}
+
+impl StoikovMicroprice {
+    /// `imbalance_buckets` splits `[0, 1]` evenly; spreads wider than
+    /// `max_spread_ticks` share the last bucket; `window` is how many states
+    /// before a mid change are credited with it.
+    pub fn new(imbalance_buckets: usize, max_spread_ticks: usize, window: usize) -> Self {
+        let imbalance_buckets = imbalance_buckets.max(1);
+        let max_spread_ticks = max_spread_ticks.max(1);
+        let states = imbalance_buckets * max_spread_ticks;
+        Self {
+            imbalance_buckets,
+            max_spread_ticks,
+            window: window.max(1),
+            sums: vec![0.0; states],
+            counts: vec![0; states],
+            last_mid: None,
+            since_change: VecDeque::new(),
+        }
+    }
+
+    fn state(&self, book: &OrderBook) -> Option<(usize, usize)> {
+        let spread = book.spread_ticks()?;
+        if spread <= 0 {
+            return None;
+        }
+        let i = book.imbalance(1)?;
+        let bucket = ((i * self.imbalance_buckets as f64) as usize).min(self.imbalance_buckets - 1);
+        let spread = (spread as usize).min(self.max_spread_ticks) - 1;
+        Some((spread, bucket))
+    }
+
+    fn index(&self, spread: usize, bucket: usize) -> usize {
+        spread * self.imbalance_buckets + bucket
+    }
+
+    /// Feed every book update through here to train the estimator.
+    pub fn observe(&mut self, book: &OrderBook) {
+        let (Some(mid), Some((spread, bucket))) = (book.mid_price(), self.state(book)) else {
+            return;
+        };
+
+        if let Some(last) = self.last_mid {
+            if mid != last {
+                let change = mid - last;
+                for idx in std::mem::take(&mut self.since_change) {
+                    let (spread, bucket) = (idx / self.imbalance_buckets, idx % self.imbalance_buckets);
+                    let mirror = self.index(spread, self.imbalance_buckets - 1 - bucket);
+                    self.sums[idx] += change;
+                    self.counts[idx] += 1;
+                    self.sums[mirror] -= change;
+                    self.counts[mirror] += 1;
+                }
+            }
+        }
+        self.last_mid = Some(mid);
+        if self.since_change.len() == self.window {
+            self.since_change.pop_front();
+        }
+        self.since_change.push_back(self.index(spread, bucket));
+    }
+
+    /// Expected mid move from the current state; `None` until that state has been seen.
+    pub fn adjustment(&self, book: &OrderBook) -> Option<f64> {
+        let (spread, bucket) = self.state(book)?;
+        let idx = self.index(spread, bucket);
+        match self.counts[idx] {
+            0 => None,
+            n => Some(self.sums[idx] / n as f64),
+        }
+    }
+
+    /// Mid plus the learned adjustment, falling back to the size-weighted
+    /// microprice while the current state has no history.
+    pub fn microprice(&self, book: &OrderBook) -> Option<f64> {
+        match self.adjustment(book) {
+            Some(g) => Some(book.mid_price()? + g),
+            None => book.microprice(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::orderbook::book;
+
+    #[test]
+    fn imbalance_counts_the_best_levels() {
+        let book = book("1", "1", &[("99", "3"), ("98", "1"), ("90", "100")], &[("101", "1"), ("102", "3")]);
+        assert_eq!(book.imbalance(1), Some(0.75));
+        assert_eq!(book.imbalance(2), Some(0.5));
+        assert_eq!(book.imbalance(3), Some(104.0 / 108.0));
+        assert_eq!(OrderBook::with_precision(book.precision).imbalance(5), None);
+    }
+
+    #[test]
+    fn weighted_imbalance_discounts_levels_by_distance() {
+        // mid 100: bids 100 and 300 bps away, asks 100 and 200 bps
+        let spread_out = book("1", "1", &[("99", "1"), ("97", "1")], &[("101", "1"), ("102", "1")]);
+        let bid = (-1.0f64).exp() + (-3.0f64).exp();
+        let ask = (-1.0f64).exp() + (-2.0f64).exp();
+        let i = spread_out.weighted_imbalance(2, 100.0).unwrap();
+        assert!((i - bid / (bid + ask)).abs() < 1e-12);
+        assert_eq!(spread_out.imbalance(2), Some(0.5));
+        assert!(i < 0.5);
+
+        // a huge decay weighs every level the same
+        let deep = book("1", "1", &[("99", "3"), ("98", "1")], &[("101", "1"), ("102", "3")]);
+        assert!((deep.weighted_imbalance(2, 1e12).unwrap() - deep.imbalance(2).unwrap()).abs() < 1e-9);
+    }
+
+    #[test]
+    fn microprice_leans_towards_the_thin_side() {
+        let heavy_bid = book("1", "1", &[("99", "3")], &[("101", "1")]);
+        assert_eq!(heavy_bid.microprice(), Some(100.5));
+        assert_eq!(book("1", "1", &[("99", "1")], &[("101", "1")]).microprice(), Some(100.0));
+        assert_eq!(book("1", "1", &[("99", "1")], &[]).microprice(), None);
+
+        let deep = book("1", "1", &[("99", "3"), ("98", "1")], &[("101", "1"), ("102", "3")]);
+        assert_eq!(deep.weighted_microprice(1, 1e12), deep.microprice());
+        assert!((deep.weighted_microprice(2, 1e12).unwrap() - 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stoikov_learns_the_move_after_a_state_and_forgets_past_its_window() {
+        let heavy_bid = book("1", "1", &[("99", "3")], &[("101", "1")]);
+        let moved_up = book("1", "1", &[("100", "1")], &[("102", "1")]);
+
+        let mut model = StoikovMicroprice::new(2, 2, 1);
+        assert_eq!(model.microprice(&heavy_bid), Some(100.5));
+        model.observe(&heavy_bid);
+        model.observe(&moved_up);
+        assert_eq!(model.adjustment(&heavy_bid), Some(1.0));
+        assert_eq!(model.microprice(&heavy_bid), Some(101.0));
+        // the mirrored state learned the opposite move
+        let heavy_ask = book("1", "1", &[("99", "1")], &[("101", "3")]);
+        assert_eq!(model.adjustment(&heavy_ask), Some(-1.0));
+
+        // with a window of one, only the last state before a change is credited
+        let mut model = StoikovMicroprice::new(2, 2, 1);
+        model.observe(&heavy_ask);
+        model.observe(&heavy_bid);
+        model.observe(&moved_up);
+        assert_eq!(model.adjustment(&heavy_bid), Some(1.0));
+        assert_eq!(model.since_change.len(), 1);
+    }
+}
@@ -0,0 +1,581 @@
+//! Paper trading: an in-process venue that rests orders against the live book
+//! and public trades instead of sending them to OKX. It answers with the same
+//! acks and `orders`-style updates OKX would, so fills reach strategies through
+//! `on_order_update`/`on_order_filled` exactly as they would live.
+//!
+//! Fills respect queue priority: an order joins the back of its level, moves
+//! up as prints trade the level and as others cancel (see `CancelModel`), and
+//! only fills once the size ahead of it is gone. Trades and books come on
+//! separate channels in either order, so a fall of the level is only put down
+//! to cancels once the prints up to that book's `ts` have had `PRINT_LAG_MS`
+//! to arrive. Fills are charged maker fees if `with_fees` set them for the
+//! instrument.
+
+use std::collections::HashMap;
+
+use crate::account::{Execution, OrderState, OrderUpdate};
+use crate::clock::Clock;
+use crate::execution::{AmendOrder, CancelOrder, NewOrder, OrdType, OrderAck, OrderRef, Route};
+use crate::fees::FeeRates;
+use crate::market::Trade;
+use crate::orderbook::OrderBook;
+use crate::strategy::Side;
+
+/// OKX error codes the paper venue answers with.
+mod codes {
+    pub const BAD_PARAMETER: &str = "51000";
+    pub const DUPLICATE_CL_ORD_ID: &str = "51016";
+    pub const CANCEL_FAILED: &str = "51400";
+    pub const AMEND_FAILED: &str = "51503";
+}
+
+/// How long after a book the prints it already reflects may still arrive.
+const PRINT_LAG_MS: u64 = 100;
+
+/// Who cancelled when the size at our level shrinks by more than has traded there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CancelModel {
+    /// Every cancel was ahead of us.
+    Optimistic,
+    /// Every cancel was behind us; only trades move us up.
+    Pessimistic,
+    /// Cancels are spread evenly over the level, so we move up by the share ahead of us.
+    #[default]
+    Proportional,
+}
+
+impl CancelModel {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "optimistic" => Some(CancelModel::Optimistic),
+            "pessimistic" => Some(CancelModel::Pessimistic),
+            "proportional" => Some(CancelModel::Proportional),
+            _ => None,
+        }
+    }
+}
+
+/// One resting paper order.
+#[derive(Debug, Clone)]
+struct PaperOrder {
+    ord_id: String,
+    cl_ord_id: String,
+    side: Side,
+    price: f64,
+    size: f64,
+    filled: f64,
+    /// Notional filled so far, for the average price.
+    filled_value: f64,
+    /// Estimated size resting ahead of us at our price.
+    queue_ahead: f64,
+    /// Size at our level in the last book seen.
+    level_size: f64,
+    /// Falls of our level not yet put down to prints or cancels:
+    /// (book ts, fall, level size before it).
+    falls: Vec<(u64, f64, f64)>,
+    /// Prints at our price not yet matched to a fall: (trade ts, size).
+    prints: Vec<(u64, f64)>,
+}
+
+impl PaperOrder {
+    /// Go to the back of the queue at the current price.
+    fn requeue(&mut self, book: Option<&OrderBook>) {
+        let level = book.map_or(0.0, |b| b.size_at(self.side, self.price));
+        self.queue_ahead = level;
+        self.level_size = level;
+        self.falls.clear();
+        self.prints.clear();
+    }
+
+    /// Take in the size at our level in a book pushed at `ts`. Falls seen by
+    /// books at least `PRINT_LAG_MS` older are matched against the prints up to
+    /// their ts, which already moved us up; `model` decides how much of the
+    /// rest, the cancels, was ahead of us.
+    fn on_level(&mut self, level: f64, ts: u64, model: CancelModel) {
+        while let Some(&(at, fall, before)) = self.falls.first().filter(|(at, ..)| at + PRINT_LAG_MS <= ts) {
+            self.falls.remove(0);
+            let mut traded = 0.0;
+            self.prints.retain(|&(t, size)| {
+                if t > at {
+                    return true;
+                }
+                traded += size;
+                false
+            });
+            let traded = f64::min(traded, fall);
+            let cancelled = fall - traded;
+            let after_trades = before - traded;
+            let moved_up = match model {
+                CancelModel::Optimistic => cancelled,
+                CancelModel::Pessimistic => 0.0,
+                CancelModel::Proportional if after_trades > 0.0 => cancelled * self.queue_ahead / after_trades,
+                CancelModel::Proportional => 0.0,
+            };
+            self.queue_ahead = (self.queue_ahead - moved_up).max(0.0);
+        }
+        // old prints later than every pending fall were in books that didn't shrink
+        let last_fall = self.falls.last().map(|&(at, ..)| at);
+        self.prints.retain(|&(t, _)| t + PRINT_LAG_MS > ts || last_fall.is_some_and(|at| t <= at));
+
+        if level < self.level_size {
+            self.falls.push((ts, self.level_size - level, self.level_size));
+        }
+        // prints of a pending fall have yet to move us up, so the level can be short of our queue till then
+        if self.falls.is_empty() {
+            self.queue_ahead = self.queue_ahead.min(level);
+        }
+        self.level_size = level;
+    }
+
+    fn remaining(&self) -> f64 {
+        (self.size - self.filled).max(0.0)
+    }
+
+    fn matches(&self, order: &OrderRef) -> bool {
+        match order {
+            OrderRef::OrdId(id) => self.ord_id == *id,
+            OrderRef::ClOrdId(id) => self.cl_ord_id == *id,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PaperVenue {
+    /// Resting orders per instrument.
+    orders: HashMap<String, Vec<PaperOrder>>,
+    /// `orders`-channel updates not yet collected by `drain_updates`.
+    updates: Vec<OrderUpdate>,
+    next_ord_id: u64,
+    next_trade_id: u64,
+    cancel_model: CancelModel,
+    /// Fee rates and contract size per instrument; unlisted instruments trade free.
+    fees: HashMap<String, (FeeRates, f64)>,
+    /// Stamps updates that aren't fills; fills carry the time of the data that filled them.
+    clock: Clock,
+    /// Newest public trade id seen per instrument; prints up to it have been counted.
+    last_trade_ids: HashMap<String, u64>,
+}
+
+impl PaperVenue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_clock(mut self, clock: Clock) -> Self {
+        self.clock = clock;
+        self
+    }
+
+    pub fn with_cancel_model(mut self, model: CancelModel) -> Self {
+        self.cancel_model = model;
+        self
+    }
+
+    /// Charge `rates` on fills of `inst_id`, whose size is in units of `contract_size`.
+    pub fn with_fees(mut self, inst_id: impl Into<String>, rates: FeeRates, contract_size: f64) -> Self {
+        self.fees.insert(inst_id.into(), (rates, contract_size));
+        self
+    }
+
+    /// Rest a post-only limit order. Like OKX, one that would take liquidity
+    /// against `book` is accepted and then immediately cancelled. Other order
+    /// types aren't simulated and are rejected.
+    pub fn place(&mut self, order: &NewOrder, book: Option<&OrderBook>) -> OrderAck {
+        if order.ord_type != OrdType::PostOnly {
+            return ack(&order.cl_ord_id, "", codes::BAD_PARAMETER, "paper venue only takes post-only orders");
+        }
+        let (Ok(price), Ok(size)) = (order.px.parse::<f64>(), order.sz.parse::<f64>()) else {
+            return ack(&order.cl_ord_id, "", codes::BAD_PARAMETER, "invalid px or sz");
+        };
+        if price <= 0.0 || size <= 0.0 {
+            return ack(&order.cl_ord_id, "", codes::BAD_PARAMETER, "px and sz must be positive");
+        }
+        let resting = self.orders.entry(order.inst_id.clone()).or_default();
+        if resting.iter().any(|o| o.cl_ord_id == order.cl_ord_id) {
+            return ack(&order.cl_ord_id, "", codes::DUPLICATE_CL_ORD_ID, "duplicated clOrdId");
+        }
+
+        self.next_ord_id += 1;
+        let mut paper = PaperOrder {
+            ord_id: self.next_ord_id.to_string(),
+            cl_ord_id: order.cl_ord_id.clone(),
+            side: order.side,
+            price,
+            size,
+            filled: 0.0,
+            filled_value: 0.0,
+            queue_ahead: 0.0,
+            level_size: 0.0,
+            falls: Vec::new(),
+            prints: Vec::new(),
+        };
+        paper.requeue(book);
+        let ack = accept(&paper.cl_ord_id, &paper.ord_id);
+        let now = self.clock.now_ms();
+        if crosses(order.side, price, book) {
+            self.updates.push(update(&order.inst_id, &paper, OrderState::Canceled, now, None));
+        } else {
+            self.updates.push(update(&order.inst_id, &paper, OrderState::Live, now, None));
+            resting.push(paper);
+        }
+        ack
+    }
+
+    /// Change price and/or total size. As on OKX, a new price or a larger size
+    /// loses queue priority, a size at or below what has filled completes the
+    /// order, and a price that would take liquidity cancels it (it's post-only).
+    /// The ack echoes the amend's `reqId`.
+    pub fn amend(&mut self, amend: &AmendOrder, book: Option<&OrderBook>) -> OrderAck {
+        let ack = self.apply_amend(amend, book);
+        OrderAck { req_id: amend.req_id.clone().unwrap_or_default(), ..ack }
+    }
+
+    fn apply_amend(&mut self, amend: &AmendOrder, book: Option<&OrderBook>) -> OrderAck {
+        let cl_ord_id = match &amend.order {
+            OrderRef::ClOrdId(id) => id.as_str(),
+            OrderRef::OrdId(_) => "",
+        };
+        let Some(resting) = self.orders.get_mut(&amend.inst_id) else {
+            return ack(cl_ord_id, "", codes::AMEND_FAILED, "order does not exist");
+        };
+        let Some(i) = resting.iter().position(|o| o.matches(&amend.order)) else {
+            return ack(cl_ord_id, "", codes::AMEND_FAILED, "order does not exist");
+        };
+        let price = amend.new_px.as_deref().map(str::parse::<f64>).transpose();
+        let size = amend.new_sz.as_deref().map(str::parse::<f64>).transpose();
+        let (Ok(price), Ok(size)) = (price, size) else {
+            return ack(cl_ord_id, &resting[i].ord_id, codes::BAD_PARAMETER, "invalid newPx or newSz");
+        };
+
+        let order = &mut resting[i];
+        let requeue = price.is_some_and(|p| p != order.price) || size.is_some_and(|s| s > order.size);
+        if let Some(price) = price {
+            order.price = price;
+        }
+        if let Some(size) = size {
+            order.size = size;
+        }
+        if requeue {
+            order.requeue(book);
+        }
+        let ack = accept(&order.cl_ord_id, &order.ord_id);
+        let now = self.clock.now_ms();
+        if order.remaining() <= 0.0 {
+            let order = resting.remove(i);
+            self.updates.push(update(&amend.inst_id, &order, OrderState::Filled, now, None));
+        } else if price.is_some() && crosses(order.side, order.price, book) {
+            let order = resting.remove(i);
+            self.updates.push(update(&amend.inst_id, &order, OrderState::Canceled, now, None));
+        } else {
+            self.updates.push(update(&amend.inst_id, order, state_of(order), now, None));
+        }
+        ack
+    }
+
+    pub fn cancel(&mut self, cancel: &CancelOrder) -> OrderAck {
+        let resting = self.orders.entry(cancel.inst_id.clone()).or_default();
+        let Some(i) = resting.iter().position(|o| o.matches(&cancel.order)) else {
+            let cl_ord_id = match &cancel.order {
+                OrderRef::ClOrdId(id) => id.as_str(),
+                OrderRef::OrdId(_) => "",
+            };
+            return ack(cl_ord_id, "", codes::CANCEL_FAILED, "order does not exist");
+        };
+        let order = resting.remove(i);
+        self.updates.push(update(&cancel.inst_id, &order, OrderState::Canceled, self.clock.now_ms(), None));
+        accept(&order.cl_ord_id, &order.ord_id)
+    }
+
+    /// Move orders up their queue as others cancel at their level, and fill
+    /// every order the book has moved through: a bid at or above the best ask
+    /// (or an ask at or below the best bid) can only be there if our level
+    /// traded away. `ts` is the book push's time in Unix ms.
+    pub fn on_book(&mut self, inst_id: &str, book: &OrderBook, ts: u64) {
+        let (best_bid, best_ask) = (book.best_bid().map(|(p, _)| p), book.best_ask().map(|(p, _)| p));
+        let model = self.cancel_model;
+        self.fill_where(inst_id, ts, |order| {
+            let crossed = match order.side {
+                Side::Buy => best_ask.is_some_and(|ask| ask <= order.price),
+                Side::Sell => best_bid.is_some_and(|bid| bid >= order.price),
+            };
+            if crossed {
+                return order.remaining();
+            }
+            order.on_level(book.size_at(order.side, order.price), ts, model);
+            0.0
+        });
+    }
+
+    /// Fill against a public print. One at our price first trades away the
+    /// queue ahead of us, then fills us with what's left; one through our
+    /// price means the whole level traded, so fills us completely. A print
+    /// seen before (by trade id) is skipped.
+    pub fn on_trade(&mut self, trade: &Trade) {
+        if let Ok(id) = trade.trade_id.parse::<u64>() {
+            let last = self.last_trade_ids.entry(trade.inst_id.clone()).or_default();
+            if id <= *last {
+                return;
+            }
+            *last = id;
+        }
+        let mut left = trade.size;
+        self.fill_where(&trade.inst_id, trade.ts, |order| {
+            let (at, through) = match (order.side, trade.side) {
+                (Side::Buy, Side::Sell) => (trade.price == order.price, trade.price < order.price),
+                (Side::Sell, Side::Buy) => (trade.price == order.price, trade.price > order.price),
+                _ => (false, false),
+            };
+            if through {
+                return order.remaining();
+            }
+            if !at {
+                return 0.0;
+            }
+            // our own orders earlier at this level are ahead too: what they took is gone
+            let past_queue = (left - order.queue_ahead).max(0.0);
+            order.queue_ahead = (order.queue_ahead - trade.size).max(0.0);
+            order.prints.push((trade.ts, trade.size));
+            let size = order.remaining().min(past_queue);
+            left -= size;
+            size
+        });
+    }
+
+    /// Updates since the last call, oldest first, in the shape of OKX's `orders` pushes.
+    pub fn drain_updates(&mut self) -> Vec<OrderUpdate> {
+        std::mem::take(&mut self.updates)
+    }
+
+    /// Fill each resting order of `inst_id` by `fill_size(order)` at its own
+    /// price (paper orders are always the maker). `fill_size` may also update
+    /// the order's queue estimate.
+    fn fill_where(&mut self, inst_id: &str, ts: u64, mut fill_size: impl FnMut(&mut PaperOrder) -> f64) {
+        let Some(resting) = self.orders.get_mut(inst_id) else {
+            return;
+        };
+        let (rates, contract_size) = self.fees.get(inst_id).copied().unwrap_or((FeeRates::default(), 1.0));
+        let mut i = 0;
+        while i < resting.len() {
+            let size = fill_size(&mut resting[i]).min(resting[i].remaining());
+            if size <= 0.0 {
+                i += 1;
+                continue;
+            }
+            self.next_trade_id += 1;
+            let order = &mut resting[i];
+            order.filled += size;
+            order.filled_value += size * order.price;
+            let execution = Execution {
+                trade_id: format!("paper-{}", self.next_trade_id),
+                price: order.price,
+                size,
+                fee: rates.fee(size * order.price * contract_size, true),
+                fee_ccy: String::new(),
+                pnl: 0.0,
+                maker: true,
+            };
+            let state = state_of(order);
+            self.updates.push(update(inst_id, order, state, ts, Some(execution)));
+            if state == OrderState::Filled {
+                resting.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+}
+
+/// Whether a `side` order at `price` would take liquidity from `book`.
+fn crosses(side: Side, price: f64, book: Option<&OrderBook>) -> bool {
+    match (side, book) {
+        (Side::Buy, Some(book)) => book.best_ask().is_some_and(|(ask, _)| price >= ask),
+        (Side::Sell, Some(book)) => book.best_bid().is_some_and(|(bid, _)| price <= bid),
+        (_, None) => false,
+    }
+}
+
+fn state_of(order: &PaperOrder) -> OrderState {
+    match order.filled {
+        f if f <= 0.0 => OrderState::Live,
+        _ if order.remaining() <= 0.0 => OrderState::Filled,
+        _ => OrderState::PartiallyFilled,
+    }
+}
+
+fn update(inst_id: &str, order: &PaperOrder, state: OrderState, ts: u64, execution: Option<Execution>) -> OrderUpdate {
+    OrderUpdate {
+        inst_id: inst_id.to_string(),
+        ord_id: order.ord_id.clone(),
+        cl_ord_id: order.cl_ord_id.clone(),
+        side: order.side,
+        price: order.price,
+        size: order.size,
+        state,
+        filled: order.filled,
+        avg_price: if order.filled > 0.0 { order.filled_value / order.filled } else { 0.0 },
+        ts,
+        execution,
+    }
+}
+
+fn accept(cl_ord_id: &str, ord_id: &str) -> OrderAck {
+    ack(cl_ord_id, ord_id, "0", "")
+}
+
+fn ack(cl_ord_id: &str, ord_id: &str, code: &str, msg: &str) -> OrderAck {
+    OrderAck {
+        cl_ord_id: cl_ord_id.to_string(),
+        ord_id: ord_id.to_string(),
+        req_id: String::new(),
+        code: code.to_string(),
+        msg: msg.to_string(),
+        route: Route::Paper,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::execution::TdMode;
+    use crate::orderbook::book;
+
+    const INST: &str = "BTC-USDT";
+
+    fn order(cl_ord_id: &str, side: Side, px: &str, sz: &str) -> NewOrder {
+        NewOrder {
+            inst_id: INST.to_string(),
+            cl_ord_id: cl_ord_id.to_string(),
+            side,
+            ord_type: OrdType::PostOnly,
+            td_mode: TdMode::Cash,
+            px: px.to_string(),
+            sz: sz.to_string(),
+            reduce_only: false,
+        }
+    }
+
+    fn amend(cl_ord_id: &str, px: Option<&str>, sz: Option<&str>) -> AmendOrder {
+        AmendOrder {
+            inst_id: INST.to_string(),
+            order: OrderRef::ClOrdId(cl_ord_id.to_string()),
+            req_id: None,
+            new_px: px.map(str::to_string),
+            new_sz: sz.map(str::to_string),
+            cancel_on_fail: false,
+        }
+    }
+
+    fn sell(price: f64, size: f64, ts: u64) -> Trade {
+        Trade { inst_id: INST.to_string(), trade_id: ts.to_string(), price, size, side: Side::Sell, ts, count: 1 }
+    }
+
+    fn last_state(paper: &mut PaperVenue) -> OrderState {
+        paper.drain_updates().last().unwrap().state
+    }
+
+    fn queue_ahead(paper: &PaperVenue, cl_ord_id: &str) -> f64 {
+        paper.orders[INST].iter().find(|o| o.cl_ord_id == cl_ord_id).unwrap().queue_ahead
+    }
+
+    fn filled(paper: &mut PaperVenue) -> f64 {
+        paper.drain_updates().iter().filter_map(|u| u.execution.as_ref()).map(|e| e.size).sum()
+    }
+
+    /// Bid of 1 behind 10 at 100, then 10 more join behind it and 4 leave.
+    fn queue_after_cancels(model: CancelModel) -> (f64, f64) {
+        let mut paper = PaperVenue::new().with_cancel_model(model);
+        paper.place(&order("a", Side::Buy, "100", "1"), Some(&book("0.1", "1", &[("100", "10")], &[("101", "5")])));
+        paper.on_book(INST, &book("0.1", "1", &[("100", "20")], &[("101", "5")]), 500);
+        paper.on_book(INST, &book("0.1", "1", &[("100", "16")], &[("101", "5")]), 1000);
+        let pending = queue_ahead(&paper, "a");
+        paper.on_book(INST, &book("0.1", "1", &[("100", "16")], &[("101", "5")]), 1000 + PRINT_LAG_MS);
+        (pending, queue_ahead(&paper, "a"))
+    }
+
+    #[test]
+    fn cancels_move_the_queue_per_model() {
+        assert_eq!(queue_after_cancels(CancelModel::Optimistic), (10.0, 6.0));
+        assert_eq!(queue_after_cancels(CancelModel::Pessimistic), (10.0, 10.0));
+        // 4 of 20 cancelled, half of the level is ahead of us
+        assert_eq!(queue_after_cancels(CancelModel::Proportional), (10.0, 8.0));
+    }
+
+    #[test]
+    fn prints_after_the_book_that_shows_them_are_not_taken_for_cancels() {
+        for trade_first in [true, false] {
+            let mut paper = PaperVenue::new().with_cancel_model(CancelModel::Optimistic);
+            paper.place(&order("a", Side::Buy, "100", "1"), Some(&book("0.1", "1", &[("100", "10")], &[("101", "5")])));
+            if trade_first {
+                paper.on_trade(&sell(100.0, 4.0, 990));
+            }
+            paper.on_book(INST, &book("0.1", "1", &[("100", "6")], &[("101", "5")]), 1000);
+            if !trade_first {
+                paper.on_trade(&sell(100.0, 4.0, 990));
+            }
+            paper.on_book(INST, &book("0.1", "1", &[("100", "6")], &[("101", "5")]), 1000 + PRINT_LAG_MS);
+            assert_eq!(queue_ahead(&paper, "a"), 6.0);
+            assert_eq!(filled(&mut paper), 0.0);
+        }
+    }
+
+    #[test]
+    fn a_print_seen_twice_fills_once() {
+        let mut paper = PaperVenue::new();
+        paper.place(&order("a", Side::Buy, "100", "5"), Some(&book("0.1", "1", &[("100", "2")], &[("101", "5")])));
+        paper.on_trade(&sell(100.0, 4.0, 1000));
+        assert_eq!(filled(&mut paper), 2.0);
+        paper.on_trade(&sell(100.0, 4.0, 1000));
+        assert_eq!(filled(&mut paper), 0.0);
+        paper.on_trade(&sell(100.0, 2.0, 1001));
+        assert_eq!(filled(&mut paper), 2.0);
+    }
+
+    #[test]
+    fn amends_requeue_on_a_new_price_or_a_larger_size() {
+        let mut paper = PaperVenue::new();
+        paper.place(&order("a", Side::Buy, "100", "3"), Some(&book("0.1", "1", &[("100", "10")], &[("101", "5")])));
+        paper.on_trade(&sell(100.0, 4.0, 1000));
+        assert_eq!(queue_ahead(&paper, "a"), 6.0);
+
+        let book = book("0.1", "1", &[("100", "7"), ("99.9", "3")], &[("101", "5")]);
+        paper.amend(&amend("a", None, Some("2")), Some(&book));
+        assert_eq!(queue_ahead(&paper, "a"), 6.0);
+        paper.amend(&amend("a", None, Some("5")), Some(&book));
+        assert_eq!(queue_ahead(&paper, "a"), 7.0);
+        paper.amend(&amend("a", Some("99.9"), None), Some(&book));
+        assert_eq!(queue_ahead(&paper, "a"), 3.0);
+    }
+
+    #[test]
+    fn own_orders_at_one_level_share_each_print() {
+        let mut paper = PaperVenue::new();
+        let book = book("0.1", "1", &[("100", "10")], &[("101", "5")]);
+        paper.place(&order("a", Side::Buy, "100", "1"), Some(&book));
+        paper.place(&order("b", Side::Buy, "100", "1"), Some(&book));
+        paper.drain_updates();
+
+        paper.on_trade(&sell(100.0, 11.0, 1000));
+        assert_eq!(filled(&mut paper), 1.0);
+        paper.on_trade(&sell(100.0, 2.0, 1001));
+        assert_eq!(filled(&mut paper), 1.0);
+        assert!(paper.orders[INST].is_empty());
+    }
+
+    #[test]
+    fn amending_through_the_spread_cancels_a_post_only_order() {
+        let book = book("0.1", "1", &[("99", "5")], &[("100", "5")]);
+        let mut paper = PaperVenue::new();
+        assert!(paper.place(&order("a", Side::Buy, "98", "1"), Some(&book)).is_ok());
+        assert!(paper.place(&order("b", Side::Sell, "101", "1"), Some(&book)).is_ok());
+        paper.drain_updates();
+
+        assert!(paper.amend(&amend("a", Some("99.5"), None), Some(&book)).is_ok());
+        assert_eq!(last_state(&mut paper), OrderState::Live);
+        assert!(paper.amend(&amend("a", Some("100"), None), Some(&book)).is_ok());
+        assert_eq!(last_state(&mut paper), OrderState::Canceled);
+        assert!(!paper.amend(&amend("a", Some("98"), None), Some(&book)).is_ok());
+
+        assert!(paper.amend(&amend("b", Some("99"), None), Some(&book)).is_ok());
+        assert_eq!(last_state(&mut paper), OrderState::Canceled);
+    }
+}
@@ -0,0 +1,237 @@
+use std::fmt;
+
+use crate::models::Instrument;
+
+/// A decimal step such as a tick size (`"0.0001"`) or lot size (`"1"`),
+/// held exactly as `units × 10^-decimals` so prices and sizes can be kept
+/// as whole numbers of steps instead of floats.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    units: i64,
+    decimals: u32,
+}
+
+impl Step {
+    pub fn parse(s: &str) -> Option<Self> {
+        let (units, decimals) = parse_decimal(s)?;
+        if units <= 0 || decimals > 18 {
+            return None;
+        }
+        Some(Self { units: i64::try_from(units).ok()?, decimals })
+    }
+
+    /// `10^-decimals`, e.g. `Step::decimal(2)` is 0.01.
+    pub const fn decimal(decimals: u32) -> Self {
+        Self { units: 1, decimals }
+    }
+
+    /// The last decimal place written in `s`: `"12.340"` → 0.001.
+    pub fn resolution(s: &str) -> Option<Self> {
+        let (_, decimals) = parse_decimal(s)?;
+        (decimals <= 18).then_some(Self::decimal(decimals))
+    }
+
+    /// The smaller of two steps.
+    pub fn finer(self, other: Self) -> Self {
+        if other.size() < self.size() { other } else { self }
+    }
+
+    pub fn size(self) -> f64 {
+        self.units as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// Whole number of steps in a decimal string, rounded to the nearest step.
+    pub fn steps(self, s: &str) -> Option<i64> {
+        let (value, step) = self.scaled(s)?;
+        i64::try_from((value.abs() + step / 2) / step * value.signum()).ok()
+    }
+
+    /// Whole number of steps in a decimal string; `None` if it's off the grid.
+    pub fn exact_steps(self, s: &str) -> Option<i64> {
+        let (value, step) = self.scaled(s)?;
+        if value % step != 0 {
+            return None;
+        }
+        i64::try_from(value / step).ok()
+    }
+
+    /// `s` and this step as integers at a common scale.
+    fn scaled(self, s: &str) -> Option<(i128, i128)> {
+        let (value, decimals) = parse_decimal(s)?;
+        let scale = decimals.max(self.decimals);
+        let value = value.checked_mul(10i128.checked_pow(scale - decimals)?)?;
+        let step = (self.units as i128).checked_mul(10i128.checked_pow(scale - self.decimals)?)?;
+        Some((value, step))
+    }
+
+    /// Whole number of steps in a float, rounded to the nearest step.
+    pub fn steps_f64(self, v: f64) -> i64 {
+        (v / self.size()).round() as i64
+    }
+
+    /// Whole number of steps at or below `v`. Values within float noise of a
+    /// step count as on it, so `0.3 / 0.1` doesn't floor to 2.
+    pub fn floor_steps_f64(self, v: f64) -> i64 {
+        let x = v / self.size();
+        snap(x).unwrap_or(x.floor()) as i64
+    }
+
+    /// Whole number of steps at or above `v`, with the same tolerance as `floor_steps_f64`.
+    pub fn ceil_steps_f64(self, v: f64) -> i64 {
+        let x = v / self.size();
+        snap(x).unwrap_or(x.ceil()) as i64
+    }
+
+    pub fn to_f64(self, steps: i64) -> f64 {
+        (steps as i128 * self.units as i128) as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// Exact decimal string for a number of steps, without trailing zeros
+    /// (the way OKX prints prices and sizes).
+    pub fn format(self, steps: i64) -> String {
+        let scaled = steps as i128 * self.units as i128;
+        let pow = 10i128.pow(self.decimals);
+        let sign = if scaled < 0 { "-" } else { "" };
+        let int = scaled.abs() / pow;
+        let frac = scaled.abs() % pow;
+        if frac == 0 {
+            format!("{}{}", sign, int)
+        } else {
+            let frac = format!("{:0width$}", frac, width = self.decimals as usize);
+            format!("{}{}.{}", sign, int, frac.trim_end_matches('0'))
+        }
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&Step { units: 1, decimals: self.decimals }.format(self.units))
+    }
+}
+
+/// Price and size grids for one instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Precision {
+    pub tick: Step,
+    pub lot: Step,
+}
+
+impl Precision {
+    pub fn from_instrument(inst: &Instrument) -> Option<Self> {
+        Some(Self {
+            tick: Step::parse(inst.tickSz.as_deref()?)?,
+            lot: Step::parse(inst.lotSz.as_deref()?)?,
+        })
+    }
+}
+
+/// The nearest integer if `x` is within float noise of it.
+fn snap(x: f64) -> Option<f64> {
+    let r = x.round();
+    ((x - r).abs() <= 1e-9 * x.abs().max(1.0)).then_some(r)
+}
+
+/// `"-12.3400"` → `(-123400, 4)`; rejects exponents, blanks and stray characters.
+fn parse_decimal(s: &str) -> Option<(i128, u32)> {
+    let s = s.trim();
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, s),
+    };
+    let (int, frac) = digits.split_once('.').unwrap_or((digits, ""));
+    if int.is_empty() && frac.is_empty() {
+        return None;
+    }
+    if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+
+    let mut value: i128 = 0;
+    for b in int.bytes().chain(frac.bytes()) {
+        value = value.checked_mul(10)?.checked_add((b - b'0') as i128)?;
+    }
+    let decimals = u32::try_from(frac.len()).ok()?;
+    Some((if negative { -value } else { value }, decimals))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_okx_step_sizes() {
+        let tick = Step::parse("0.1").unwrap();
+        assert_eq!(tick.size(), 0.1);
+        assert_eq!(tick.to_string(), "0.1");
+        assert_eq!(Step::parse("0.00001").unwrap().to_string(), "0.00001");
+        assert_eq!(Step::parse("5").unwrap().size(), 5.0);
+
+        assert_eq!(Step::parse("0"), None);
+        assert_eq!(Step::parse("-0.1"), None);
+        assert_eq!(Step::parse("1e-5"), None);
+        assert_eq!(Step::parse(""), None);
+        assert_eq!(Step::parse("."), None);
+    }
+
+    #[test]
+    fn steps_round_and_exact_steps_reject_off_grid() {
+        let tick = Step::parse("0.1").unwrap();
+        assert_eq!(tick.steps("3366.1"), Some(33661));
+        assert_eq!(tick.steps("3366.14"), Some(33661));
+        assert_eq!(tick.steps("3366.15"), Some(33662));
+        assert_eq!(tick.steps("-0.25"), Some(-3));
+        assert_eq!(tick.exact_steps("3366.10"), Some(33661));
+        assert_eq!(tick.exact_steps("3366.15"), None);
+
+        let five = Step::parse("5").unwrap();
+        assert_eq!(five.steps("12"), Some(2));
+        assert_eq!(five.steps("13"), Some(3));
+        assert_eq!(five.exact_steps("15"), Some(3));
+        assert_eq!(five.exact_steps("12"), None);
+
+        let fine = Step::parse("0.00001").unwrap();
+        assert_eq!(fine.exact_steps("0.12345"), Some(12345));
+        assert_eq!(fine.exact_steps("0.123456"), None);
+        assert_eq!(fine.steps("1"), Some(100_000));
+    }
+
+    #[test]
+    fn floor_and_ceil_do_not_move_exact_multiples() {
+        let tick = Step::parse("0.1").unwrap();
+        // 0.3 / 0.1 is 2.9999999999999996 in floats
+        assert_eq!(tick.floor_steps_f64(0.3), 3);
+        assert_eq!(tick.ceil_steps_f64(0.3), 3);
+        assert_eq!(tick.floor_steps_f64(0.7), 7);
+        assert_eq!(tick.ceil_steps_f64(0.7), 7);
+        assert_eq!(tick.floor_steps_f64(0.35), 3);
+        assert_eq!(tick.ceil_steps_f64(0.35), 4);
+
+        let fine = Step::parse("0.00001").unwrap();
+        assert_eq!(fine.floor_steps_f64(0.00003), 3);
+        assert_eq!(fine.ceil_steps_f64(0.00003), 3);
+        assert_eq!(fine.floor_steps_f64(0.000035), 3);
+        assert_eq!(fine.ceil_steps_f64(0.000035), 4);
+
+        let five = Step::parse("5").unwrap();
+        assert_eq!(five.floor_steps_f64(15.0), 3);
+        assert_eq!(five.ceil_steps_f64(15.0), 3);
+        assert_eq!(five.floor_steps_f64(14.9), 2);
+        assert_eq!(five.ceil_steps_f64(15.1), 4);
+    }
+
+    #[test]
+    fn format_prints_like_okx() {
+        let tick = Step::parse("0.1").unwrap();
+        assert_eq!(tick.format(33661), "3366.1");
+        assert_eq!(tick.format(33660), "3366");
+        assert_eq!(tick.format(-5), "-0.5");
+
+        let fine = Step::parse("0.00001").unwrap();
+        assert_eq!(fine.format(12340), "0.1234");
+        assert_eq!(fine.format(100_000), "1");
+        assert_eq!(fine.format(7), "0.00007");
+
+        assert_eq!(Step::parse("5").unwrap().format(3), "15");
+        assert_eq!(Step::parse("0.25").unwrap().format(3), "0.75");
+    }
+}
@@ -0,0 +1,234 @@
+//! Quote diffing: turns a strategy's desired two-sided quote into the fewest
+//! place/amend/cancel actions against its working orders, leaving orders
+//! alone while they're within tolerance so they keep their queue position.
+
+use crate::oms::{ManagedOrder, OrderAction, OrderStatus};
+use crate::precision::Precision;
+use crate::strategy::{OrderRequest, Side};
+
+/// Desired resting order on each side; `None` means no order on that side.
+#[derive(Debug, Clone, Default)]
+pub struct Quote {
+    pub bid: Option<OrderRequest>,
+    pub ask: Option<OrderRequest>,
+}
+
+impl Quote {
+    /// The last buy and last sell of a strategy's requests. A side it asked
+    /// for nothing on is pulled.
+    pub fn from_requests(reqs: &[OrderRequest]) -> Self {
+        let last = |side: Side| reqs.iter().rev().find(|r| r.side == side).cloned();
+        Self { bid: last(Side::Buy), ask: last(Side::Sell) }
+    }
+
+    fn side(&self, side: Side) -> Option<&OrderRequest> {
+        match side {
+            Side::Buy => self.bid.as_ref(),
+            Side::Sell => self.ask.as_ref(),
+        }
+    }
+}
+
+/// Actions that move the working orders to a `Quote`.
+#[derive(Debug, Clone, Default)]
+pub struct QuoteDiff {
+    pub place: Vec<OrderRequest>,
+    /// Amends and cancels of working orders.
+    pub modify: Vec<OrderAction>,
+}
+
+#[derive(Debug, Clone)]
+pub struct QuoteEngine {
+    precision: Precision,
+    /// Price moves of up to this many ticks leave an order where it is.
+    price_tolerance: i64,
+    /// Size changes of up to this fraction of the resting size are ignored.
+    size_tolerance: f64,
+}
+
+impl QuoteEngine {
+    pub fn new(precision: Precision) -> Self {
+        Self { precision, price_tolerance: 0, size_tolerance: 0.0 }
+    }
+
+    pub fn with_price_tolerance(mut self, ticks: i64) -> Self {
+        self.price_tolerance = ticks.max(0);
+        self
+    }
+
+    pub fn with_size_tolerance(mut self, fraction: f64) -> Self {
+        self.size_tolerance = fraction.max(0.0);
+        self
+    }
+
+    /// Per side: keep the working order closest to the target and cancel the
+    /// rest, amend the kept one if it's outside tolerance, or place if there's
+    /// none. Orders with a cancel or amend in flight, or not yet acked, are
+    /// left as they are until OKX has answered.
+    pub fn diff(&self, quote: &Quote, working: &[&ManagedOrder]) -> QuoteDiff {
+        let mut diff = QuoteDiff::default();
+        for side in [Side::Buy, Side::Sell] {
+            let orders = working.iter().filter(|o| o.side == side && o.status.is_open() && !o.cancel_pending);
+            let Some(target) = quote.side(side).filter(|t| t.size > 0.0) else {
+                diff.modify.extend(orders.map(|o| OrderAction::Cancel(o.id)));
+                continue;
+            };
+
+            let tick = self.precision.tick;
+            let target_ticks = tick.steps_f64(target.price);
+            let mut orders: Vec<_> = orders.collect();
+            orders.sort_by_key(|o| (tick.steps_f64(o.price) - target_ticks).abs());
+            let Some((keep, extra)) = orders.split_first() else {
+                diff.place.push(target.clone());
+                continue;
+            };
+            diff.modify.extend(extra.iter().map(|o| OrderAction::Cancel(o.id)));
+            if keep.status == OrderStatus::New || keep.amend_pending {
+                continue;
+            }
+
+            let price = ((tick.steps_f64(keep.price) - target_ticks).abs() > self.price_tolerance).then_some(target.price);
+            // compare in lots so float noise in `size - filled` doesn't trigger amends
+            let lot = self.precision.lot;
+            let remaining = lot.steps_f64(keep.remaining());
+            let size = ((remaining - lot.steps_f64(target.size)).abs() as f64 > remaining as f64 * self.size_tolerance)
+                // OKX's new size includes what has already filled
+                .then_some(keep.filled + target.size);
+            if price.is_some() || size.is_some() {
+                diff.modify.push(OrderAction::Amend { id: keep.id, price, size });
+            }
+        }
+        diff
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::precision::Step;
+    use uuid::Uuid;
+
+    fn engine() -> QuoteEngine {
+        let precision = Precision { tick: Step::parse("0.1").unwrap(), lot: Step::parse("1").unwrap() };
+        QuoteEngine::new(precision).with_price_tolerance(1).with_size_tolerance(0.1)
+    }
+
+    fn working(side: Side, price: f64, size: f64) -> ManagedOrder {
+        let id = Uuid::new_v4();
+        ManagedOrder {
+            id,
+            cl_ord_id: id.simple().to_string(),
+            strategy: "BTC-USDT".to_string(),
+            inst_id: "BTC-USDT".to_string(),
+            side,
+            price,
+            size,
+            filled: 0.0,
+            avg_price: 0.0,
+            status: OrderStatus::Acked,
+            ord_id: Some("1".to_string()),
+            cancel_pending: false,
+            amend_pending: false,
+            amend_req_id: None,
+            reject_reason: None,
+            created: 0,
+            updated: 0,
+        }
+    }
+
+    fn bid(price: f64, size: f64) -> Quote {
+        Quote { bid: Some(OrderRequest::new(Side::Buy, price, size)), ask: None }
+    }
+
+    fn amends(diff: &QuoteDiff) -> Vec<(Uuid, Option<f64>, Option<f64>)> {
+        diff.modify
+            .iter()
+            .filter_map(|a| match a {
+                OrderAction::Amend { id, price, size } => Some((*id, *price, *size)),
+                OrderAction::Cancel(_) => None,
+            })
+            .collect()
+    }
+
+    fn cancels(diff: &QuoteDiff) -> Vec<Uuid> {
+        diff.modify
+            .iter()
+            .filter_map(|a| match a {
+                OrderAction::Cancel(id) => Some(*id),
+                OrderAction::Amend { .. } => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn price_moves_within_tolerance_are_left_alone() {
+        let order = working(Side::Buy, 100.0, 10.0);
+        let diff = engine().diff(&bid(100.1, 10.0), &[&order]);
+        assert!(diff.place.is_empty() && diff.modify.is_empty());
+
+        let diff = engine().diff(&bid(100.2, 10.0), &[&order]);
+        assert_eq!(amends(&diff), vec![(order.id, Some(100.2), None)]);
+    }
+
+    #[test]
+    fn size_changes_within_tolerance_are_left_alone() {
+        let order = working(Side::Buy, 100.0, 10.0);
+        let diff = engine().diff(&bid(100.0, 11.0), &[&order]);
+        assert!(diff.modify.is_empty());
+
+        let diff = engine().diff(&bid(100.0, 12.0), &[&order]);
+        assert_eq!(amends(&diff), vec![(order.id, None, Some(12.0))]);
+    }
+
+    #[test]
+    fn amended_size_includes_what_has_filled() {
+        let mut order = working(Side::Buy, 100.0, 5.0);
+        order.filled = 2.0;
+        order.status = OrderStatus::PartiallyFilled;
+        let diff = engine().diff(&bid(100.0, 5.0), &[&order]);
+        assert_eq!(amends(&diff), vec![(order.id, None, Some(7.0))]);
+    }
+
+    #[test]
+    fn keeps_the_closest_order_and_cancels_the_rest() {
+        let far = working(Side::Buy, 99.0, 10.0);
+        let near = working(Side::Buy, 99.9, 10.0);
+        let ask = working(Side::Sell, 101.0, 10.0);
+        let diff = engine().diff(&bid(100.0, 10.0), &[&far, &near, &ask]);
+        assert!(diff.place.is_empty());
+        assert!(amends(&diff).is_empty());
+        let mut cancelled = cancels(&diff);
+        cancelled.sort();
+        let mut expected = vec![far.id, ask.id];
+        expected.sort();
+        assert_eq!(cancelled, expected);
+    }
+
+    #[test]
+    fn orders_awaiting_an_answer_are_not_amended() {
+        let mut new = working(Side::Buy, 90.0, 10.0);
+        new.status = OrderStatus::New;
+        assert!(engine().diff(&bid(100.0, 10.0), &[&new]).modify.is_empty());
+
+        let mut amending = working(Side::Buy, 90.0, 10.0);
+        amending.amend_pending = true;
+        let diff = engine().diff(&bid(100.0, 10.0), &[&amending]);
+        assert!(diff.place.is_empty() && diff.modify.is_empty());
+
+        // one being cancelled no longer counts: a replacement goes out
+        let mut cancelling = working(Side::Buy, 100.0, 10.0);
+        cancelling.cancel_pending = true;
+        let diff = engine().diff(&bid(100.0, 10.0), &[&cancelling]);
+        assert_eq!(diff.place.len(), 1);
+        assert!(diff.modify.is_empty());
+    }
+
+    #[test]
+    fn asking_for_nothing_pulls_the_quote() {
+        let bid = working(Side::Buy, 100.0, 10.0);
+        let ask = working(Side::Sell, 101.0, 10.0);
+        let diff = engine().diff(&Quote::from_requests(&[]), &[&bid, &ask]);
+        assert!(diff.place.is_empty());
+        assert_eq!(cancels(&diff).len(), 2);
+    }
+}
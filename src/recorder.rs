@@ -0,0 +1,382 @@
+//! Market data recorder: appends every raw WS frame, unparsed, to gzip files
+//! laid out as `DIR/YYYY-MM-DD/INST/CHANNEL.PART.tsv.gz` (UTC day of receipt).
+//! Each line is `received_ns <TAB> exchange_ts <TAB> frame`, where
+//! `exchange_ts` is the first data row's `ts` in Unix ms (empty for events).
+//! Frames without an `arg` (errors, notices, logins) go under `_events/_events`.
+//!
+//! The instruments being recorded are saved as OKX lists them in
+//! `DIR/instruments.json`, so a replay can trade the specs of the time.
+//!
+//! Files are never reopened: a restart or rotation starts the next part. Each
+//! finished part gets a line in `DIR/index.jsonl`; parts still open when the
+//! process dies are readable up to the last `flush` but not indexed.
+//!
+//! `Recorder::spawn` moves the compression and disk writes onto a blocking
+//! thread fed over a channel, so they never hold up the event loop.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+use crate::models::Instrument;
+use crate::sources::okx::RawFrame;
+use crate::sources::okx_auth::iso_timestamp;
+
+/// Channel and instrument directory for frames that aren't tied to a subscription.
+pub const EVENTS: &str = "_events";
+pub const INDEX_FILE: &str = "index.jsonl";
+pub const INSTRUMENTS_FILE: &str = "instruments.json";
+
+/// Default uncompressed size at which a part is closed and the next one started.
+const MAX_PART_BYTES: u64 = 256 * 1024 * 1024;
+
+/// One finished part, as listed in `index.jsonl`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Relative to the recording directory.
+    pub path: String,
+    pub date: String,
+    pub inst_id: String,
+    pub channel: String,
+    pub part: u32,
+    pub frames: u64,
+    /// Uncompressed bytes.
+    pub bytes: u64,
+    pub first_received_ns: u64,
+    pub last_received_ns: u64,
+    pub first_ts: Option<u64>,
+    pub last_ts: Option<u64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct FileKey {
+    date: String,
+    inst_id: String,
+    channel: String,
+}
+
+impl FileKey {
+    fn dir(&self) -> PathBuf {
+        Path::new(&self.date).join(&self.inst_id)
+    }
+}
+
+struct OpenPart {
+    encoder: GzEncoder<File>,
+    entry: IndexEntry,
+}
+
+pub struct Recorder {
+    dir: PathBuf,
+    max_part_bytes: u64,
+    open: HashMap<FileKey, OpenPart>,
+    /// Next part number per key, so parts already on disk are never reused.
+    next_part: HashMap<FileKey, u32>,
+    /// UTC day of the last frame; parts of earlier days are closed when it changes.
+    today: Option<String>,
+}
+
+impl Recorder {
+    pub fn open(dir: impl Into<PathBuf>) -> io::Result<Self> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir, max_part_bytes: MAX_PART_BYTES, open: HashMap::new(), next_part: HashMap::new(), today: None })
+    }
+
+    /// Save `instruments` as the recording's specs, replacing any an earlier
+    /// run saved.
+    pub fn write_instruments(&self, instruments: &[Instrument]) -> io::Result<()> {
+        let json = serde_json::to_string_pretty(instruments).map_err(io::Error::other)?;
+        fs::write(self.dir.join(INSTRUMENTS_FILE), json)
+    }
+
+    /// Record on a blocking thread until every `RecorderHandle` is dropped,
+    /// then close. The returned task ends once the recording is closed and
+    /// indexed; write errors are reported as they happen and don't stop it.
+    pub fn spawn(mut self) -> (RecorderHandle, JoinHandle<io::Result<()>>) {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let writer = tokio::task::spawn_blocking(move || {
+            while let Some(command) = rx.blocking_recv() {
+                let result = match command {
+                    RecorderCommand::Record(frame) => self.record(&frame),
+                    RecorderCommand::Flush => self.flush(),
+                };
+                if let Err(e) = result {
+                    eprintln!("⚠️ Recording failed: {}", e);
+                }
+            }
+            self.close()
+        });
+        (RecorderHandle { tx }, writer)
+    }
+
+    pub fn record(&mut self, frame: &RawFrame) -> io::Result<()> {
+        let (inst_id, channel, ts) = describe(&frame.text);
+        let date = iso_timestamp(UNIX_EPOCH + Duration::from_nanos(frame.received_ns))[..10].to_string();
+        if self.today.as_deref() != Some(date.as_str()) {
+            let stale: Vec<_> = self.open.keys().filter(|k| k.date != date).cloned().collect();
+            for key in stale {
+                self.finish(&key)?;
+            }
+            self.today = Some(date.clone());
+        }
+
+        let key = FileKey { date, inst_id: sanitize(&inst_id), channel: sanitize(&channel) };
+        let line = format!("{}\t{}\t{}\n", frame.received_ns, ts.map(|t| t.to_string()).unwrap_or_default(), frame.text);
+        let full = self.open.get(&key).is_some_and(|p| p.entry.frames > 0 && p.entry.bytes + line.len() as u64 > self.max_part_bytes);
+        if full {
+            self.finish(&key)?;
+        }
+        if !self.open.contains_key(&key) {
+            let part = self.start(&key, frame.received_ns)?;
+            self.open.insert(key.clone(), part);
+        }
+
+        let part = self.open.get_mut(&key).expect("part opened above");
+        part.encoder.write_all(line.as_bytes())?;
+        let entry = &mut part.entry;
+        entry.frames += 1;
+        entry.bytes += line.len() as u64;
+        entry.last_received_ns = frame.received_ns;
+        if ts.is_some() {
+            entry.first_ts = entry.first_ts.or(ts);
+            entry.last_ts = ts;
+        }
+        Ok(())
+    }
+
+    /// Push buffered data to disk so open parts are readable up to here.
+    pub fn flush(&mut self) -> io::Result<()> {
+        for part in self.open.values_mut() {
+            part.encoder.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Finish and index every open part.
+    pub fn close(mut self) -> io::Result<()> {
+        let keys: Vec<_> = self.open.keys().cloned().collect();
+        for key in keys {
+            self.finish(&key)?;
+        }
+        Ok(())
+    }
+
+    fn start(&mut self, key: &FileKey, received_ns: u64) -> io::Result<OpenPart> {
+        let dir = self.dir.join(key.dir());
+        fs::create_dir_all(&dir)?;
+        let mut part = self.next_part.get(key).copied().unwrap_or(0);
+        let (file, name) = loop {
+            let name = format!("{}.{:04}.tsv.gz", key.channel, part);
+            match OpenOptions::new().write(true).create_new(true).open(dir.join(&name)) {
+                Ok(file) => break (file, name),
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => part += 1,
+                Err(e) => return Err(e),
+            }
+        };
+        self.next_part.insert(key.clone(), part + 1);
+
+        let path = key.dir().join(name).to_string_lossy().replace('\\', "/");
+        Ok(OpenPart {
+            encoder: GzEncoder::new(file, Compression::default()),
+            entry: IndexEntry {
+                path,
+                date: key.date.clone(),
+                inst_id: key.inst_id.clone(),
+                channel: key.channel.clone(),
+                part,
+                frames: 0,
+                bytes: 0,
+                first_received_ns: received_ns,
+                last_received_ns: received_ns,
+                first_ts: None,
+                last_ts: None,
+            },
+        })
+    }
+
+    fn finish(&mut self, key: &FileKey) -> io::Result<()> {
+        let Some(part) = self.open.remove(key) else {
+            return Ok(());
+        };
+        part.encoder.finish()?;
+        let mut index = OpenOptions::new().create(true).append(true).open(self.dir.join(INDEX_FILE))?;
+        let line = serde_json::to_string(&part.entry).map_err(io::Error::other)?;
+        writeln!(index, "{}", line)
+    }
+}
+
+#[derive(Debug)]
+enum RecorderCommand {
+    Record(RawFrame),
+    Flush,
+}
+
+/// Feeds a spawned `Recorder`. Cheap to clone.
+#[derive(Debug, Clone)]
+pub struct RecorderHandle {
+    tx: mpsc::UnboundedSender<RecorderCommand>,
+}
+
+impl RecorderHandle {
+    pub fn record(&self, frame: RawFrame) {
+        let _ = self.tx.send(RecorderCommand::Record(frame));
+    }
+
+    /// Have the writer push what it has buffered to disk.
+    pub fn flush(&self) {
+        let _ = self.tx.send(RecorderCommand::Flush);
+    }
+}
+
+#[derive(Deserialize)]
+struct Probe {
+    arg: Option<ProbeArg>,
+    #[serde(default)]
+    data: Vec<serde_json::Value>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ProbeArg {
+    channel: String,
+    inst_id: Option<String>,
+    inst_family: Option<String>,
+    inst_type: Option<String>,
+}
+
+/// Instrument, channel and exchange `ts` of a frame. Candle rows are arrays
+/// with the timestamp first; every other channel has a `ts` field.
+fn describe(text: &str) -> (String, String, Option<u64>) {
+    let Ok(Probe { arg: Some(arg), data }) = serde_json::from_str::<Probe>(text) else {
+        return (EVENTS.to_string(), EVENTS.to_string(), None);
+    };
+    let ts = data.first().and_then(|row| match row {
+        serde_json::Value::Array(fields) => fields.first(),
+        row => row.get("ts"),
+    });
+    let ts = ts.and_then(|t| t.as_str()?.parse().ok());
+    let inst_id = arg.inst_id.or(arg.inst_family).or(arg.inst_type).unwrap_or_else(|| EVENTS.to_string());
+    (inst_id, arg.channel, ts)
+}
+
+/// Keep names usable as path components.
+fn sanitize(name: &str) -> String {
+    name.chars().map(|c| if c.is_ascii_alphanumeric() || "-_.".contains(c) { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    use flate2::read::MultiGzDecoder;
+
+    /// 2024-01-01T00:00:00Z in Unix ns.
+    const DAY_NS: u64 = 1_704_067_200_000_000_000;
+    const NS_PER_DAY: u64 = 86_400_000_000_000;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("recorder-{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    fn book_frame(received_ns: u64, ts: u64) -> RawFrame {
+        let text = format!(r#"{{"arg":{{"channel":"books5","instId":"BTC-USDT"}},"data":[{{"ts":"{}"}}]}}"#, ts);
+        RawFrame { received_ns, text }
+    }
+
+    fn index(dir: &Path) -> Vec<IndexEntry> {
+        let text = fs::read_to_string(dir.join(INDEX_FILE)).unwrap_or_default();
+        text.lines().map(|line| serde_json::from_str(line).unwrap()).collect()
+    }
+
+    fn read_part(dir: &Path, entry: &IndexEntry) -> String {
+        let mut text = String::new();
+        MultiGzDecoder::new(File::open(dir.join(&entry.path)).unwrap()).read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn full_parts_rotate() {
+        let dir = temp_dir();
+        let mut recorder = Recorder::open(&dir).unwrap();
+        recorder.max_part_bytes = 1;
+        for i in 0..3 {
+            recorder.record(&book_frame(DAY_NS + i, 1000 + i)).unwrap();
+        }
+        recorder.close().unwrap();
+
+        let entries = index(&dir);
+        assert_eq!(entries.iter().map(|e| e.part).collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert!(entries.iter().all(|e| e.frames == 1));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn a_new_day_closes_the_last_days_parts() {
+        let dir = temp_dir();
+        let mut recorder = Recorder::open(&dir).unwrap();
+        recorder.record(&book_frame(DAY_NS, 1000)).unwrap();
+        recorder.record(&book_frame(DAY_NS + NS_PER_DAY, 2000)).unwrap();
+        let entries = index(&dir);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].date, "2024-01-01");
+
+        recorder.close().unwrap();
+        let entries = index(&dir);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].date, "2024-01-02");
+        assert_eq!(entries[1].path, "2024-01-02/BTC-USDT/books5.0000.tsv.gz");
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn the_index_describes_each_part() {
+        let dir = temp_dir();
+        let mut recorder = Recorder::open(&dir).unwrap();
+        recorder.record(&book_frame(DAY_NS + 1, 1000)).unwrap();
+        recorder.record(&RawFrame { received_ns: DAY_NS + 2, text: r#"{"event":"error","code":"60018"}"#.to_string() }).unwrap();
+        recorder.record(&book_frame(DAY_NS + 3, 1100)).unwrap();
+        recorder.close().unwrap();
+        // a restart starts the next part instead of overwriting
+        let mut recorder = Recorder::open(&dir).unwrap();
+        recorder.record(&book_frame(DAY_NS + 4, 1200)).unwrap();
+        recorder.close().unwrap();
+
+        let entries = index(&dir);
+        let books: Vec<_> = entries.iter().filter(|e| e.channel == "books5").collect();
+        assert_eq!(books.iter().map(|e| e.part).collect::<Vec<_>>(), vec![0, 1]);
+        let first = books[0];
+        assert_eq!((first.inst_id.as_str(), first.frames), ("BTC-USDT", 2));
+        assert_eq!((first.first_received_ns, first.last_received_ns), (DAY_NS + 1, DAY_NS + 3));
+        assert_eq!((first.first_ts, first.last_ts), (Some(1000), Some(1100)));
+        let text = read_part(&dir, first);
+        assert_eq!(text.len() as u64, first.bytes);
+        assert_eq!(text.lines().next().unwrap(), format!("{}\t1000\t{}", DAY_NS + 1, book_frame(0, 1000).text));
+
+        let events = entries.iter().find(|e| e.channel == EVENTS).unwrap();
+        assert_eq!((events.frames, events.first_ts), (1, None));
+        assert!(read_part(&dir, events).starts_with(&format!("{}\t\t", DAY_NS + 2)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn a_spawned_recorder_closes_once_its_handles_are_dropped() {
+        let dir = temp_dir();
+        let (handle, writer) = Recorder::open(&dir).unwrap().spawn();
+        handle.record(book_frame(DAY_NS, 1000));
+        handle.flush();
+        drop(handle);
+        writer.await.unwrap().unwrap();
+        assert_eq!(index(&dir).len(), 1);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,439 @@
+//! Replay of a recorder directory (see recorder.rs): every part of the chosen
+//! instruments is merged back into one frame sequence, ordered by receive or
+//! exchange time, and paced against a simulated clock.
+//!
+//! Everything that reads time during a replay must read it from `Replay`:
+//! `now()` follows the recording, and `on_timer` ticks are emitted in between
+//! frames exactly where the live 1s timer would have fired. Two replays of the
+//! same recording therefore see the same events at the same clock readings,
+//! whatever the speed.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Lines};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use flate2::read::MultiGzDecoder;
+
+use crate::clock::Clock;
+use crate::models::Instrument;
+use crate::recorder::{IndexEntry, EVENTS, INDEX_FILE};
+
+/// Which recorded timestamp orders the frames and drives the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeOrder {
+    /// Local receive time: the exact sequence the recording process saw.
+    #[default]
+    Receive,
+    /// OKX's `ts`; frames without one (events) fall back to receive time.
+    Exchange,
+}
+
+impl TimeOrder {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "receive" => Some(Self::Receive),
+            "exchange" => Some(Self::Exchange),
+            _ => None,
+        }
+    }
+}
+
+/// How fast simulated time runs against the wall clock.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Speed {
+    /// No waiting at all.
+    #[default]
+    Max,
+    /// This many simulated seconds per wall second (`1.0` is real time).
+    Times(f64),
+}
+
+impl Speed {
+    /// `max`, or a positive multiple such as `1`, `10` or `0.5`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "max" => Some(Self::Max),
+            n => n.parse().ok().filter(|x: &f64| *x > 0.0 && x.is_finite()).map(Self::Times),
+        }
+    }
+}
+
+/// One recorded frame.
+#[derive(Debug, Clone)]
+pub struct ReplayFrame {
+    /// Simulated time of the frame, Unix nanoseconds (per `TimeOrder`).
+    pub at: u64,
+    pub received_ns: u64,
+    pub text: String,
+}
+
+type PartLines = Lines<BufReader<MultiGzDecoder<File>>>;
+
+/// Streams the frames of many parts as one sequence, by (time, receive time,
+/// part path), so ties always break the same way. A part is only opened once
+/// the sequence reaches its first frame and is closed when it runs out, so a
+/// long recording never holds more than its concurrent parts open.
+pub struct FrameReader {
+    order: TimeOrder,
+    paths: Vec<PathBuf>,
+    /// Parts not opened yet, as (earliest frame time, part), latest first.
+    pending: Vec<(u64, usize)>,
+    parts: Vec<Option<PartLines>>,
+    heads: Vec<Option<ReplayFrame>>,
+    queue: BinaryHeap<Reverse<(u64, u64, usize)>>,
+}
+
+impl FrameReader {
+    /// Every part under `dir` for `inst_ids` (the recorder's directory names,
+    /// so index ids like `BTC-USDT` need listing too), in any day. Where a part
+    /// starts is taken from `index.jsonl`; parts it doesn't list (left open by
+    /// a recorder that died) are read up to their first frame to find out.
+    pub fn open(dir: &Path, inst_ids: &[String], order: TimeOrder) -> io::Result<Self> {
+        let mut paths = Vec::new();
+        for day in sorted_entries(dir)? {
+            if !day.is_dir() {
+                continue;
+            }
+            for inst in sorted_entries(&day)? {
+                let name = inst.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+                if name == EVENTS || !inst_ids.iter().any(|id| id == name) {
+                    continue;
+                }
+                paths.extend(sorted_entries(&inst)?.into_iter().filter(|p| p.to_string_lossy().ends_with(".tsv.gz")));
+            }
+        }
+
+        let starts = indexed_starts(dir, order)?;
+        let mut pending = Vec::new();
+        for (i, path) in paths.iter().enumerate() {
+            let start = match path.strip_prefix(dir).ok().and_then(|p| starts.get(&p.to_string_lossy().replace('\\', "/"))) {
+                Some(&start) => Some(start),
+                None => open_part(path)?.find_map(|line| parse_line(&line.ok()?, order)).map(|frame| frame.at),
+            };
+            // a part without a single readable frame has nothing to replay
+            if let Some(start) = start {
+                pending.push((start, i));
+            }
+        }
+        pending.sort_by_key(|&(start, i)| Reverse((start, i)));
+
+        let n = paths.len();
+        Ok(Self { order, paths, pending, parts: (0..n).map(|_| None).collect(), heads: vec![None; n], queue: BinaryHeap::new() })
+    }
+
+    /// Number of parts being merged.
+    pub fn parts(&self) -> usize {
+        self.paths.len()
+    }
+
+    /// Open every part that starts no later than the next queued frame.
+    fn open_due(&mut self) {
+        while let Some(&(start, i)) = self.pending.last() {
+            if self.queue.peek().is_some_and(|Reverse((at, _, _))| *at < start) {
+                return;
+            }
+            self.pending.pop();
+            match open_part(&self.paths[i]) {
+                Ok(lines) => {
+                    self.parts[i] = Some(lines);
+                    self.advance(i);
+                }
+                Err(e) => eprintln!("⚠️ Skipping {}: {}", self.paths[i].display(), e),
+            }
+        }
+    }
+
+    /// Read part `i`'s next frame into its head slot and queue it, or close
+    /// the part once it has none left.
+    fn advance(&mut self, i: usize) {
+        let Some(lines) = self.parts[i].as_mut() else {
+            return;
+        };
+        loop {
+            let line = match lines.next() {
+                None => break,
+                Some(Ok(line)) => line,
+                Some(Err(e)) => {
+                    // a part still open when the recorder died ends mid-block
+                    eprintln!("⚠️ {} ends early: {}", self.paths[i].display(), e);
+                    break;
+                }
+            };
+            let Some(frame) = parse_line(&line, self.order) else {
+                eprintln!("⚠️ Skipping malformed line in {}", self.paths[i].display());
+                continue;
+            };
+            self.queue.push(Reverse((frame.at, frame.received_ns, i)));
+            self.heads[i] = Some(frame);
+            return;
+        }
+        self.parts[i] = None;
+    }
+
+    /// Time of the next frame without taking it.
+    pub fn peek_at(&mut self) -> Option<u64> {
+        self.open_due();
+        self.queue.peek().map(|Reverse((at, _, _))| *at)
+    }
+}
+
+impl Iterator for FrameReader {
+    type Item = ReplayFrame;
+
+    fn next(&mut self) -> Option<ReplayFrame> {
+        self.open_due();
+        let Reverse((_, _, i)) = self.queue.pop()?;
+        let frame = self.heads[i].take();
+        self.advance(i);
+        frame
+    }
+}
+
+/// Instrument specs saved with a recording (see `Recorder::write_instruments`),
+/// or any file holding a JSON array of OKX instrument rows.
+pub fn read_instruments(path: &Path) -> io::Result<Vec<Instrument>> {
+    serde_json::from_str(&fs::read_to_string(path)?).map_err(io::Error::other)
+}
+
+fn open_part(path: &Path) -> io::Result<PartLines> {
+    Ok(BufReader::new(MultiGzDecoder::new(File::open(path)?)).lines())
+}
+
+/// Earliest possible frame time of each part `index.jsonl` lists, by path.
+/// By exchange time, a part's frames without a `ts` go by receive time, so
+/// it may start at either.
+fn indexed_starts(dir: &Path, order: TimeOrder) -> io::Result<HashMap<String, u64>> {
+    let text = match fs::read_to_string(dir.join(INDEX_FILE)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(e) => return Err(e),
+    };
+    let mut starts = HashMap::new();
+    for line in text.lines() {
+        let Ok(entry) = serde_json::from_str::<IndexEntry>(line) else {
+            eprintln!("⚠️ Skipping malformed index line {:?}", line);
+            continue;
+        };
+        let start = match (order, entry.first_ts) {
+            (TimeOrder::Exchange, Some(ts)) => entry.first_received_ns.min(ts * 1_000_000),
+            _ => entry.first_received_ns,
+        };
+        starts.insert(entry.path, start);
+    }
+    Ok(starts)
+}
+
+/// `received_ns <TAB> exchange_ts <TAB> frame`, as written by the recorder.
+fn parse_line(line: &str, order: TimeOrder) -> Option<ReplayFrame> {
+    let mut fields = line.splitn(3, '\t');
+    let received_ns = fields.next()?.parse().ok()?;
+    let exchange_ts = match fields.next()? {
+        "" => None,
+        ts => Some(ts.parse::<u64>().ok()?),
+    };
+    let text = fields.next()?.to_string();
+    let at = match (order, exchange_ts) {
+        (TimeOrder::Exchange, Some(ts)) => ts * 1_000_000,
+        _ => received_ns,
+    };
+    Some(ReplayFrame { at, received_ns, text })
+}
+
+fn sorted_entries(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?.map(|e| e.map(|e| e.path())).collect::<io::Result<Vec<_>>>()?;
+    entries.sort();
+    Ok(entries)
+}
+
+/// What comes next in simulated time.
+#[derive(Debug)]
+pub enum ReplayEvent {
+    /// The live timer would have fired here.
+    Timer(Instant),
+    Frame(ReplayFrame),
+}
+
+/// A `FrameReader` with a simulated clock and timer.
+pub struct Replay {
+    frames: FrameReader,
+    speed: Speed,
+    timer_every: Duration,
+    /// Instant the first frame maps to (when it was replayed); every reading
+    /// is an offset from it.
+    origin: Instant,
+    /// Simulated Unix ns of the first frame, and the current reading.
+    start_ns: Option<u64>,
+    now_ns: u64,
+    next_timer_ns: u64,
+    /// `now_ns` in ms, shared with whatever stamps orders and fills.
+    clock: Clock,
+}
+
+impl Replay {
+    pub fn new(frames: FrameReader, speed: Speed, timer_every: Duration) -> Self {
+        Self {
+            frames,
+            speed,
+            timer_every,
+            origin: Instant::now(),
+            start_ns: None,
+            now_ns: 0,
+            next_timer_ns: 0,
+            clock: Clock::simulated(0),
+        }
+    }
+
+    /// A handle on the simulated clock, for the OMS and the paper venue.
+    pub fn clock(&self) -> Clock {
+        self.clock.clone()
+    }
+
+    /// The simulated clock; stands in for `Instant::now()` during a replay.
+    pub fn now(&self) -> Instant {
+        self.origin + Duration::from_nanos(self.now_ns - self.start_ns.unwrap_or(self.now_ns))
+    }
+
+    /// The simulated clock as Unix ms.
+    pub fn now_ms(&self) -> u64 {
+        self.now_ns / 1_000_000
+    }
+
+    /// The next timer tick or frame, whichever is earlier in simulated time,
+    /// after waiting for the wall clock to catch up at `Times` speeds. `None`
+    /// once every frame has been replayed.
+    pub async fn next(&mut self) -> Option<ReplayEvent> {
+        let frame_at = self.frames.peek_at()?;
+        let start = match self.start_ns {
+            Some(start) => start,
+            None => {
+                // the first frame is at the origin, with a timer tick right before it
+                self.origin = Instant::now();
+                self.start_ns = Some(frame_at);
+                (self.now_ns, self.next_timer_ns) = (frame_at, frame_at);
+                frame_at
+            }
+        };
+        // exchange timestamps aren't monotonic across channels; the clock is
+        let frame_at = frame_at.max(self.now_ns);
+
+        let timer = self.next_timer_ns <= frame_at;
+        let at = if timer { self.next_timer_ns } else { frame_at };
+        if let Speed::Times(x) = self.speed {
+            let due = Duration::from_nanos(at - start).div_f64(x);
+            tokio::time::sleep_until((self.origin + due).into()).await;
+        }
+        self.now_ns = at;
+        self.clock.set_ms(self.now_ms());
+
+        if timer {
+            self.next_timer_ns += self.timer_every.as_nanos() as u64;
+            return Some(ReplayEvent::Timer(self.now()));
+        }
+        self.frames.next().map(ReplayEvent::Frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::recorder::Recorder;
+    use crate::sources::okx::RawFrame;
+
+    /// 2024-01-01T00:00:00Z in Unix ns.
+    const T0: u64 = 1_704_067_200_000_000_000;
+    const MS: u64 = 1_000_000;
+
+    fn temp_dir() -> PathBuf {
+        std::env::temp_dir().join(format!("replay-{}", uuid::Uuid::new_v4().simple()))
+    }
+
+    fn frame(received_ns: u64, channel: &str, inst_id: &str) -> RawFrame {
+        let ts = received_ns / MS;
+        let text = format!(r#"{{"arg":{{"channel":"{}","instId":"{}"}},"data":[{{"ts":"{}"}}]}}"#, channel, inst_id, ts);
+        RawFrame { received_ns, text }
+    }
+
+    /// One recorder run over `frames`; `close: false` leaves its parts unindexed,
+    /// as if it had died.
+    fn record(dir: &Path, frames: &[RawFrame], close: bool) {
+        let mut recorder = Recorder::open(dir).unwrap();
+        for frame in frames {
+            recorder.record(frame).unwrap();
+        }
+        match close {
+            true => recorder.close().unwrap(),
+            false => recorder.flush().unwrap(),
+        }
+    }
+
+    /// Two instruments over three recorder runs, the last of which died.
+    fn fixture() -> PathBuf {
+        let dir = temp_dir();
+        for (run, close) in [(0, true), (1, true), (2, false)] {
+            let start = T0 + run * 3_000 * MS;
+            let frames: Vec<_> = (0..6)
+                .map(|i| match i % 3 {
+                    0 => frame(start + i * 400 * MS, "books5", "BTC-USDT"),
+                    1 => frame(start + i * 400 * MS, "trades", "BTC-USDT"),
+                    _ => frame(start + i * 400 * MS, "books5", "ETH-USDT"),
+                })
+                .collect();
+            record(&dir, &frames, close);
+        }
+        dir
+    }
+
+    /// Every event of a replay, with its clock reading as an offset from the first.
+    async fn run(dir: &Path) -> Vec<(String, Duration, u64)> {
+        let inst_ids = ["BTC-USDT".to_string(), "ETH-USDT".to_string()];
+        let frames = FrameReader::open(dir, &inst_ids, TimeOrder::Receive).unwrap();
+        let mut replay = Replay::new(frames, Speed::Max, Duration::from_secs(1));
+        let clock = replay.clock();
+        let mut events = Vec::new();
+        let mut origin = None;
+        while let Some(event) = replay.next().await {
+            let (what, now) = match event {
+                ReplayEvent::Timer(now) => ("timer".to_string(), now),
+                ReplayEvent::Frame(frame) => (frame.text, replay.now()),
+            };
+            let origin = *origin.get_or_insert(now);
+            events.push((what, now - origin, clock.now_ms()));
+        }
+        events
+    }
+
+    #[tokio::test]
+    async fn two_runs_see_the_same_events_at_the_same_times() {
+        let dir = fixture();
+        let first = run(&dir).await;
+        assert_eq!(first, run(&dir).await);
+
+        assert_eq!(first.iter().filter(|(what, ..)| what != "timer").count(), 18);
+        // a tick before the first frame, then one a second up to the last frame at 8s
+        assert_eq!(first.iter().filter(|(what, ..)| what == "timer").count(), 9);
+        assert!(first.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(first.last().unwrap().2, (T0 + 8_000 * MS) / MS);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn parts_are_opened_only_once_due_and_closed_once_read() {
+        let dir = fixture();
+        let mut frames = FrameReader::open(&dir, &["BTC-USDT".to_string()], TimeOrder::Receive).unwrap();
+        assert_eq!(frames.parts(), 6);
+        assert!(frames.parts.iter().all(Option::is_none));
+
+        let mut received = Vec::new();
+        while let Some(frame) = frames.next() {
+            received.push(frame.received_ns);
+            // one run's books5 and trades parts at a time
+            assert!(frames.parts.iter().filter(|p| p.is_some()).count() <= 2);
+        }
+        assert_eq!(received.len(), 12);
+        assert!(received.windows(2).all(|w| w[0] < w[1]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
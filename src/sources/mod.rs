@@ -1 +1,3 @@
-pub mod okx;
\ No newline at end of file
+pub mod okx;
+pub mod okx_auth;
+pub mod okx_rest;
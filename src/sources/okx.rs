@@ -1,70 +1,628 @@
-use tokio_tungstenite::connect_async;
-use websocket::{ClientBuilder, OwnedMessage};
-use serde_json::json;
-use tokio::runtime::Runtime;
+//! OKX WebSocket feed: one connection that keeps itself alive with OKX's text
+//! `ping`/`pong`, reconnects with exponential backoff, logs in first when given
+//! credentials (private channels), replays every active subscription on
+//! reconnect, and hands typed events to the caller over a channel.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use futures_util::stream::{SplitSink, SplitStream};
 use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::net::TcpStream;
+use tokio::sync::{mpsc, oneshot};
+use tokio::time::{self, Instant};
+use tokio_tungstenite::tungstenite::{Error as WsError, Message};
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+use crate::models::{ws_codes, OkxWsMessage, WsEvent, WsOpReply};
+use crate::sources::okx_auth::Credentials;
+
+type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
+type WsSink = SplitSink<WsStream, Message>;
+type WsSource = SplitStream<WsStream>;
+
+pub const PUBLIC_URL: &str = "wss://ws.okx.com:8443/ws/v5/public";
+/// Endpoint for `trades-all`, candles and other "business" channels.
+pub const BUSINESS_URL: &str = "wss://ws.okx.com:8443/ws/v5/business";
+/// Endpoint for account channels (`orders`, `positions`, ...); needs a login.
+pub const PRIVATE_URL: &str = "wss://ws.okx.com:8443/ws/v5/private";
+
+/// One subscription argument: `{channel, instId}` for market data,
+/// `{channel, instType}` or just `{channel}` for account channels.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize)]
+pub struct Subscription {
+    pub channel: String,
+    #[serde(rename = "instId", skip_serializing_if = "Option::is_none")]
+    pub inst_id: Option<String>,
+    #[serde(rename = "instType", skip_serializing_if = "Option::is_none")]
+    pub inst_type: Option<String>,
+}
+
+impl Subscription {
+    pub fn new(channel: impl Into<String>, inst_id: impl Into<String>) -> Self {
+        Self { channel: channel.into(), inst_id: Some(inst_id.into()), inst_type: None }
+    }
+
+    /// Subscription to every instrument of `inst_type` (`ANY` for all).
+    pub fn by_type(channel: impl Into<String>, inst_type: impl Into<String>) -> Self {
+        Self { channel: channel.into(), inst_id: None, inst_type: Some(inst_type.into()) }
+    }
+
+    /// Subscription to a channel that takes no instrument argument, e.g. `account`.
+    pub fn account(channel: impl Into<String>) -> Self {
+        Self { channel: channel.into(), inst_id: None, inst_type: None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FeedConfig {
+    pub url: String,
+    /// Send `ping` after this long without any message; OKX drops idle
+    /// connections after 30s.
+    pub ping_after: Duration,
+    /// Reconnect if nothing (not even `pong`) arrives this long after a `ping`.
+    pub pong_timeout: Duration,
+    pub backoff_min: Duration,
+    pub backoff_max: Duration,
+    /// Log in before subscribing; required on `PRIVATE_URL`.
+    pub credentials: Option<Credentials>,
+    /// Also deliver every frame verbatim as `FeedEvent::Raw`, ahead of its parsed form.
+    pub raw_frames: bool,
+}
+
+impl FeedConfig {
+    pub fn with_url(url: impl Into<String>) -> Self {
+        Self { url: url.into(), ..Self::default() }
+    }
+
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    pub fn with_raw_frames(mut self) -> Self {
+        self.raw_frames = true;
+        self
+    }
+}
+
+impl Default for FeedConfig {
+    fn default() -> Self {
+        Self {
+            url: PUBLIC_URL.to_string(),
+            ping_after: Duration::from_secs(20),
+            pong_timeout: Duration::from_secs(10),
+            backoff_min: Duration::from_millis(500),
+            backoff_max: Duration::from_secs(30),
+            credentials: None,
+            raw_frames: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum FeedEvent {
+    /// (Re)connected to `url` (and logged in, if configured) and every active subscription has been sent again.
+    /// Books must wait for fresh snapshots.
+    Connected { url: String },
+    /// The connection to `url` is gone; a reconnect is scheduled after `retry_in`.
+    Disconnected { url: String, reason: String, retry_in: Duration },
+    /// The feed for `url` has given up for good (e.g. OKX rejected the login)
+    /// and won't reconnect.
+    Stopped { url: String, reason: String },
+    /// A parsed frame: data push or event reply.
+    Message(OkxWsMessage),
+    /// A frame that isn't valid OKX JSON or belongs to an unsupported channel.
+    Unparsed(String),
+    /// A frame exactly as received (only with `FeedConfig::raw_frames`).
+    Raw(RawFrame),
+}
+
+/// One text frame as it came off the socket.
+#[derive(Debug, Clone)]
+pub struct RawFrame {
+    /// Local receive time, Unix nanoseconds.
+    pub received_ns: u64,
+    pub text: String,
+}
+
+#[derive(Debug)]
+enum FeedCommand {
+    Subscribe(Vec<Subscription>),
+    Unsubscribe(Vec<Subscription>),
+    Resubscribe(Subscription),
+    Request { id: String, msg: String, reply: oneshot::Sender<WsOpReply> },
+}
+
+/// Handle to a running feed task; dropping it (and the event receiver) stops the task.
+#[derive(Debug, Clone)]
+pub struct OkxFeed {
+    commands: mpsc::UnboundedSender<FeedCommand>,
+    connected: Arc<AtomicBool>,
+    next_id: Arc<AtomicU64>,
+}
+
+impl OkxFeed {
+    /// Spawn the feed task on the current tokio runtime, delivering into `events`
+    /// so several connections (e.g. public and business) can share one consumer.
+    pub fn spawn_into(config: FeedConfig, subscriptions: Vec<Subscription>, events: mpsc::UnboundedSender<FeedEvent>) -> Self {
+        let (cmd_tx, cmd_rx) = mpsc::unbounded_channel();
+        let connected = Arc::new(AtomicBool::new(false));
+        tokio::spawn(run(config, subscriptions, cmd_rx, events, connected.clone()));
+        Self { commands: cmd_tx, connected, next_id: Arc::new(AtomicU64::new(1)) }
+    }
+
+    /// True while a session is up (and logged in, if configured).
+    pub fn is_connected(&self) -> bool {
+        self.connected.load(Ordering::Acquire)
+    }
 
-use crate::models::BookMsg;
-
-pub async fn subscribe_to_books(inst_id: &str, channel: &str) {
-    let rt = Runtime::new().unwrap();
-    rt.block_on(async {
-        let url = "wss://ws.okx.com:8443/ws/v5/public";
-        let (mut ws_stream, _) = connect_async(url).await.expect("connect failed");
-
-        let msg = json!({
-            "op": "subscribe",
-            "args": [{
-                "channel": "books",
-                "instId": "BTC-USDT-SWAP"
-            }]
-        })
-        .to_string();
-
-        ws_stream.send(tokio_tungstenite::tungstenite::Message::Text(msg.into())).await.unwrap();
-
-        while let Some(msg) = ws_stream.next().await {
-            let msg = msg.unwrap();
-            if let tokio_tungstenite::tungstenite::Message::Text(txt) = msg {
-                println!("📨 {}", txt);
+    /// Send a trading op (`order`, `amend-order`, ...) and wait on the returned
+    /// receiver for OKX's reply, matched by request id. `None` if the connection
+    /// is down; the receiver fails if it drops before the reply arrives, in which
+    /// case the outcome is unknown. `exp_time` (Unix ms) makes OKX reject the
+    /// request if it arrives late, e.g. after a reconnect.
+    pub fn request(&self, op: &str, args: Vec<serde_json::Value>, exp_time: Option<u64>) -> Option<oneshot::Receiver<WsOpReply>> {
+        if !self.is_connected() {
+            return None;
+        }
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+        let mut msg = serde_json::json!({ "id": id, "op": op, "args": args });
+        if let Some(exp_time) = exp_time {
+            msg["expTime"] = exp_time.to_string().into();
+        }
+        let (reply, rx) = oneshot::channel();
+        self.commands.send(FeedCommand::Request { id, msg: msg.to_string(), reply }).ok()?;
+        Some(rx)
+    }
+
+    pub fn subscribe(&self, subscriptions: Vec<Subscription>) {
+        let _ = self.commands.send(FeedCommand::Subscribe(subscriptions));
+    }
+
+    pub fn unsubscribe(&self, subscriptions: Vec<Subscription>) {
+        let _ = self.commands.send(FeedCommand::Unsubscribe(subscriptions));
+    }
+
+    /// Unsubscribe and subscribe again so OKX pushes a fresh snapshot.
+    pub fn resubscribe(&self, subscription: Subscription) {
+        let _ = self.commands.send(FeedCommand::Resubscribe(subscription));
+    }
+}
+
+/// Why a session ended.
+enum SessionEnd {
+    /// The caller went away; stop for good.
+    Shutdown,
+    /// Reconnecting won't help (the login was rejected); stop and tell the caller.
+    Fatal(String),
+    Lost(String),
+}
+
+async fn run(
+    config: FeedConfig,
+    mut subscriptions: Vec<Subscription>,
+    mut commands: mpsc::UnboundedReceiver<FeedCommand>,
+    events: mpsc::UnboundedSender<FeedEvent>,
+    connected: Arc<AtomicBool>,
+) {
+    let mut backoff = config.backoff_min;
+
+    loop {
+        let reason = match connect_async(config.url.as_str()).await {
+            Ok((ws, _)) => {
+                backoff = config.backoff_min;
+                let end = session(&config, ws, &mut subscriptions, &mut commands, &events, &connected).await;
+                connected.store(false, Ordering::Release);
+                match end {
+                    SessionEnd::Shutdown => return,
+                    SessionEnd::Fatal(reason) => {
+                        let _ = events.send(FeedEvent::Stopped { url: config.url.clone(), reason });
+                        return;
+                    }
+                    SessionEnd::Lost(reason) => reason,
+                }
             }
+            Err(e) => format!("connect failed: {}", e),
+        };
+
+        let url = config.url.clone();
+        if events.send(FeedEvent::Disconnected { url, reason, retry_in: backoff }).is_err() {
+            return;
         }
-    });
-    // let url = "wss://ws.okx.com:8443/ws/v5/public";
-    // let mut client = ClientBuilder::new(url)
-    //     .unwrap()
-    //     .connect_insecure()
-    //     .unwrap();
-
-    // let sub_msg = serde_json::json!({
-    //     "op": "subscribe",
-    //     "args": [{
-    //         "channel": channel,
-    //         "instId": inst_id
-    //     }]
-    // })
-    // .to_string();
-
-    // client.send_message(&OwnedMessage::Text(sub_msg)).unwrap();
-    // println!("📡 Subscribed to {} | {}", channel, inst_id);
-
-    // loop {
-    //     let msg = client.recv_message().unwrap();
-
-    //     if let OwnedMessage::Text(txt) = msg {
-    //         if txt.contains("event") {
-    //             println!("📥 Sub event: {}", txt);
-    //         } else {
-    //             if let Ok(parsed) = serde_json::from_str::<BookMsg>(&txt) {
-    //                 println!("🔄 {}: {} entries", parsed.arg.instId, parsed.data.len());
-    //                 for book in parsed.data {
-    //                     println!("  Asks: {:?}", &book.asks[..1.min(book.asks.len())]);
-    //                     println!("  Bids: {:?}", &book.bids[..1.min(book.bids.len())]);
-    //                 }
-    //             } else {
-    //                 println!("⚠️ Couldn't parse: {}", txt);
-    //             }
-    //         }
-    //     }
-    // }
-}
\ No newline at end of file
+        time::sleep(backoff).await;
+        backoff = (backoff * 2).min(config.backoff_max);
+    }
+}
+
+async fn session(
+    config: &FeedConfig,
+    ws: WsStream,
+    subscriptions: &mut Vec<Subscription>,
+    commands: &mut mpsc::UnboundedReceiver<FeedCommand>,
+    events: &mpsc::UnboundedSender<FeedEvent>,
+    connected: &AtomicBool,
+) -> SessionEnd {
+    let (mut sink, mut stream) = ws.split();
+    // trading ops awaiting a reply; dropped (failing their receivers) if the session ends
+    let mut pending: HashMap<String, oneshot::Sender<WsOpReply>> = HashMap::new();
+
+    if let Some(credentials) = &config.credentials {
+        if let Err(end) = login(config, credentials, &mut sink, &mut stream, events).await {
+            return end;
+        }
+    }
+    if !subscriptions.is_empty() {
+        if let Err(e) = sink.send(op_message("subscribe", subscriptions)).await {
+            return SessionEnd::Lost(format!("subscribe failed: {}", e));
+        }
+    }
+    connected.store(true, Ordering::Release);
+    if events.send(FeedEvent::Connected { url: config.url.clone() }).is_err() {
+        return SessionEnd::Shutdown;
+    }
+
+    let mut deadline = Instant::now() + config.ping_after;
+    let mut awaiting_pong = false;
+
+    loop {
+        tokio::select! {
+            msg = stream.next() => {
+                let txt = match msg {
+                    Some(Ok(Message::Text(txt))) => txt,
+                    Some(Ok(Message::Close(frame))) => return SessionEnd::Lost(format!("closed by server: {:?}", frame)),
+                    Some(Ok(_)) => continue,
+                    Some(Err(e)) => return SessionEnd::Lost(e.to_string()),
+                    None => return SessionEnd::Lost("stream ended".to_string()),
+                };
+                deadline = Instant::now() + config.ping_after;
+                awaiting_pong = false;
+
+                if txt.as_str() == "pong" {
+                    continue;
+                }
+                if config.raw_frames {
+                    let received_ns = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos() as u64;
+                    let frame = RawFrame { received_ns, text: txt.to_string() };
+                    if events.send(FeedEvent::Raw(frame)).is_err() {
+                        return SessionEnd::Shutdown;
+                    }
+                }
+                let msg = match OkxWsMessage::parse(&txt) {
+                    Ok(msg) => msg,
+                    Err(_) => {
+                        if events.send(FeedEvent::Unparsed(txt.to_string())).is_err() {
+                            return SessionEnd::Shutdown;
+                        }
+                        continue;
+                    }
+                };
+
+                let msg = match msg {
+                    OkxWsMessage::OpReply(reply) => match pending.remove(&reply.id) {
+                        Some(waiter) => {
+                            let _ = waiter.send(reply);
+                            continue;
+                        }
+                        None => OkxWsMessage::OpReply(reply),
+                    },
+                    msg => msg,
+                };
+
+                // Don't replay a subscription OKX has rejected; reconnect early ahead of an upgrade
+                let mut upgrade = false;
+                if let OkxWsMessage::Event(event) = &msg {
+                    match event {
+                        WsEvent::Error { code, msg } if code == ws_codes::BAD_CHANNEL_OR_INST => {
+                            if let Some(bad) = rejected_subscription(msg) {
+                                subscriptions.retain(|s| *s != bad);
+                            }
+                        }
+                        WsEvent::Notice { code, .. } if code == ws_codes::SERVICE_UPGRADE => upgrade = true,
+                        _ => {}
+                    }
+                }
+                if events.send(FeedEvent::Message(msg)).is_err() {
+                    return SessionEnd::Shutdown;
+                }
+                if upgrade {
+                    return SessionEnd::Lost("service upgrade notice".to_string());
+                }
+            }
+
+            cmd = commands.recv() => {
+                let Some(cmd) = cmd else {
+                    return SessionEnd::Shutdown;
+                };
+                let sent = match cmd {
+                    FeedCommand::Subscribe(subs) => {
+                        let new: Vec<_> = subs.into_iter().filter(|s| !subscriptions.contains(s)).collect();
+                        subscriptions.extend(new.iter().cloned());
+                        send_op(&mut sink, "subscribe", &new).await
+                    }
+                    FeedCommand::Unsubscribe(subs) => {
+                        subscriptions.retain(|s| !subs.contains(s));
+                        send_op(&mut sink, "unsubscribe", &subs).await
+                    }
+                    FeedCommand::Resubscribe(sub) => {
+                        let subs = [sub];
+                        match send_op(&mut sink, "unsubscribe", &subs).await {
+                            Ok(()) => send_op(&mut sink, "subscribe", &subs).await,
+                            err => err,
+                        }
+                    }
+                    FeedCommand::Request { id, msg, reply } => {
+                        pending.retain(|_, waiter| !waiter.is_closed());
+                        pending.insert(id, reply);
+                        sink.send(Message::Text(msg.into())).await
+                    }
+                };
+                if let Err(e) = sent {
+                    return SessionEnd::Lost(format!("send failed: {}", e));
+                }
+            }
+
+            _ = time::sleep_until(deadline) => {
+                if awaiting_pong {
+                    return SessionEnd::Lost("pong timeout".to_string());
+                }
+                if let Err(e) = sink.send(Message::Text("ping".into())).await {
+                    return SessionEnd::Lost(format!("ping failed: {}", e));
+                }
+                awaiting_pong = true;
+                deadline = Instant::now() + config.pong_timeout;
+            }
+        }
+    }
+}
+
+/// Send `login` and wait for OKX's answer, which is forwarded to the caller
+/// either way. A rejection stops the feed: the same key would be rejected
+/// again on every reconnect.
+async fn login(
+    config: &FeedConfig,
+    credentials: &Credentials,
+    sink: &mut WsSink,
+    stream: &mut WsSource,
+    events: &mpsc::UnboundedSender<FeedEvent>,
+) -> Result<(), SessionEnd> {
+    let msg = serde_json::json!({ "op": "login", "args": [credentials.ws_login_args()] }).to_string();
+    if let Err(e) = sink.send(Message::Text(msg.into())).await {
+        return Err(SessionEnd::Lost(format!("login failed: {}", e)));
+    }
+
+    let deadline = Instant::now() + config.pong_timeout;
+    loop {
+        let msg = match time::timeout_at(deadline, stream.next()).await {
+            Err(_) => return Err(SessionEnd::Lost("login timed out".to_string())),
+            Ok(Some(Ok(Message::Text(txt)))) => OkxWsMessage::parse(&txt),
+            Ok(Some(Ok(Message::Close(frame)))) => return Err(SessionEnd::Lost(format!("closed by server: {:?}", frame))),
+            Ok(Some(Ok(_))) => continue,
+            Ok(Some(Err(e))) => return Err(SessionEnd::Lost(e.to_string())),
+            Ok(None) => return Err(SessionEnd::Lost("stream ended".to_string())),
+        };
+        let (code, reason) = match &msg {
+            Ok(OkxWsMessage::Event(WsEvent::Login { code, msg } | WsEvent::Error { code, msg })) => (code.clone(), msg.clone()),
+            _ => continue,
+        };
+        if let Ok(msg) = msg {
+            if events.send(FeedEvent::Message(msg)).is_err() {
+                return Err(SessionEnd::Shutdown);
+            }
+        }
+        return match code.as_str() {
+            "0" => Ok(()),
+            _ => Err(SessionEnd::Fatal(format!("login rejected ({}): {}", code, reason))),
+        };
+    }
+}
+
+async fn send_op(sink: &mut WsSink, op: &str, subscriptions: &[Subscription]) -> Result<(), WsError> {
+    if subscriptions.is_empty() {
+        return Ok(());
+    }
+    sink.send(op_message(op, subscriptions)).await
+}
+
+fn op_message(op: &str, subscriptions: &[Subscription]) -> Message {
+    let msg = serde_json::json!({ "op": op, "args": subscriptions }).to_string();
+    Message::Text(msg.into())
+}
+
+/// Pull `channel:books,instId:FOO-USDT` out of an OKX `60018` error message.
+pub fn rejected_subscription(msg: &str) -> Option<Subscription> {
+    let channel = msg.split("channel:").nth(1)?.split(',').next()?;
+    let inst_id = msg.split("instId:").nth(1)?.split_whitespace().next()?;
+    Some(Subscription::new(channel, inst_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpListener;
+    use tokio_tungstenite::accept_async;
+
+    type ServerWs = WebSocketStream<TcpStream>;
+
+    async fn listen() -> (TcpListener, FeedConfig) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let config = FeedConfig {
+            backoff_min: Duration::from_millis(10),
+            backoff_max: Duration::from_millis(10),
+            ..FeedConfig::with_url(url)
+        };
+        (listener, config)
+    }
+
+    async fn listen_private() -> (TcpListener, FeedConfig) {
+        let (listener, config) = listen().await;
+        (listener, config.with_credentials(Credentials::new("key", "secret", "pass")))
+    }
+
+    async fn accept(listener: &TcpListener) -> ServerWs {
+        let (tcp, _) = listener.accept().await.unwrap();
+        accept_async(tcp).await.unwrap()
+    }
+
+    async fn recv_text(ws: &mut ServerWs) -> String {
+        loop {
+            match ws.next().await.unwrap().unwrap() {
+                Message::Text(txt) => return txt.to_string(),
+                _ => continue,
+            }
+        }
+    }
+
+    async fn recv_json(ws: &mut ServerWs) -> serde_json::Value {
+        serde_json::from_str(&recv_text(ws).await).unwrap()
+    }
+
+    async fn send_json(ws: &mut ServerWs, value: serde_json::Value) {
+        ws.send(Message::Text(value.to_string().into())).await.unwrap();
+    }
+
+    fn spawn(config: FeedConfig, subscriptions: Vec<Subscription>) -> (OkxFeed, mpsc::UnboundedReceiver<FeedEvent>) {
+        let (events_tx, events) = mpsc::unbounded_channel();
+        (OkxFeed::spawn_into(config, subscriptions, events_tx), events)
+    }
+
+    async fn next_event(events: &mut mpsc::UnboundedReceiver<FeedEvent>) -> FeedEvent {
+        time::timeout(Duration::from_secs(5), events.recv()).await.expect("no feed event").expect("feed stopped")
+    }
+
+    #[tokio::test]
+    async fn logs_in_subscribes_and_matches_op_replies() {
+        let (listener, config) = listen_private().await;
+        let (feed, mut events) = spawn(config, vec![Subscription::account("orders")]);
+        let mut ws = accept(&listener).await;
+
+        let login = recv_json(&mut ws).await;
+        assert_eq!(login["op"], "login");
+        assert_eq!(login["args"][0]["apiKey"], "key");
+        assert_eq!(login["args"][0]["passphrase"], "pass");
+        let timestamp = login["args"][0]["timestamp"].as_str().unwrap();
+        let sign = Credentials::new("key", "secret", "pass").sign(timestamp, "GET", "/users/self/verify", "");
+        assert_eq!(login["args"][0]["sign"], sign.as_str());
+        send_json(&mut ws, serde_json::json!({ "event": "login", "code": "0", "msg": "", "connId": "a" })).await;
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Message(OkxWsMessage::Event(WsEvent::Login { .. }))));
+
+        let subscribe = recv_json(&mut ws).await;
+        assert_eq!(subscribe, serde_json::json!({ "op": "subscribe", "args": [{ "channel": "orders" }] }));
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+        assert!(feed.is_connected());
+
+        let reply = feed.request("order", vec![serde_json::json!({ "instId": "BTC-USDT", "clOrdId": "c1" })], Some(1)).unwrap();
+        let order = recv_json(&mut ws).await;
+        assert_eq!(order["op"], "order");
+        assert_eq!(order["expTime"], "1");
+        assert_eq!(order["args"][0]["clOrdId"], "c1");
+        send_json(
+            &mut ws,
+            serde_json::json!({
+                "id": order["id"],
+                "op": "order",
+                "code": "0",
+                "msg": "",
+                "data": [{ "clOrdId": "c1", "ordId": "42", "sCode": "0", "sMsg": "" }],
+            }),
+        )
+        .await;
+        let reply = time::timeout(Duration::from_secs(5), reply).await.unwrap().unwrap();
+        assert_eq!(reply.code, "0");
+        assert_eq!(reply.data[0].ordId, "42");
+    }
+
+    #[tokio::test]
+    async fn stops_after_a_rejected_login() {
+        let (listener, config) = listen_private().await;
+        let (_feed, mut events) = spawn(config, Vec::new());
+        let mut ws = accept(&listener).await;
+
+        assert_eq!(recv_json(&mut ws).await["op"], "login");
+        send_json(&mut ws, serde_json::json!({ "event": "error", "code": ws_codes::LOGIN_FAILED, "msg": "Login failed." })).await;
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Message(OkxWsMessage::Event(WsEvent::Error { .. }))));
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Stopped { .. }));
+
+        // no reconnect, even well past the backoff
+        assert!(time::timeout(Duration::from_millis(200), listener.accept()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn backs_off_exponentially_until_a_connection_succeeds() {
+        let (listener, config) = listen().await;
+        let config = FeedConfig { backoff_max: Duration::from_millis(40), ..config };
+        let (_feed, mut events) = spawn(config, Vec::new());
+
+        // drop the TCP connection before the handshake: every attempt fails
+        for expected in [10, 20, 40, 40] {
+            drop(listener.accept().await.unwrap());
+            let FeedEvent::Disconnected { retry_in, .. } = next_event(&mut events).await else {
+                panic!("expected a disconnect");
+            };
+            assert_eq!(retry_in, Duration::from_millis(expected));
+        }
+        let mut ws = accept(&listener).await;
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+
+        // a successful session resets the backoff
+        ws.close(None).await.unwrap();
+        let FeedEvent::Disconnected { retry_in, .. } = next_event(&mut events).await else {
+            panic!("expected a disconnect");
+        };
+        assert_eq!(retry_in, Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn replays_active_subscriptions_after_a_reconnect() {
+        let (listener, config) = listen().await;
+        let books = Subscription::new("books", "BTC-USDT");
+        let trades = Subscription::new("trades", "BTC-USDT");
+        let tickers = Subscription::new("tickers", "BTC-USDT");
+        let (feed, mut events) = spawn(config, vec![books.clone()]);
+
+        let mut ws = accept(&listener).await;
+        assert_eq!(recv_json(&mut ws).await, serde_json::json!({ "op": "subscribe", "args": [books] }));
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+        feed.subscribe(vec![trades.clone(), tickers.clone()]);
+        assert_eq!(recv_json(&mut ws).await, serde_json::json!({ "op": "subscribe", "args": [trades, tickers] }));
+        feed.unsubscribe(vec![tickers.clone()]);
+        assert_eq!(recv_json(&mut ws).await, serde_json::json!({ "op": "unsubscribe", "args": [tickers] }));
+
+        drop(ws);
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Disconnected { .. }));
+        assert!(!feed.is_connected());
+
+        let mut ws = accept(&listener).await;
+        assert_eq!(recv_json(&mut ws).await, serde_json::json!({ "op": "subscribe", "args": [books, trades] }));
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+        assert!(feed.is_connected());
+    }
+
+    #[tokio::test]
+    async fn a_ping_without_a_pong_forces_a_reconnect() {
+        let (listener, config) = listen().await;
+        let config = FeedConfig { ping_after: Duration::from_millis(50), pong_timeout: Duration::from_millis(50), ..config };
+        let (_feed, mut events) = spawn(config, Vec::new());
+
+        // answered pings keep the session up
+        let mut ws = accept(&listener).await;
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+        for _ in 0..3 {
+            assert_eq!(recv_text(&mut ws).await, "ping");
+            ws.send(Message::Text("pong".into())).await.unwrap();
+        }
+
+        // an unanswered one doesn't
+        assert_eq!(recv_text(&mut ws).await, "ping");
+        let FeedEvent::Disconnected { reason, .. } = next_event(&mut events).await else {
+            panic!("expected a disconnect");
+        };
+        assert_eq!(reason, "pong timeout");
+        let _ws = accept(&listener).await;
+        assert!(matches!(next_event(&mut events).await, FeedEvent::Connected { .. }));
+    }
+}
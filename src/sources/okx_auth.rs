@@ -0,0 +1,130 @@
+//! OKX API credentials and request signing: base64(HMAC-SHA256(secret, prehash)).
+
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Endpoint whose signature authenticates a WebSocket login.
+const WS_LOGIN_PATH: &str = "/users/self/verify";
+
+#[derive(Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub secret: String,
+    pub passphrase: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials").field("api_key", &self.api_key).finish_non_exhaustive()
+    }
+}
+
+impl Credentials {
+    pub fn new(api_key: impl Into<String>, secret: impl Into<String>, passphrase: impl Into<String>) -> Self {
+        Self { api_key: api_key.into(), secret: secret.into(), passphrase: passphrase.into() }
+    }
+
+    /// `OKX_API_KEY`, `OKX_API_SECRET` and `OKX_API_PASSPHRASE`, if all are set.
+    pub fn from_env() -> Option<Self> {
+        let var = |name| std::env::var(name).ok().filter(|v| !v.is_empty());
+        Some(Self::new(var("OKX_API_KEY")?, var("OKX_API_SECRET")?, var("OKX_API_PASSPHRASE")?))
+    }
+
+    /// Sign `timestamp + method + path + body`.
+    pub fn sign(&self, timestamp: &str, method: &str, path: &str, body: &str) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes()).expect("HMAC accepts any key length");
+        mac.update(timestamp.as_bytes());
+        mac.update(method.as_bytes());
+        mac.update(path.as_bytes());
+        mac.update(body.as_bytes());
+        BASE64.encode(mac.finalize().into_bytes())
+    }
+
+    /// `OK-ACCESS-*` headers for a REST request to `path` (including any query string).
+    pub fn rest_headers(&self, method: &str, path: &str, body: &str) -> [(&'static str, String); 4] {
+        let timestamp = iso_timestamp(SystemTime::now());
+        let sign = self.sign(&timestamp, method, path, body);
+        [
+            ("OK-ACCESS-KEY", self.api_key.clone()),
+            ("OK-ACCESS-SIGN", sign),
+            ("OK-ACCESS-TIMESTAMP", timestamp),
+            ("OK-ACCESS-PASSPHRASE", self.passphrase.clone()),
+        ]
+    }
+
+    /// Arguments for the WebSocket `login` op, signed with the current Unix time in seconds.
+    pub fn ws_login_args(&self) -> serde_json::Value {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+        serde_json::json!({
+            "apiKey": self.api_key,
+            "passphrase": self.passphrase,
+            "timestamp": timestamp,
+            "sign": self.sign(&timestamp, "GET", WS_LOGIN_PATH, ""),
+        })
+    }
+}
+
+/// UTC time as OKX's REST timestamp, e.g. `2020-12-08T09:08:57.715Z`.
+pub fn iso_timestamp(time: SystemTime) -> String {
+    let since_epoch = time.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+
+    // days since 1970-01-01 → civil date (Howard Hinnant's algorithm)
+    let z = days as i64 + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rem / 3_600,
+        rem % 3_600 / 60,
+        rem % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    // The request from OKX's REST authentication docs; the expected signature
+    // was computed independently (Python's hmac/hashlib).
+    const SECRET: &str = "22582BD0CFF14C41EDBF1AB98506286D";
+    const TIMESTAMP: &str = "2020-12-08T09:08:57.715Z";
+
+    #[test]
+    fn signs_the_documented_rest_request() {
+        let credentials = Credentials::new("key", SECRET, "pass");
+        let sign = credentials.sign(TIMESTAMP, "GET", "/api/v5/account/balance?ccy=BTC", "");
+        assert_eq!(sign, "HiZhvSfMtWJA3uUIVXV3a/bSXNPCWvYFXoGCVS8V4zY=");
+    }
+
+    #[test]
+    fn signs_the_websocket_login() {
+        let credentials = Credentials::new("key", SECRET, "pass");
+        let sign = credentials.sign("1538054050", "GET", WS_LOGIN_PATH, "");
+        assert_eq!(sign, "+LdIr8lkkvhr5hoA3g9TMC0+uQJ849ftAcocA/ouu4M=");
+    }
+
+    #[test]
+    fn formats_okx_timestamps() {
+        let time = UNIX_EPOCH + Duration::from_millis(1_607_418_537_715);
+        assert_eq!(iso_timestamp(time), TIMESTAMP);
+    }
+}
@@ -0,0 +1,696 @@
+//! Async client for OKX's REST API: public market endpoints, plus signed POSTs
+//! for trading when given credentials. Every call unwraps the `{code, msg, data}`
+//! envelope and turns non-zero codes into `OkxError::Api`; GETs retry rate
+//! limits and transient server errors with exponential backoff.
+
+use std::fmt;
+use std::time::Duration;
+
+use futures::future::join_all;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+
+use crate::kline::{Bar, Candle};
+use crate::market::{FundingPayment, MarkPrice, Ticker};
+use crate::sources::okx_auth::Credentials;
+use crate::models::{BookData, FundingHistoryData, Instrument, MarkPriceData, SystemTimeData, TickerData};
+
+pub const REST_URL: &str = "https://www.okx.com";
+
+/// OKX product families, the `instType` query parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum InstType {
+    Spot,
+    Margin,
+    Swap,
+    Futures,
+    Option,
+}
+
+impl InstType {
+    pub const ALL: [InstType; 5] = [InstType::Spot, InstType::Margin, InstType::Swap, InstType::Futures, InstType::Option];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            InstType::Spot => "SPOT",
+            InstType::Margin => "MARGIN",
+            InstType::Swap => "SWAP",
+            InstType::Futures => "FUTURES",
+            InstType::Option => "OPTION",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        InstType::ALL.into_iter().find(|t| t.as_str() == name)
+    }
+}
+
+impl fmt::Display for InstType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// OKX error codes the client treats specially.
+pub mod rest_codes {
+    /// Service temporarily unavailable.
+    pub const SERVICE_UNAVAILABLE: &str = "50001";
+    /// Endpoint request timeout.
+    pub const TIMEOUT: &str = "50004";
+    /// Too many requests.
+    pub const RATE_LIMITED: &str = "50011";
+    /// System busy.
+    pub const BUSY: &str = "50013";
+    /// Instrument ID doesn't exist.
+    pub const UNKNOWN_INSTRUMENT: &str = "51001";
+    /// Order doesn't exist.
+    pub const ORDER_NOT_FOUND: &str = "51603";
+}
+
+#[derive(Debug)]
+pub enum OkxError {
+    Http(reqwest::Error),
+    /// Non-2xx response without an OKX envelope.
+    Status { status: u16, body: String },
+    /// OKX answered with a non-zero `code`.
+    Api { code: String, msg: String },
+    Decode(serde_json::Error),
+    /// The call succeeded but returned no rows where one was expected.
+    Empty,
+    /// A private endpoint was called without credentials.
+    Unauthenticated,
+}
+
+impl OkxError {
+    /// Rate limits and transient server-side failures; worth another attempt.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            OkxError::Http(e) => e.is_timeout() || e.is_connect(),
+            OkxError::Status { status, .. } => *status == 429 || *status >= 500,
+            OkxError::Api { code, .. } => matches!(
+                code.as_str(),
+                rest_codes::SERVICE_UNAVAILABLE | rest_codes::TIMEOUT | rest_codes::RATE_LIMITED | rest_codes::BUSY
+            ),
+            OkxError::Decode(_) | OkxError::Empty | OkxError::Unauthenticated => false,
+        }
+    }
+
+    /// OKX doesn't list the instrument asked about.
+    pub fn is_unknown_instrument(&self) -> bool {
+        matches!(self, OkxError::Api { code, .. } if code == rest_codes::UNKNOWN_INSTRUMENT)
+    }
+}
+
+impl fmt::Display for OkxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OkxError::Http(e) => write!(f, "request failed: {}", e),
+            OkxError::Status { status, body } => write!(f, "HTTP {}: {}", status, body),
+            OkxError::Api { code, msg } => write!(f, "OKX error {}: {}", code, msg),
+            OkxError::Decode(e) => write!(f, "unexpected response: {}", e),
+            OkxError::Empty => write!(f, "empty response"),
+            OkxError::Unauthenticated => write!(f, "no API credentials configured"),
+        }
+    }
+}
+
+impl std::error::Error for OkxError {}
+
+impl From<reqwest::Error> for OkxError {
+    fn from(e: reqwest::Error) -> Self {
+        OkxError::Http(e)
+    }
+}
+
+impl From<serde_json::Error> for OkxError {
+    fn from(e: serde_json::Error) -> Self {
+        OkxError::Decode(e)
+    }
+}
+
+/// `data` stays raw until the code has been checked; error replies may omit it.
+#[derive(Deserialize)]
+struct Envelope {
+    code: String,
+    msg: String,
+    #[serde(default)]
+    data: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
+pub struct OkxRestClient {
+    http: reqwest::Client,
+    base_url: String,
+    max_retries: u32,
+    retry_backoff: Duration,
+    credentials: Option<Credentials>,
+}
+
+impl Default for OkxRestClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OkxRestClient {
+    pub fn new() -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            base_url: REST_URL.to_string(),
+            max_retries: 3,
+            retry_backoff: Duration::from_millis(250),
+            credentials: None,
+        }
+    }
+
+    /// Point at another host, e.g. a mock server.
+    pub fn with_base_url(mut self, url: impl Into<String>) -> Self {
+        self.base_url = url.into();
+        self
+    }
+
+    /// Sign private requests with these credentials.
+    pub fn with_credentials(mut self, credentials: Credentials) -> Self {
+        self.credentials = Some(credentials);
+        self
+    }
+
+    /// Signed GET from a private endpoint. Retried like `get`.
+    pub async fn get_signed<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<Vec<T>, OkxError> {
+        let credentials = self.credentials.as_ref().ok_or(OkxError::Unauthenticated)?;
+        // the signature covers the encoded query string
+        let url = reqwest::Url::parse_with_params(&format!("{}{}", self.base_url, path), query)
+            .map_err(|e| OkxError::Decode(serde::de::Error::custom(e)))?;
+        let signed_path = match url.query() {
+            Some(q) if !q.is_empty() => format!("{}?{}", url.path(), q),
+            _ => url.path().to_string(),
+        };
+
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            let mut req = self.http.get(url.clone());
+            for (name, value) in credentials.rest_headers("GET", &signed_path, "") {
+                req = req.header(name, value);
+            }
+            let result = match req.send().await {
+                Ok(resp) => self.decode(resp).await,
+                Err(e) => Err(e.into()),
+            };
+            match result {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Signed POST of a JSON body to a private endpoint. Never retried: trading
+    /// calls aren't idempotent. A non-zero `code` that still carries rows (e.g.
+    /// a batch where some orders failed) returns the rows, so callers can read
+    /// each item's own `sCode`.
+    pub async fn post_signed<T: DeserializeOwned>(&self, path: &str, body: &serde_json::Value) -> Result<Vec<T>, OkxError> {
+        let credentials = self.credentials.as_ref().ok_or(OkxError::Unauthenticated)?;
+        let body = body.to_string();
+        let mut req = self
+            .http
+            .post(format!("{}{}", self.base_url, path))
+            .header("Content-Type", "application/json")
+            .body(body.clone());
+        for (name, value) in credentials.rest_headers("POST", path, &body) {
+            req = req.header(name, value);
+        }
+
+        let resp = req.send().await?;
+        let status = resp.status();
+        let text = resp.text().await?;
+        let envelope: Envelope = match serde_json::from_str(&text) {
+            Ok(envelope) => envelope,
+            Err(_) if !status.is_success() => return Err(OkxError::Status { status: status.as_u16(), body: text }),
+            Err(e) => return Err(OkxError::Decode(e)),
+        };
+        let rows: Vec<T> = match envelope.data {
+            serde_json::Value::Array(rows) if !rows.is_empty() => serde_json::from_value(serde_json::Value::Array(rows))?,
+            _ if envelope.code != "0" => return Err(OkxError::Api { code: envelope.code, msg: envelope.msg }),
+            _ => Vec::new(),
+        };
+        Ok(rows)
+    }
+
+    /// GET `path` and decode the envelope's `data` rows.
+    pub async fn get<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<Vec<T>, OkxError> {
+        let mut backoff = self.retry_backoff;
+        let mut attempt = 0;
+        loop {
+            match self.get_once(path, query).await {
+                Err(e) if e.is_retryable() && attempt < self.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    async fn get_once<T: DeserializeOwned>(&self, path: &str, query: &[(&str, String)]) -> Result<Vec<T>, OkxError> {
+        let resp = self.http.get(format!("{}{}", self.base_url, path)).query(query).send().await?;
+        self.decode(resp).await
+    }
+
+    async fn decode<T: DeserializeOwned>(&self, resp: reqwest::Response) -> Result<Vec<T>, OkxError> {
+        let status = resp.status();
+        let body = resp.text().await?;
+
+        let envelope: Envelope = match serde_json::from_str(&body) {
+            Ok(envelope) => envelope,
+            Err(_) if !status.is_success() => return Err(OkxError::Status { status: status.as_u16(), body }),
+            Err(e) => return Err(OkxError::Decode(e)),
+        };
+        if envelope.code != "0" {
+            return Err(OkxError::Api { code: envelope.code, msg: envelope.msg });
+        }
+        if envelope.data.is_null() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_value(envelope.data)?)
+    }
+
+    /// Page backwards through a history endpoint with OKX's `after` cursor until
+    /// `count` rows are collected or history runs out. Rows come back newest
+    /// first; `cursor` extracts the value to page from (usually a timestamp).
+    pub async fn paginate<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        query: &[(&str, String)],
+        page_size: usize,
+        count: usize,
+        cursor: impl Fn(&T) -> Option<String>,
+    ) -> Result<Vec<T>, OkxError> {
+        let mut rows: Vec<T> = Vec::with_capacity(count);
+        let mut after: Option<String> = None;
+
+        while rows.len() < count {
+            let mut page_query = query.to_vec();
+            page_query.push(("limit", (count - rows.len()).min(page_size).to_string()));
+            if let Some(after) = &after {
+                page_query.push(("after", after.clone()));
+            }
+
+            let page: Vec<T> = self.get(path, &page_query).await?;
+            match page.last().and_then(&cursor) {
+                Some(next) if after.as_ref() != Some(&next) => after = Some(next),
+                _ => {
+                    rows.extend(page);
+                    break;
+                }
+            }
+            rows.extend(page);
+        }
+        rows.truncate(count);
+        Ok(rows)
+    }
+
+    // ─── Public data ─────────────────────────────────────────────────────
+
+    /// Instruments of one type. OPTION requires `inst_family` (e.g. `BTC-USD`).
+    pub async fn instruments(&self, inst_type: InstType, inst_family: Option<&str>) -> Result<Vec<Instrument>, OkxError> {
+        let mut query = vec![("instType", inst_type.as_str().to_string())];
+        if let Some(family) = inst_family {
+            query.push(("instFamily", family.to_string()));
+        }
+        self.get("/api/v5/public/instruments", &query).await
+    }
+
+    /// Every live instrument across all types, options across all of their
+    /// families. MARGIN is left out: it lists the spot pairs again under the
+    /// same instIds. Types and families are fetched concurrently; one that fails
+    /// is logged and left out, and only an error from every fetch is returned.
+    pub async fn all_instruments(&self) -> Result<Vec<Instrument>, OkxError> {
+        let types = [InstType::Spot, InstType::Swap, InstType::Futures];
+        let mut results = join_all(types.map(|t| async move { (t.as_str().to_string(), self.instruments(t, None).await) })).await;
+        match self.underlyings(InstType::Option).await {
+            Ok(families) => {
+                let options = families.iter().map(|family| async move {
+                    (format!("OPTION {}", family), self.instruments(InstType::Option, Some(family)).await)
+                });
+                results.extend(join_all(options).await);
+            }
+            Err(e) => results.push(("OPTION underlyings".to_string(), Err(e))),
+        }
+
+        let mut all = Vec::new();
+        let mut first_error = None;
+        for (what, result) in results {
+            match result {
+                Ok(rows) => all.extend(rows),
+                Err(e) => {
+                    eprintln!("⚠️ Fetching {} instruments failed: {}", what, e);
+                    first_error.get_or_insert(e);
+                }
+            }
+        }
+        match first_error {
+            Some(e) if all.is_empty() => Err(e),
+            _ => Ok(all),
+        }
+    }
+
+    /// Underlyings (instrument families) listed for a derivatives type.
+    pub async fn underlyings(&self, inst_type: InstType) -> Result<Vec<String>, OkxError> {
+        let rows: Vec<Vec<String>> = self.get("/api/v5/public/underlying", &[("instType", inst_type.as_str().to_string())]).await?;
+        Ok(rows.into_iter().flatten().collect())
+    }
+
+    /// OKX's clock, in Unix milliseconds.
+    pub async fn system_time(&self) -> Result<u64, OkxError> {
+        let rows: Vec<SystemTimeData> = self.get("/api/v5/public/time", &[]).await?;
+        let row = rows.first().ok_or(OkxError::Empty)?;
+        row.ts.parse().map_err(|_| OkxError::Decode(serde::de::Error::custom(format!("bad timestamp {:?}", row.ts))))
+    }
+
+    /// Mark prices for every instrument of `inst_type`, or just `inst_id`.
+    pub async fn mark_prices(&self, inst_type: InstType, inst_id: Option<&str>) -> Result<Vec<MarkPrice>, OkxError> {
+        let mut query = vec![("instType", inst_type.as_str().to_string())];
+        if let Some(inst_id) = inst_id {
+            query.push(("instId", inst_id.to_string()));
+        }
+        let rows: Vec<MarkPriceData> = self.get("/api/v5/public/mark-price", &query).await?;
+        Ok(rows.iter().filter_map(MarkPrice::from_okx).collect())
+    }
+
+    /// Settled funding for a perpetual swap, oldest first.
+    pub async fn funding_history(&self, inst_id: &str, count: usize) -> Result<Vec<FundingPayment>, OkxError> {
+        let query = [("instId", inst_id.to_string())];
+        let rows: Vec<FundingHistoryData> =
+            self.paginate("/api/v5/public/funding-rate-history", &query, 100, count, |r: &FundingHistoryData| Some(r.fundingTime.clone())).await?;
+        Ok(rows.iter().rev().filter_map(FundingPayment::from_okx).collect())
+    }
+
+    // ─── Market data ─────────────────────────────────────────────────────
+
+    /// Book snapshot, up to 400 levels a side. Feed it to `OrderBook::apply_snapshot`.
+    pub async fn order_book(&self, inst_id: &str, depth: usize) -> Result<BookData, OkxError> {
+        let query = [("instId", inst_id.to_string()), ("sz", depth.min(400).to_string())];
+        let rows: Vec<BookData> = self.get("/api/v5/market/books", &query).await?;
+        rows.into_iter().next().ok_or(OkxError::Empty)
+    }
+
+    pub async fn ticker(&self, inst_id: &str) -> Result<Ticker, OkxError> {
+        let rows: Vec<TickerData> = self.get("/api/v5/market/ticker", &[("instId", inst_id.to_string())]).await?;
+        rows.iter().find_map(Ticker::from_okx).ok_or(OkxError::Empty)
+    }
+
+    /// Tickers of every instrument of `inst_type`.
+    pub async fn tickers(&self, inst_type: InstType) -> Result<Vec<Ticker>, OkxError> {
+        let rows: Vec<TickerData> = self.get("/api/v5/market/tickers", &[("instType", inst_type.as_str().to_string())]).await?;
+        Ok(rows.iter().filter_map(Ticker::from_okx).collect())
+    }
+
+    /// The most recent `count` bars, oldest first, from `history-candles`.
+    pub async fn candles(&self, inst_id: &str, bar: Bar, count: usize) -> Result<Vec<Candle>, OkxError> {
+        let query = [("instId", inst_id.to_string()), ("bar", bar.as_str().to_string())];
+        let rows: Vec<Vec<String>> =
+            self.paginate("/api/v5/market/history-candles", &query, 100, count, |r: &Vec<String>| r.first().cloned()).await?;
+        Ok(rows.iter().rev().filter_map(|row| Candle::from_okx(row)).collect())
+    }
+}
+
+/// A local HTTP server answering each request, in order, with the next scripted
+/// `(status, body)` and then closing the connection; it stops listening once the
+/// script runs out.
+#[cfg(test)]
+pub(crate) struct MockRest {
+    pub url: String,
+    requests: std::sync::Arc<std::sync::Mutex<Vec<MockRequest>>>,
+}
+
+#[cfg(test)]
+#[derive(Debug, Clone)]
+pub(crate) struct MockRequest {
+    pub method: String,
+    /// Path and query string.
+    pub target: String,
+    pub body: String,
+}
+
+#[cfg(test)]
+impl MockRest {
+    pub async fn start(replies: Vec<(u16, String)>) -> Self {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let log = requests.clone();
+        tokio::spawn(async move {
+            for (status, body) in replies {
+                let (mut tcp, _) = listener.accept().await.unwrap();
+                let mut buf = Vec::new();
+                let mut chunk = [0u8; 4096];
+                let head_end = loop {
+                    let n = tcp.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                    if let Some(i) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                        break i + 4;
+                    }
+                };
+                let head = String::from_utf8_lossy(&buf[..head_end]).to_string();
+                let length = head
+                    .lines()
+                    .find_map(|l| l.to_ascii_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+                while buf.len() < head_end + length {
+                    let n = tcp.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                let mut line = head.lines().next().unwrap().split(' ');
+                log.lock().unwrap().push(MockRequest {
+                    method: line.next().unwrap().to_string(),
+                    target: line.next().unwrap().to_string(),
+                    body: String::from_utf8_lossy(&buf[head_end..]).to_string(),
+                });
+                let reply = format!(
+                    "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                tcp.write_all(reply.as_bytes()).await.unwrap();
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// `{"code":"0","msg":"","data":data}`
+    pub fn ok(data: serde_json::Value) -> (u16, String) {
+        (200, serde_json::json!({ "code": "0", "msg": "", "data": data }).to_string())
+    }
+
+    pub fn requests(&self) -> Vec<MockRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn client(mock: &MockRest) -> OkxRestClient {
+        OkxRestClient { retry_backoff: Duration::from_millis(1), ..OkxRestClient::new().with_base_url(mock.url.as_str()) }
+    }
+
+    fn error(code: &str, msg: &str) -> (u16, String) {
+        (200, serde_json::json!({ "code": code, "msg": msg, "data": [] }).to_string())
+    }
+
+    #[tokio::test]
+    async fn a_non_zero_code_is_an_api_error() {
+        let mock = MockRest::start(vec![error(rest_codes::UNKNOWN_INSTRUMENT, "Instrument ID doesn't exist")]).await;
+        let result = client(&mock).underlyings(InstType::Swap).await;
+        assert!(matches!(result, Err(OkxError::Api { code, .. }) if code == rest_codes::UNKNOWN_INSTRUMENT));
+        assert_eq!(mock.requests().len(), 1);
+        assert_eq!(mock.requests()[0].target, "/api/v5/public/underlying?instType=SWAP");
+    }
+
+    #[tokio::test]
+    async fn rate_limits_and_server_errors_are_retried() {
+        let mock = MockRest::start(vec![
+            (429, "Too Many Requests".to_string()),
+            (503, "Service Unavailable".to_string()),
+            error(rest_codes::RATE_LIMITED, "Too Many Requests"),
+            MockRest::ok(serde_json::json!([["BTC-USD", "ETH-USD"]])),
+        ])
+        .await;
+        let families = client(&mock).underlyings(InstType::Option).await.unwrap();
+        assert_eq!(families, vec!["BTC-USD", "ETH-USD"]);
+        assert_eq!(mock.requests().len(), 4);
+
+        // and given up on after max_retries
+        let mock = MockRest::start(vec![(500, String::new()); 4]).await;
+        let result = client(&mock).underlyings(InstType::Option).await;
+        assert!(matches!(result, Err(OkxError::Status { status: 500, .. })));
+        assert_eq!(mock.requests().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        let mock = MockRest::start(vec![(404, "Not Found".to_string())]).await;
+        let result = client(&mock).underlyings(InstType::Swap).await;
+        assert!(matches!(result, Err(OkxError::Status { status: 404, .. })));
+        assert_eq!(mock.requests().len(), 1);
+
+        let mock = MockRest::start(vec![(400, serde_json::json!({ "code": "51000", "msg": "Parameter bar error" }).to_string())]).await;
+        let result = client(&mock).underlyings(InstType::Swap).await;
+        assert!(matches!(result, Err(OkxError::Api { code, .. }) if code == "51000"));
+        assert_eq!(mock.requests().len(), 1);
+    }
+
+    /// `n` candle rows, newest first, opening a minute apart back from `newest`.
+    fn candle_page(newest: u64, n: u64) -> serde_json::Value {
+        let rows: Vec<_> = (0..n).map(|i| serde_json::json!([(newest - i * 60_000).to_string(), "1", "1", "1", "1", "1"])).collect();
+        serde_json::Value::Array(rows)
+    }
+
+    #[tokio::test]
+    async fn paginate_stops_at_the_requested_count() {
+        let newest = 100 * 60_000 * 60;
+        let mock = MockRest::start(vec![
+            MockRest::ok(candle_page(newest, 100)),
+            MockRest::ok(candle_page(newest - 100 * 60_000, 100)),
+            MockRest::ok(candle_page(newest - 200 * 60_000, 50)),
+        ])
+        .await;
+        let candles = client(&mock).candles("BTC-USDT", Bar::M1, 250).await.unwrap();
+        assert_eq!(candles.len(), 250);
+        assert_eq!(candles.first().unwrap().ts, newest - 249 * 60_000);
+        assert_eq!(candles.last().unwrap().ts, newest);
+
+        let targets: Vec<_> = mock.requests().into_iter().map(|r| r.target).collect();
+        assert_eq!(
+            targets,
+            vec![
+                "/api/v5/market/history-candles?instId=BTC-USDT&bar=1m&limit=100".to_string(),
+                format!("/api/v5/market/history-candles?instId=BTC-USDT&bar=1m&limit=100&after={}", newest - 99 * 60_000),
+                format!("/api/v5/market/history-candles?instId=BTC-USDT&bar=1m&limit=50&after={}", newest - 199 * 60_000),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn paginate_stops_when_history_runs_out() {
+        let newest = 100 * 60_000 * 60;
+        let mock = MockRest::start(vec![
+            MockRest::ok(candle_page(newest, 100)),
+            MockRest::ok(candle_page(newest - 100 * 60_000, 30)),
+            MockRest::ok(serde_json::json!([])),
+        ])
+        .await;
+        let candles = client(&mock).candles("BTC-USDT", Bar::M1, 500).await.unwrap();
+        assert_eq!(candles.len(), 130);
+        assert_eq!(mock.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn system_time_reads_okx_clock() {
+        let mock = MockRest::start(vec![MockRest::ok(serde_json::json!([{ "ts": "1597026383085" }])), MockRest::ok(serde_json::json!([]))]).await;
+        assert_eq!(client(&mock).system_time().await.unwrap(), 1597026383085);
+        assert!(matches!(client(&mock).system_time().await, Err(OkxError::Empty)));
+        assert_eq!(mock.requests()[0].target, "/api/v5/public/time");
+    }
+
+    fn ticker_row(inst_id: &str, bid: &str, ask: &str) -> serde_json::Value {
+        serde_json::json!({
+            "instType": "SWAP", "instId": inst_id, "last": bid, "lastSz": "1", "askPx": ask, "askSz": "11", "bidPx": bid, "bidSz": "5",
+            "open24h": "9000", "high24h": "10100", "low24h": "8888.88", "volCcy24h": "2.222", "vol24h": "2222", "ts": "1597026383085",
+        })
+    }
+
+    #[tokio::test]
+    async fn ticker_is_one_row_or_an_error() {
+        let mock = MockRest::start(vec![
+            MockRest::ok(serde_json::json!([ticker_row("BTC-USDT-SWAP", "9999.5", "10000.5")])),
+            MockRest::ok(serde_json::json!([])),
+            error(rest_codes::UNKNOWN_INSTRUMENT, "Instrument ID doesn't exist"),
+        ])
+        .await;
+        let ticker = client(&mock).ticker("BTC-USDT-SWAP").await.unwrap();
+        assert_eq!((ticker.inst_id.as_str(), ticker.bid, ticker.ask), ("BTC-USDT-SWAP", 9999.5, 10000.5));
+        assert_eq!(mock.requests()[0].target, "/api/v5/market/ticker?instId=BTC-USDT-SWAP");
+
+        assert!(matches!(client(&mock).ticker("BTC-USDT-SWAP").await, Err(OkxError::Empty)));
+        assert!(client(&mock).ticker("BTC-USDC-SWAP").await.unwrap_err().is_unknown_instrument());
+    }
+
+    #[tokio::test]
+    async fn tickers_list_a_whole_type() {
+        let rows = serde_json::json!([ticker_row("BTC-USDT-SWAP", "9999.5", "10000.5"), ticker_row("ETH-USDT-SWAP", "2999.5", "3000.5")]);
+        let mock = MockRest::start(vec![MockRest::ok(rows)]).await;
+        let tickers = client(&mock).tickers(InstType::Swap).await.unwrap();
+        let ids: Vec<_> = tickers.iter().map(|t| t.inst_id.as_str()).collect();
+        assert_eq!(ids, vec!["BTC-USDT-SWAP", "ETH-USDT-SWAP"]);
+        assert_eq!(mock.requests()[0].target, "/api/v5/market/tickers?instType=SWAP");
+    }
+
+    #[tokio::test]
+    async fn order_book_is_one_snapshot_at_most_400_deep() {
+        let snapshot = serde_json::json!([{
+            "asks": [["10000.5", "11", "0", "2"]],
+            "bids": [["9999.5", "5", "0", "1"], ["9999.4", "3", "0", "1"]],
+            "ts": "1597026383085",
+        }]);
+        let mock = MockRest::start(vec![
+            MockRest::ok(snapshot),
+            MockRest::ok(serde_json::json!([])),
+            error(rest_codes::UNKNOWN_INSTRUMENT, "Instrument ID doesn't exist"),
+        ])
+        .await;
+        let book = client(&mock).order_book("BTC-USDT-SWAP", 1000).await.unwrap();
+        assert_eq!((book.bids.len(), book.asks.len(), book.ts.as_str()), (2, 1, "1597026383085"));
+        assert_eq!(mock.requests()[0].target, "/api/v5/market/books?instId=BTC-USDT-SWAP&sz=400");
+
+        assert!(matches!(client(&mock).order_book("BTC-USDT-SWAP", 5).await, Err(OkxError::Empty)));
+        assert!(client(&mock).order_book("BTC-USDC-SWAP", 5).await.unwrap_err().is_unknown_instrument());
+    }
+
+    #[tokio::test]
+    async fn mark_prices_by_type_or_instrument() {
+        let row = |inst_id: &str, px: &str| serde_json::json!({ "instType": "SWAP", "instId": inst_id, "markPx": px, "ts": "1630049139746" });
+        let mock = MockRest::start(vec![
+            MockRest::ok(serde_json::json!([row("BTC-USDT-SWAP", "42310.6"), row("ETH-USDT-SWAP", "")])),
+            MockRest::ok(serde_json::json!([row("BTC-USDT-SWAP", "42310.6")])),
+        ])
+        .await;
+        // rows without a price are left out
+        let marks = client(&mock).mark_prices(InstType::Swap, None).await.unwrap();
+        assert_eq!(marks.len(), 1);
+        let marks = client(&mock).mark_prices(InstType::Swap, Some("BTC-USDT-SWAP")).await.unwrap();
+        assert_eq!((marks[0].inst_id.as_str(), marks[0].price), ("BTC-USDT-SWAP", 42310.6));
+
+        let targets: Vec<_> = mock.requests().into_iter().map(|r| r.target).collect();
+        assert_eq!(targets, vec!["/api/v5/public/mark-price?instType=SWAP", "/api/v5/public/mark-price?instType=SWAP&instId=BTC-USDT-SWAP"]);
+    }
+
+    #[tokio::test]
+    async fn funding_history_comes_back_oldest_first() {
+        let row = |time: u64, rate: &str, realized: &str| {
+            serde_json::json!({
+                "instType": "SWAP", "instId": "BTC-USDT-SWAP", "fundingRate": rate, "realizedRate": realized,
+                "fundingTime": time.to_string(), "method": "current_period",
+            })
+        };
+        let newest = 1_700_006_400_000u64;
+        let mock = MockRest::start(vec![MockRest::ok(serde_json::json!([
+            row(newest, "0.0001", "0.00009"),
+            row(newest - 28_800_000, "-0.0002", "-0.0002"),
+        ]))])
+        .await;
+        let history = client(&mock).funding_history("BTC-USDT-SWAP", 2).await.unwrap();
+        let settled: Vec<_> = history.iter().map(|p| (p.funding_time, p.rate, p.realized_rate)).collect();
+        assert_eq!(settled, vec![(newest - 28_800_000, -0.0002, -0.0002), (newest, 0.0001, 0.00009)]);
+        assert_eq!(mock.requests()[0].target, "/api/v5/public/funding-rate-history?instId=BTC-USDT-SWAP&limit=2");
+    }
+}
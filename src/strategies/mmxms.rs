@@ -1,9 +1,8 @@
 use std::time::Instant;
 
-use reqwest::Client;
-use serde::Deserialize;
 use async_trait::async_trait;
 
+use crate::kline::{Bar, BarStore, Candle};
 use crate::strategy::{OrderRequest, Side, Strategy, OrderFill};
 use crate::orderbook::OrderBook;
 
@@ -39,23 +38,17 @@ pub struct MMXMStrategy {
     // account
     account_balance: f64,
 
-    // HTTP client for klines
-    http: Client,
+    // HTF candles for PD arrays
     symbol: String,
-    htf_bar: String,
+    htf_bar: Bar,
     htf_hours: usize,
 }
 
-#[derive(Deserialize)]
-struct OkxKlineResponse {
-    data: Vec<[String; 6]>, // [ts, open, high, low, close, vol]
-}
-
 impl MMXMStrategy {
     pub fn new(
         symbol: impl Into<String>,
         start_balance: f64,
-        htf_bar: impl Into<String>,
+        htf_bar: Bar,
         htf_hours: usize,
     ) -> Self {
         Self {
@@ -71,60 +64,33 @@ impl MMXMStrategy {
             open_orders: Vec::new(),
             closed_pnl: Vec::new(),
             account_balance: start_balance,
-            http: Client::new(),
             symbol: symbol.into(),
-            htf_bar: htf_bar.into(),
+            htf_bar,
             htf_hours,
         }
     }
 
-    /// Fetch klines & compute support/resistance/SMA
-    async fn update_pd_arrays(&mut self) {
-        let limit = self.htf_hours * 60 / 
-            match &*self.htf_bar {
-                "1H" => 60,
-                "4H" => 240,
-                _    => 20
-            };
-        let url = format!(
-            "https://www.okx.com/api/v5/market/history-candles?instId={}&bar={}&limit={}",
-            self.symbol, self.htf_bar, limit
-        );
-
-        if let Ok(resp) = self.http.get(&url).send().await {
-            if let Ok(parsed_resp) = resp.json::<OkxKlineResponse>().await {
-                let mut highs = Vec::new();
-                let mut lows  = Vec::new();
-                let mut closes = Vec::new();
-
-                for entry in parsed_resp.data.iter().rev() {
-                let high: f64 = entry[2].parse().unwrap_or(0.0);
-                let low:  f64 = entry[3].parse().unwrap_or(0.0);
-                let close:f64 = entry[4].parse().unwrap_or(0.0);
-                highs.push(high);
-                lows.push(low);
-                closes.push(close);
-            }
+    /// Support/resistance/SMA over the last `htf_hours` of closed HTF bars
+    fn update_pd_arrays(&mut self, bars: &BarStore) {
+        let n = self.htf_bar.bars_in_hours(self.htf_hours);
+        let candles = bars.closed(&self.symbol, self.htf_bar, n);
+        if candles.is_empty() {
+            return;
+        }
 
-            // simple SMA
-            let sma = closes.iter().sum::<f64>() / closes.len() as f64;
-
-            self.support   = lows.iter().cloned().fold(None, |min, x| {
-                Some(min.map_or(x, |m| m.min(x)))
-            });
-            self.resistance= highs.iter().cloned().fold(None, |max, x| {
-                Some(max.map_or(x, |m| m.max(x)))
-            });
-            if let Some(s) = self.support {
-                self.is_bullish = closes.last().copied().unwrap_or(0.0) > sma;
-                println!(
-                    "🗺 PD Arrays S={:.5}, R={:.5}, Bullish={} SMA={:.5}",
-                    s, self.resistance.unwrap_or(0.0), self.is_bullish, sma
-                );
-            }
+        // simple SMA
+        let sma = candles.iter().map(|c| c.close).sum::<f64>() / candles.len() as f64;
+
+        self.support = candles.iter().map(|c| c.low).reduce(f64::min);
+        self.resistance = candles.iter().map(|c| c.high).reduce(f64::max);
+        if let Some(s) = self.support {
+            self.is_bullish = candles.last().map_or(0.0, |c| c.close) > sma;
+            println!(
+                "🗺 PD Arrays S={:.5}, R={:.5}, Bullish={} SMA={:.5}",
+                s, self.resistance.unwrap_or(0.0), self.is_bullish, sma
+            );
         }
     }
-}
 
     /// 90th-percentile threshold
     fn quantile_90(qs: &mut [f64]) -> f64 {
@@ -166,7 +132,7 @@ impl MMXMStrategy {
     }
 
     fn place_order(&mut self, side: Side, price: f64, size: f64) {
-        self.open_orders.push(OrderRequest { side, price, size });
+        self.open_orders.push(OrderRequest::new(side, price, size));
         println!("📤 PLACE {:?} @ {:.5} x {:.5}", side, price, size);
     }
 
@@ -177,16 +143,18 @@ impl MMXMStrategy {
 #[async_trait::async_trait]
 #[async_trait]
 impl Strategy for MMXMStrategy {
-    fn on_timer(&mut self, _now: Instant) -> Vec<OrderRequest> {
-        // refresh PD arrays every hour
-        futures::executor::block_on(self.update_pd_arrays());
+    fn on_candle(&mut self, inst_id: &str, bar: Bar, candle: &Candle, bars: &BarStore) -> Vec<OrderRequest> {
+        // refresh PD arrays on every closed HTF bar (and on the first one seen after backfill)
+        if inst_id == self.symbol && bar == self.htf_bar && (candle.confirmed || self.support.is_none()) {
+            self.update_pd_arrays(bars);
+        }
         Vec::new()
     }
 
     fn on_order_book(&mut self, bids: &OrderBook, asks: &OrderBook) -> Vec<OrderRequest> {
         // store snapshot for detectors
-        self.last_bids = bids.bids.iter().map(|(k, v)| (k.into_inner(), *v)).collect();
-        self.last_asks = asks.asks.iter().map(|(k, v)| (k.into_inner(), *v)).collect();
+        self.last_bids = bids.bid_levels().collect();
+        self.last_asks = asks.ask_levels().collect();
         Vec::new()
     }
 
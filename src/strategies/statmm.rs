@@ -54,12 +54,30 @@ Usage
 
 */
 
+use std::collections::VecDeque;
 use std::time::Instant;
 use crate::{
-    orderbook::OrderBook,
+    orderbook::{signals::StoikovMicroprice, BookSide, OrderBook},
+    market::{FundingRate, IndexPrice, MarkPrice, Trade},
     strategy::{Strategy, Side, OrderRequest, OrderFill},
 };
 
+/// Smoothing factor for the order-flow EWMAs.
+const FLOW_ALPHA: f64 = 0.05;
+
+/// What the quotes are centred on.
+#[derive(Debug, Clone)]
+pub enum QuoteCentre {
+    /// The raw mid passed to `on_price_tick`.
+    Mid,
+    /// Size-weighted microprice of the last book seen.
+    Microprice,
+    /// Microprice weighted by the distance-weighted imbalance of the best `levels` levels.
+    WeightedMicroprice { levels: usize, decay_bps: f64 },
+    /// Stoikov microprice, trained on every book seen.
+    Stoikov(StoikovMicroprice),
+}
+
 pub struct StatMM {
     prices: Vec<f64>,    // rolling buffer of recent mid-prices
     window: usize,       // rolling window size
@@ -69,6 +87,19 @@ pub struct StatMM {
     kappa: f64,          // fill‐rate sensitivity
     T: f64,              // quoting horizon (in same units as timestamps)
     inventory: f64,      // current net position
+    centre: QuoteCentre, // fair value the quotes are centred on
+    fair: Option<f64>,   // latest book-derived fair value, if `centre` uses one
+    adaptive_kappa: bool,     // re-estimate kappa from trade prints
+    trade_dists: VecDeque<f64>, // rolling |trade price - mid| of recent prints
+    order_flow: f64,          // EWMA of signed taker volume (+ buys, - sells)
+    flow_volume: f64,         // EWMA of taker volume
+    flow_skew: f64,           // weight of order flow in the quote centre (0 = off)
+    funding_skew: f64,        // weight of funding carry in the quote centre (0 = off)
+    funding_rate: Option<f64>, // latest swap funding rate
+    mark: Option<f64>,        // latest mark price
+    index: Option<f64>,       // latest index price of the underlying
+    depth_cap: Option<(f64, f64)>, // (bps, share): quote at most `share` of the size within `bps` of mid
+    near_depth: Option<(f64, f64)>, // (bid, ask) size within the cap's band on the last book
 }
 
 impl StatMM {
@@ -82,8 +113,59 @@ impl StatMM {
             kappa,
             T,
             inventory: 0.0,
+            centre: QuoteCentre::Mid,
+            fair: None,
+            adaptive_kappa: false,
+            trade_dists: VecDeque::with_capacity(window),
+            order_flow: 0.0,
+            flow_volume: 0.0,
+            flow_skew: 0.0,
+            funding_skew: 0.0,
+            funding_rate: None,
+            mark: None,
+            index: None,
+            depth_cap: None,
+            near_depth: None,
         }
     }
+
+    /// Shift the quote centre by `weight × funding rate × mark price`, i.e. the
+    /// carry one unit pays (long, positive funding) or earns per funding period,
+    /// so inventory builds on the side that collects funding. The index stands
+    /// in for the mark until one arrives.
+    pub fn with_funding_skew(mut self, weight: f64) -> Self {
+        self.funding_skew = weight;
+        self
+    }
+
+    /// Shift the quote centre by `weight × σ × flow`, where flow is signed over
+    /// total taker volume (−1 when every print hits the bid, +1 when every one
+    /// lifts the offer), so quotes lean the way the prints are pushing the price.
+    pub fn with_flow_skew(mut self, weight: f64) -> Self {
+        self.flow_skew = weight;
+        self
+    }
+
+    /// Re-estimate `kappa` from public trades instead of keeping it fixed.
+    /// With fill intensity λ(δ) = A·e^(−κδ), distances of prints from the mid are
+    /// exponential with rate κ, so the MLE over the window is κ = 1 / mean(δ).
+    pub fn with_adaptive_kappa(mut self) -> Self {
+        self.adaptive_kappa = true;
+        self
+    }
+
+    pub fn with_centre(mut self, centre: QuoteCentre) -> Self {
+        self.centre = centre;
+        self
+    }
+
+    /// Quote at most `share` of the size resting within `bps` of the mid on
+    /// each side, so our quotes never dominate thin books.
+    pub fn with_depth_cap(mut self, bps: f64, share: f64) -> Self {
+        self.depth_cap = Some((bps, share));
+        self
+    }
+
     fn fit_vol(&mut self) {
         let n = self.prices.len() as f64;
         let mean = self.prices.iter().sum::<f64>() / n;
@@ -151,11 +233,25 @@ impl Strategy for StatMM {
         // 5) Compute offsets
         let (δ_bid, δ_ask) = self.ao_offsets();
 
-        println!("StatMM: σ {:.5} δ_bid {:.5} δ_ask {:.5}", self.sigma, δ_bid, δ_ask);
+        println!(
+            "StatMM: σ {:.5} δ_bid {:.5} δ_ask {:.5} κ {:.5} flow {:.5}",
+            self.sigma, δ_bid, δ_ask, self.kappa, self.order_flow
+        );
 
-        // 6) Center around *current* mid (= price)
-        let bid_price = price - δ_bid;
-        let ask_price = price + δ_ask;
+        // 6) Center around the current mid (= price), or the book's fair value if configured,
+        //    shifted against funding carry and towards order flow
+        let centre = match self.centre {
+            QuoteCentre::Mid => price,
+            _ => self.fair.unwrap_or(price),
+        };
+        let carry = match self.funding_rate {
+            Some(rate) => self.funding_skew * rate * self.mark.or(self.index).unwrap_or(centre),
+            None => 0.0,
+        };
+        let flow = if self.flow_volume > 0.0 { self.order_flow / self.flow_volume } else { 0.0 };
+        let centre = centre - carry + self.flow_skew * self.sigma * flow;
+        let bid_price = centre - δ_bid;
+        let ask_price = centre + δ_ask;
 
         println!("StatMM: bid {:.5} ask {:.5}", bid_price, ask_price);
 
@@ -164,12 +260,75 @@ impl Strategy for StatMM {
             return Vec::new();
         }
 
-        // 7) Emit both sides
-        let size = 1.0;
-        vec![
-            OrderRequest { side: Side::Buy,  price: bid_price, size },
-            OrderRequest { side: Side::Sell, price: ask_price, size },
-        ]
+        // 7) Emit both sides, capped to the size near the touch
+        let size: f64 = 1.0;
+        let (bid_size, ask_size) = match (self.depth_cap, self.near_depth) {
+            (Some((_, share)), Some((bids, asks))) => (size.min(share * bids), size.min(share * asks)),
+            _ => (size, size),
+        };
+        [(Side::Buy, bid_price, bid_size), (Side::Sell, ask_price, ask_size)]
+            .into_iter()
+            .filter(|&(_, _, size)| size > 0.0)
+            .map(|(side, price, size)| OrderRequest::new(side, price, size))
+            .collect()
+    }
+
+    /// Track the book-derived fair value used to centre quotes, and the depth quotes are capped to
+    fn on_order_book(&mut self, book: &OrderBook, _asks: &OrderBook) -> Vec<OrderRequest> {
+        self.near_depth = self.depth_cap.and_then(|(bps, _)| {
+            Some((book.depth_within_bps(BookSide::Bid, bps)?, book.depth_within_bps(BookSide::Ask, bps)?))
+        });
+        self.fair = match &mut self.centre {
+            QuoteCentre::Mid => None,
+            QuoteCentre::Microprice => book.microprice(),
+            QuoteCentre::WeightedMicroprice { levels, decay_bps } => book.weighted_microprice(*levels, *decay_bps),
+            QuoteCentre::Stoikov(model) => {
+                model.observe(book);
+                model.microprice(book)
+            }
+        };
+        Vec::new()
+    }
+
+    /// Track order flow and, if enabled, the fill-rate sensitivity from prints
+    fn on_trade(&mut self, trade: &Trade) -> Vec<OrderRequest> {
+        let signed = match trade.side {
+            Side::Buy => trade.size,
+            Side::Sell => -trade.size,
+        };
+        // an aggregated print stands for `count` fills: decay as if each came on its own, sharing the size
+        let fills = trade.count.max(1);
+        let decay = (1.0 - FLOW_ALPHA).powi(fills as i32);
+        let share = (1.0 - decay) / fills as f64;
+        self.order_flow = share * signed + decay * self.order_flow;
+        self.flow_volume = share * trade.size + decay * self.flow_volume;
+
+        if let Some(&mid) = self.prices.last() {
+            self.trade_dists.push_back((trade.price - mid).abs());
+            if self.trade_dists.len() > self.window {
+                self.trade_dists.pop_front();
+            }
+            let mean = self.trade_dists.iter().sum::<f64>() / self.trade_dists.len() as f64;
+            if self.adaptive_kappa && self.trade_dists.len() == self.window && mean > 0.0 {
+                self.kappa = 1.0 / mean;
+            }
+        }
+        Vec::new()
+    }
+
+    fn on_mark_price(&mut self, mark: &MarkPrice) -> Vec<OrderRequest> {
+        self.mark = Some(mark.price);
+        Vec::new()
+    }
+
+    fn on_index_price(&mut self, index: &IndexPrice) -> Vec<OrderRequest> {
+        self.index = Some(index.price);
+        Vec::new()
+    }
+
+    fn on_funding_rate(&mut self, funding: &FundingRate) -> Vec<OrderRequest> {
+        self.funding_rate = Some(funding.rate);
+        Vec::new()
     }
 
     /// When an order actually fills, update inventory
@@ -1,9 +1,14 @@
 use std::time::Instant;
 
-use crate::orderbook::OrderBook;
+use crate::account::{AccountUpdate, OrderUpdate, Position};
+use crate::instruments::InstrumentSpec;
+use crate::kline::{Bar, BarStore, Candle};
+use crate::market::{FundingRate, IndexPrice, MarkPrice, OpenInterest, Ticker, Trade};
+use crate::oms::{ManagedOrder, OrderAction};
+use crate::orderbook::{manager::BookManager, BookStatus, OrderBook};
 
 // Reusable order and fill types
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Side { Buy, Sell }
 
 #[derive(Debug, Clone)]
@@ -11,6 +16,21 @@ pub struct OrderRequest {
     pub side: Side,
     pub price: f64,
     pub size: f64,
+    /// `price` and `size` on the instrument's grid, once normalised.
+    pub grid: Option<GridPoint>,
+}
+
+impl OrderRequest {
+    pub fn new(side: Side, price: f64, size: f64) -> Self {
+        Self { side, price, size, grid: None }
+    }
+}
+
+/// A price and size as whole ticks and lots.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct GridPoint {
+    pub ticks: i64,
+    pub lots: i64,
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -28,8 +48,42 @@ pub trait Strategy {
     fn on_price_tick(&mut self, price: f64, now: Instant) -> Vec<OrderRequest> { Vec::new() }
     /// Called whenever you get a new full or incremental order‐book snapshot
     fn on_order_book(&mut self, bids: &OrderBook, asks: &OrderBook) -> Vec<OrderRequest> { Vec::new() }
+    /// Called when the book for `inst_id` changes; `books` gives access to every tracked instrument.
+    /// Defaults to `on_order_book` with the updated book.
+    fn on_book_update(&mut self, inst_id: &str, books: &BookManager) -> Vec<OrderRequest> {
+        match books.get(inst_id) {
+            Some(book) => self.on_order_book(book, book),
+            None => Vec::new(),
+        }
+    }
     /// Called on every timer tick (e.g. 1s, 5s) if you need periodic work
     fn on_timer(&mut self, now: Instant) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every public trade print
+    fn on_trade(&mut self, _trade: &Trade) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every 24h ticker / top-of-book snapshot
+    fn on_ticker(&mut self, _ticker: &Ticker) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every mark price update (derivatives only)
+    fn on_mark_price(&mut self, _mark: &MarkPrice) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every index price update for the instrument's underlying
+    fn on_index_price(&mut self, _index: &IndexPrice) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every funding rate update (perpetual swaps only)
+    fn on_funding_rate(&mut self, _funding: &FundingRate) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every open interest update (derivatives only)
+    fn on_open_interest(&mut self, _oi: &OpenInterest) -> Vec<OrderRequest> { Vec::new() }
+    /// Called on every `candle*` push once `bars` has been updated with it; `candle.confirmed` marks a closed bar
+    fn on_candle(&mut self, _inst_id: &str, _bar: Bar, _candle: &Candle, _bars: &BarStore) -> Vec<OrderRequest> { Vec::new() }
     /// Called whenever an order is filled
     fn on_order_filled(&mut self, fill: OrderFill) {}
+    /// Called on every state change of one of our orders on this instrument (before `on_order_filled` for fills)
+    fn on_order_update(&mut self, _update: &OrderUpdate) {}
+    /// Called on every timer tick with this strategy's working orders (live trading only); return cancels/amends
+    fn manage_orders(&mut self, _open: &[&ManagedOrder]) -> Vec<OrderAction> { Vec::new() }
+    /// Called whenever our position in this instrument changes
+    fn on_position(&mut self, _position: &Position) {}
+    /// Called on account balance changes (shared by every strategy)
+    fn on_account(&mut self, _account: &AccountUpdate) {}
+    /// Called when the book becomes invalid (gap, checksum failure) and again once it has been rebuilt
+    fn on_book_status(&mut self, _inst_id: &str, _status: BookStatus) {}
+    /// Called when a refresh changes this instrument's metadata (tick/lot size, limits, state)
+    fn on_instrument(&mut self, _spec: &InstrumentSpec) {}
 }
\ No newline at end of file